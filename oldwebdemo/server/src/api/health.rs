@@ -0,0 +1,33 @@
+//! actix-web 3 的健康检查路由。`health` 的路由是 actix-web 4 的，这里只用它不依赖框架的
+//! 检查和响应体，接口和 webdemo 里的服务一致。
+
+use actix_web::{http::StatusCode, web, HttpResponse};
+use ::health::Health;
+
+/// 注册 `/healthz`、`/readyz` 和 `/version`：`App::new().configure(health::configure(health.clone()))`
+pub fn configure(health: Health) -> impl FnOnce(&mut web::ServiceConfig) {
+    move |cfg| {
+        cfg.data(health)
+            .route("/healthz", web::get().to(healthz))
+            .route("/readyz", web::get().to(readyz))
+            .route("/version", web::get().to(version));
+    }
+}
+
+async fn healthz(health: web::Data<Health>) -> HttpResponse {
+    HttpResponse::Ok().json(health.live())
+}
+
+async fn readyz(health: web::Data<Health>) -> HttpResponse {
+    let (ready, body) = health.readiness().await;
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    HttpResponse::build(status).json(body)
+}
+
+async fn version(health: web::Data<Health>) -> HttpResponse {
+    HttpResponse::Ok().json(health.build())
+}
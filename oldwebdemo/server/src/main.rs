@@ -1,14 +1,26 @@
 use actix_web::{App, HttpServer};
+use sqlx::{Connection, SqliteConnection};
 
 mod api;
 
 use api::varia::*;
 use api::visitor::*;
 
+/// 就绪检查：数据库能连上
+async fn check_database() -> Result<(), String> {
+    let conn = SqliteConnection::connect("sqlite:exert.db")
+        .await
+        .map_err(|e| e.to_string())?;
+    conn.close().await.map_err(|e| e.to_string())
+}
+
 #[actix_rt::main]
 async fn main() -> std::io::Result<()> {
-    HttpServer::new(|| {
+    let health = health::Health::new(health::build_info!()).check("database", check_database);
+
+    HttpServer::new(move || {
         App::new()
+            .configure(api::health::configure(health.clone()))
             .service(do_d)
             .service(index)
             .service(user_index)
@@ -0,0 +1,37 @@
+use std::path::PathBuf;
+
+use clap::{CommandFactory, FromArgMatches, Parser};
+
+use crate::config::LogFormat;
+
+/// 各个服务共用的命令行参数，优先级最高
+#[derive(Debug, Default, Parser)]
+#[command(about = "webdemo server")]
+pub struct ServerArgs {
+    /// 配置文件，默认读取 $<NAME>_CONFIG 或 ./<name>/config.toml
+    #[arg(short, long)]
+    pub config: Option<PathBuf>,
+    /// 监听地址，可以重复，例如 --bind 127.0.0.1:8080
+    #[arg(long)]
+    pub bind: Vec<String>,
+    #[arg(long)]
+    pub workers: Option<usize>,
+    #[arg(long)]
+    pub log_level: Option<String>,
+    #[arg(long, value_enum)]
+    pub log_format: Option<LogFormat>,
+    /// 覆盖任意配置项，例如 --set server.workers=8 --set 'cors.allowed_origins=["http://localhost:3000"]'
+    #[arg(long = "set", value_name = "KEY=VALUE")]
+    pub set: Vec<String>,
+    /// 打印合并后的配置并退出
+    #[arg(long)]
+    pub print_config: bool,
+}
+
+impl ServerArgs {
+    /// 解析命令行，帮助信息中使用服务的名字
+    pub fn parse(name: &'static str) -> Self {
+        let matches = Self::command().name(name).bin_name(name).get_matches();
+        Self::from_arg_matches(&matches).unwrap_or_else(|e| e.exit())
+    }
+}
@@ -0,0 +1,367 @@
+use std::{
+    env, fs, io,
+    net::ToSocketAddrs,
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tracing_subscriber::EnvFilter;
+
+use crate::{args::ServerArgs, error::Error};
+
+/// 服务的配置，包含公共的 `[server]` 和 `[log]` 两节，由 [`Loader`] 读取
+pub trait AppConfig: DeserializeOwned {
+    fn server(&self) -> &ServerConfig;
+
+    fn log(&self) -> &LogConfig;
+
+    /// 服务自己的校验，把错误加入 `errors`，和公共部分的错误一起报告
+    fn validate(&self, _errors: &mut Vec<String>) {}
+}
+
+/// 只有公共部分的配置，没有其他配置项的服务直接使用
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BaseConfig {
+    pub server: ServerConfig,
+    pub log: LogConfig,
+}
+
+impl BaseConfig {
+    /// 默认配置，只指定 HTTP 监听地址
+    pub fn new(bind: &str) -> Self {
+        Self {
+            server: ServerConfig::new(bind),
+            log: LogConfig::default(),
+        }
+    }
+}
+
+impl AppConfig for BaseConfig {
+    fn server(&self) -> &ServerConfig {
+        &self.server
+    }
+
+    fn log(&self) -> &LogConfig {
+        &self.log
+    }
+}
+
+/// `[server]`：监听地址、worker 数量和停止
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// HTTP 监听地址
+    pub bind: Vec<String>,
+    pub workers: usize,
+    /// 收到 SIGINT / SIGTERM 后等待处理中的请求完成的时间，超时强制关闭连接
+    pub shutdown_timeout_secs: u64,
+    /// 注册 `/healthz`、`/readyz` 和 `/version`
+    pub health: bool,
+    pub tls: TlsConfig,
+}
+
+impl ServerConfig {
+    pub fn new(bind: &str) -> Self {
+        Self {
+            bind: vec![bind.to_owned()],
+            ..Self::default()
+        }
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: Vec::new(),
+            workers: 2,
+            shutdown_timeout_secs: 30,
+            health: true,
+            tls: TlsConfig::default(),
+        }
+    }
+}
+
+/// `[server.tls]`：HTTPS 监听
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    pub enabled: bool,
+    pub bind: Vec<String>,
+    /// PEM 证书链
+    pub cert: PathBuf,
+    /// PEM 私钥（PKCS#8、RSA 或 EC）
+    pub key: PathBuf,
+}
+
+impl TlsConfig {
+    pub fn server_config(&self) -> io::Result<rustls::ServerConfig> {
+        let read = |path: &Path| {
+            fs::File::open(path)
+                .map(io::BufReader::new)
+                .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", path.display())))
+        };
+
+        let certs = rustls_pemfile::certs(&mut read(&self.cert)?)?
+            .into_iter()
+            .map(rustls::Certificate)
+            .collect();
+        let key = rustls_pemfile::read_all(&mut read(&self.key)?)?
+            .into_iter()
+            .find_map(|item| match item {
+                rustls_pemfile::Item::PKCS8Key(key) | rustls_pemfile::Item::RSAKey(key) | rustls_pemfile::Item::ECKey(key) => {
+                    Some(rustls::PrivateKey(key))
+                }
+                _ => None,
+            })
+            .ok_or_else(|| invalid(format!("no private key in {}", self.key.display())))?;
+
+        rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .map_err(invalid)
+    }
+}
+
+/// `[log]`：日志和链路追踪
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// tracing 过滤规则，`RUST_LOG` 优先
+    pub level: String,
+    pub format: LogFormat,
+    /// 按 OTLP/JSON 格式追加 span 的文件，可以由 collector 的 otlpjsonfile receiver 读取
+    pub otlp_file: Option<PathBuf>,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_owned(),
+            format: LogFormat::Json,
+            otlp_file: None,
+        }
+    }
+}
+
+/// 日志格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// 每行一个 JSON 对象，带上当前请求 span 的字段
+    Json,
+    Text,
+}
+
+/// 按默认值、配置文件、环境变量、命令行参数的顺序合并配置。
+///
+/// - 配置文件：`--config`，或 `<NAME>_CONFIG` 环境变量，都必须存在；
+///   否则读取 `./<name>/config.toml`（相对 webdemo 工作区目录），不存在时跳过
+/// - 环境变量：`<NAME>_` 前缀，`__` 分隔嵌套的键，例如 `WEB1_SERVER__WORKERS=8`
+/// - 命令行：`--bind`、`--workers`、`--log-level`、`--log-format` 和 `--set KEY=VALUE`
+pub struct Loader {
+    name: &'static str,
+    defaults: toml::Value,
+    redact: Vec<&'static str>,
+}
+
+impl Loader {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            defaults: toml::Value::Table(Default::default()),
+            redact: Vec::new(),
+        }
+    }
+
+    /// 默认值，通常是配置类型的 `Default`，`--print-config` 会完整打印出来
+    pub fn defaults(mut self, defaults: impl Serialize) -> Self {
+        let value = toml::Value::try_from(defaults).expect("defaults must serialize to a TOML table");
+        merge(&mut self.defaults, value);
+        self
+    }
+
+    /// `--print-config` 时隐藏的配置项，例如 `auth.jwt_secret`
+    pub fn redact(mut self, key: &'static str) -> Self {
+        self.redact.push(key);
+        self
+    }
+
+    /// 合并各层配置并校验，一次报告所有错误。`--print-config` 时打印合并结果并退出。
+    pub fn load<T: AppConfig>(&self, args: &ServerArgs) -> Result<T, Error> {
+        let value = self.merged(args)?;
+        let config: T = value
+            .clone()
+            .try_into()
+            .map_err(|e: toml::de::Error| Error::config(format!("invalid config: {}", e.to_string().trim_end())))?;
+
+        let mut errors = Vec::new();
+        validate_server(config.server(), &mut errors);
+        validate_log(config.log(), &mut errors);
+        config.validate(&mut errors);
+        if !errors.is_empty() {
+            return Err(Error::config(format!("invalid config:\n  {}", errors.join("\n  "))));
+        }
+
+        if args.print_config {
+            print!("{}", self.to_toml(value));
+            std::process::exit(0);
+        }
+        Ok(config)
+    }
+
+    fn merged(&self, args: &ServerArgs) -> Result<toml::Value, Error> {
+        let config_env = self.env_name("CONFIG");
+        let default_path = PathBuf::from(format!("./{}/config.toml", self.name));
+        let mut value = self.defaults.clone();
+
+        let file = match (&args.config, env::var_os(&config_env)) {
+            (Some(path), _) => Some(path.clone()),
+            (None, Some(path)) => Some(PathBuf::from(path)),
+            (None, None) if default_path.exists() => Some(default_path),
+            (None, None) => None,
+        };
+        if let Some(path) = file {
+            let text = fs::read_to_string(&path)
+                .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", path.display())))?;
+            let file: toml::Value = toml::from_str(&text)
+                .map_err(|e| Error::config(format!("invalid config {}: {e}", path.display())))?;
+            merge(&mut value, file);
+        }
+
+        let prefix = self.env_name("");
+        let mut vars: Vec<_> = env::vars()
+            .filter(|(name, _)| name.starts_with(&prefix) && *name != config_env)
+            .collect();
+        vars.sort();
+        for (name, raw) in vars {
+            let key = name[prefix.len()..].to_ascii_lowercase().replace("__", ".");
+            set(&mut value, &key, &raw).map_err(|e| Error::config(format!("{name}: {e}")))?;
+        }
+
+        if !args.bind.is_empty() {
+            let bind = args.bind.iter().cloned().map(toml::Value::String).collect();
+            set_value(&mut value, "server.bind", toml::Value::Array(bind))?;
+        }
+        if let Some(workers) = args.workers {
+            set_value(&mut value, "server.workers", toml::Value::Integer(workers as i64))?;
+        }
+        if let Some(level) = &args.log_level {
+            set_value(&mut value, "log.level", toml::Value::String(level.clone()))?;
+        }
+        if let Some(format) = args.log_format {
+            set_value(&mut value, "log.format", toml::Value::try_from(format).map_err(invalid)?)?;
+        }
+        for item in &args.set {
+            let (key, raw) = item
+                .split_once('=')
+                .ok_or_else(|| Error::config(format!("--set {item}: expected KEY=VALUE")))?;
+            set(&mut value, key.trim(), raw).map_err(|e| Error::config(format!("--set {item}: {e}")))?;
+        }
+        Ok(value)
+    }
+
+    /// `WEB1_CONFIG`、`WEB1_`
+    fn env_name(&self, suffix: &str) -> String {
+        format!("{}_{suffix}", self.name.to_ascii_uppercase().replace('-', "_"))
+    }
+
+    fn to_toml(&self, mut value: toml::Value) -> String {
+        for key in &self.redact {
+            let secret = key.split('.').try_fold(&mut value, |value, part| value.get_mut(part));
+            if let Some(secret) = secret {
+                *secret = toml::Value::String("<redacted>".to_owned());
+            }
+        }
+        toml::to_string_pretty(&value).unwrap_or_default()
+    }
+}
+
+fn validate_server(server: &ServerConfig, errors: &mut Vec<String>) {
+    let check_bind = |errors: &mut Vec<String>, name: &str, addrs: &[String]| {
+        for addr in addrs {
+            if let Err(e) = addr.to_socket_addrs() {
+                errors.push(format!("{name}: {addr}: {e}"));
+            }
+        }
+    };
+    if server.bind.is_empty() && !server.tls.enabled {
+        errors.push("server.bind: at least one address is required".to_owned());
+    }
+    check_bind(errors, "server.bind", &server.bind);
+    if server.tls.enabled {
+        if server.tls.bind.is_empty() {
+            errors.push("server.tls.bind: at least one address is required".to_owned());
+        }
+        check_bind(errors, "server.tls.bind", &server.tls.bind);
+        for (name, path) in [("server.tls.cert", &server.tls.cert), ("server.tls.key", &server.tls.key)] {
+            if !path.is_file() {
+                errors.push(format!("{name}: {} does not exist", path.display()));
+            }
+        }
+    }
+    if server.workers == 0 {
+        errors.push("server.workers: must be at least 1".to_owned());
+    }
+}
+
+fn validate_log(log: &LogConfig, errors: &mut Vec<String>) {
+    if EnvFilter::try_new(&log.level).is_err() {
+        errors.push(format!("log.level: invalid filter {}", log.level));
+    }
+    if let Some(dir) = log.otlp_file.as_deref().and_then(Path::parent) {
+        if !dir.as_os_str().is_empty() && !dir.is_dir() {
+            errors.push(format!("log.otlp_file: directory {} does not exist", dir.display()));
+        }
+    }
+}
+
+fn invalid(e: impl ToString) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+/// 表递归合并，其他值直接覆盖
+fn merge(base: &mut toml::Value, other: toml::Value) {
+    match (base, other) {
+        (toml::Value::Table(base), toml::Value::Table(other)) => {
+            for (key, value) in other {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, other) => *base = other,
+    }
+}
+
+/// 按 TOML 解析值，失败时作为字符串：`true`、`8080`、`["a", "b"]` 或 `0.0.0.0:8080`
+fn set(root: &mut toml::Value, key: &str, raw: &str) -> io::Result<()> {
+    let value = toml::from_str::<toml::Table>(&format!("v = {raw}"))
+        .ok()
+        .and_then(|mut t| t.remove("v"))
+        .unwrap_or_else(|| toml::Value::String(raw.to_owned()));
+    set_value(root, key, value)
+}
+
+fn set_value(root: &mut toml::Value, key: &str, value: toml::Value) -> io::Result<()> {
+    let mut parts: Vec<&str> = key.split('.').collect();
+    let last = parts.pop().filter(|k| !k.is_empty()).ok_or_else(|| invalid("empty key"))?;
+
+    let mut table = root;
+    for part in parts {
+        table = table
+            .as_table_mut()
+            .ok_or_else(|| invalid(format!("{key} is not a table")))?
+            .entry(part)
+            .or_insert_with(|| toml::Value::Table(Default::default()));
+    }
+    table
+        .as_table_mut()
+        .ok_or_else(|| invalid(format!("{key} is not a table")))?
+        .insert(last.to_owned(), value);
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use std::{fmt, io, process};
+
+/// 启动阶段的错误，`main` 中用 [`Error::exit`] 输出并退出
+#[derive(Debug)]
+pub enum Error {
+    /// 配置文件、环境变量或命令行参数有误，退出码 2
+    Config(String),
+    /// 监听端口、读取证书等失败，退出码 1
+    Io(io::Error),
+}
+
+impl Error {
+    pub fn config(message: impl Into<String>) -> Self {
+        Error::Config(message.into())
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Config(_) => 2,
+            Error::Io(_) => 1,
+        }
+    }
+
+    /// 输出 `name: error` 到标准错误并退出。日志可能还没初始化，所以不用 `log`。
+    pub fn exit(self, name: &str) -> ! {
+        eprintln!("{name}: {self}");
+        process::exit(self.exit_code())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Config(message) => f.write_str(message),
+            Error::Io(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Config(_) => None,
+            Error::Io(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
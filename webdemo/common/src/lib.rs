@@ -0,0 +1,30 @@
+//! webdemo 各个服务共用的启动代码：命令行参数、分层配置、日志、HTTP 服务和健康检查。
+//!
+//! ```ignore
+//! #[actix_web::main]
+//! async fn main() {
+//!     if let Err(e) = run().await {
+//!         e.exit("wsserver1");
+//!     }
+//! }
+//!
+//! async fn run() -> Result<(), Error> {
+//!     let args = ServerArgs::parse("wsserver1");
+//!     let config: Config = Loader::new("wsserver1").defaults(Config::default()).load(&args)?;
+//!     Server::new(&config, health::build_info!())?
+//!         .run(move || App::new().service(...))
+//!         .await
+//! }
+//! ```
+
+pub mod args;
+pub mod config;
+pub mod error;
+pub mod logging;
+pub mod server;
+
+pub use args::ServerArgs;
+pub use config::{AppConfig, BaseConfig, Loader, LogConfig, LogFormat, ServerConfig, TlsConfig};
+pub use error::Error;
+pub use health;
+pub use server::Server;
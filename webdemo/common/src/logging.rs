@@ -0,0 +1,223 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, IsTerminal as _, Write as _},
+    path::Path,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rand::RngCore as _;
+use serde_json::{json, Value};
+use tracing::{
+    field::{Field, Visit},
+    span, Subscriber,
+};
+use tracing_subscriber::{
+    layer::{Context, SubscriberExt as _},
+    registry::LookupSpan,
+    util::SubscriberInitExt as _,
+    EnvFilter, Layer,
+};
+
+use crate::config::{LogConfig, LogFormat};
+
+/// 初始化日志：`RUST_LOG` 优先于 `log.level`，`log` 宏的输出也转到 tracing。
+/// 配置了 `log.otlp_file` 时把 span 按 OTLP/JSON 格式追加到文件，`service.name` 为 `service_name`。
+pub fn init(config: &LogConfig, service_name: &str) -> io::Result<()> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(&config.level));
+    let fmt = match config.format {
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .boxed(),
+        LogFormat::Text => tracing_subscriber::fmt::layer()
+            .with_ansi(io::stdout().is_terminal())
+            .boxed(),
+    };
+    let otlp = config
+        .otlp_file
+        .as_deref()
+        .map(|path| OtlpFile::create(path, service_name))
+        .transpose()?;
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt)
+        .with(otlp)
+        .try_init()
+        .map_err(io::Error::other)
+}
+
+/// 把结束的 span 写成 OTLP/JSON（`ExportTraceServiceRequest`，每行一个），
+/// 可以用 OpenTelemetry Collector 的 `otlpjsonfile` receiver 读取。
+struct OtlpFile {
+    file: Mutex<File>,
+    service_name: String,
+}
+
+/// span 开始时记录的数据，保存在 span 的 extensions 中
+struct SpanData {
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+    parent_span_id: Option<[u8; 8]>,
+    start: SystemTime,
+    attributes: Vec<(&'static str, Value)>,
+}
+
+impl OtlpFile {
+    fn create(path: &Path, service_name: &str) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", path.display())))?;
+        Ok(Self {
+            file: Mutex::new(file),
+            service_name: service_name.to_owned(),
+        })
+    }
+
+    fn export(&self, name: &str, data: SpanData, end: SystemTime) {
+        let attribute = |key: &str, value: &Value| {
+            let value = match value {
+                Value::Bool(b) => json!({ "boolValue": b }),
+                Value::Number(n) if n.is_f64() => json!({ "doubleValue": n }),
+                Value::Number(n) => json!({ "intValue": n.to_string() }),
+                value => json!({ "stringValue": value.as_str().map(str::to_owned).unwrap_or_else(|| value.to_string()) }),
+            };
+            json!({ "key": key, "value": value })
+        };
+        let field = |key: &str| data.attributes.iter().find(|(k, _)| *k == key).map(|(_, v)| v);
+        let status = field("status").and_then(Value::as_u64).unwrap_or_default();
+        // 请求 span 按 OpenTelemetry 的习惯命名为 `GET /api/{controller}/{action}.json`
+        let name = match (field("method").and_then(Value::as_str), field("route").and_then(Value::as_str)) {
+            (Some(method), Some("-")) => method.to_owned(),
+            (Some(method), Some(route)) => format!("{method} {route}"),
+            _ => name.to_owned(),
+        };
+
+        let span = json!({
+            "traceId": hex(&data.trace_id),
+            "spanId": hex(&data.span_id),
+            "parentSpanId": data.parent_span_id.as_ref().map(|id| hex(id)).unwrap_or_default(),
+            "name": name,
+            // SPAN_KIND_SERVER / SPAN_KIND_INTERNAL
+            "kind": if data.parent_span_id.is_none() { 2 } else { 1 },
+            "startTimeUnixNano": unix_nanos(data.start),
+            "endTimeUnixNano": unix_nanos(end),
+            "attributes": data.attributes.iter().map(|(k, v)| attribute(k, v)).collect::<Vec<_>>(),
+            // STATUS_CODE_ERROR / STATUS_CODE_UNSET
+            "status": { "code": if status >= 500 { 2 } else { 0 } },
+        });
+        let request = json!({
+            "resourceSpans": [{
+                "resource": { "attributes": [attribute("service.name", &json!(self.service_name))] },
+                "scopeSpans": [{ "scope": { "name": self.service_name }, "spans": [span] }],
+            }],
+        });
+
+        let mut line = request.to_string();
+        line.push('\n');
+        if let Err(e) = self.file.lock().unwrap().write_all(line.as_bytes()) {
+            eprintln!("{}: failed to export span: {e}", self.service_name);
+        }
+    }
+}
+
+impl<S> Layer<S> for OtlpFile
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let parent = span
+            .parent()
+            .and_then(|parent| parent.extensions().get::<SpanData>().map(|p| (p.trace_id, p.span_id)));
+
+        let mut rng = rand::thread_rng();
+        let trace_id = parent.map(|(trace_id, _)| trace_id).unwrap_or_else(|| {
+            let mut id = [0; 16];
+            rng.fill_bytes(&mut id);
+            id
+        });
+        let mut span_id = [0; 8];
+        rng.fill_bytes(&mut span_id);
+
+        let mut data = SpanData {
+            trace_id,
+            span_id,
+            parent_span_id: parent.map(|(_, span_id)| span_id),
+            start: SystemTime::now(),
+            attributes: Vec::new(),
+        };
+        attrs.record(&mut Attributes(&mut data.attributes));
+        span.extensions_mut().insert(data);
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(data) = span.extensions_mut().get_mut::<SpanData>() {
+                values.record(&mut Attributes(&mut data.attributes));
+            }
+        }
+    }
+
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let data = span.extensions_mut().remove::<SpanData>();
+        if let Some(data) = data {
+            self.export(span.name(), data, SystemTime::now());
+        }
+    }
+}
+
+/// 把 span 的字段收集成 JSON 值，同名字段后记录的覆盖先记录的
+struct Attributes<'a>(&'a mut Vec<(&'static str, Value)>);
+
+impl Attributes<'_> {
+    fn set(&mut self, field: &Field, value: Value) {
+        match self.0.iter_mut().find(|(k, _)| *k == field.name()) {
+            Some((_, v)) => *v = value,
+            None => self.0.push((field.name(), value)),
+        }
+    }
+}
+
+impl Visit for Attributes<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.set(field, json!(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.set(field, json!(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.set(field, json!(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.set(field, json!(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.set(field, json!(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.set(field, json!(format!("{value:?}")));
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos().to_string()
+}
@@ -0,0 +1,79 @@
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceFactory, ServiceRequest, ServiceResponse},
+    App, HttpServer,
+};
+use health::{BuildInfo, Health};
+
+use crate::{
+    config::{AppConfig, ServerConfig},
+    error::Error,
+    logging,
+};
+
+/// 按 `[server]` 配置启动 HTTP(S) 服务，挂载健康检查，收到 SIGINT / SIGTERM 时等待请求完成后退出
+pub struct Server {
+    name: &'static str,
+    config: ServerConfig,
+    health: Health,
+}
+
+impl Server {
+    /// 初始化日志，之后就可以使用 `log` 和 `tracing` 的宏
+    pub fn new(config: &impl AppConfig, build: BuildInfo) -> Result<Self, Error> {
+        logging::init(config.log(), build.name)?;
+        Ok(Self {
+            name: build.name,
+            config: config.server().clone(),
+            health: Health::new(build),
+        })
+    }
+
+    /// 添加 `/readyz` 的检查：`.health(|h| h.check_url("upstream", url))`
+    pub fn health(mut self, f: impl FnOnce(Health) -> Health) -> Self {
+        self.health = f(self.health);
+        self
+    }
+
+    /// `factory` 在每个 worker 中调用一次，返回的 `App` 上再注册健康检查路由
+    pub async fn run<F, T, B>(self, factory: F) -> Result<(), Error>
+    where
+        F: Fn() -> App<T> + Send + Clone + 'static,
+        T: ServiceFactory<
+                ServiceRequest,
+                Config = (),
+                Response = ServiceResponse<B>,
+                Error = actix_web::Error,
+                InitError = (),
+            > + 'static,
+        B: MessageBody + 'static,
+    {
+        let Self { name, config, health } = self;
+        let with_health = config.health;
+        let mut server = HttpServer::new(move || {
+            let app = factory();
+            match with_health {
+                true => app.configure(health.configure()),
+                false => app,
+            }
+        })
+        .workers(config.workers)
+        .shutdown_timeout(config.shutdown_timeout_secs);
+
+        for addr in &config.bind {
+            log::info!("{name}: starting HTTP server at http://{addr}");
+            server = server.bind(addr)?;
+        }
+        if config.tls.enabled {
+            let tls = config.tls.server_config()?;
+            for addr in &config.tls.bind {
+                log::info!("{name}: starting HTTPS server at https://{addr}");
+                server = server.bind_rustls(addr, tls.clone())?;
+            }
+        }
+
+        server.run().await?;
+        log::info!("{name}: stopped");
+        Ok(())
+    }
+}
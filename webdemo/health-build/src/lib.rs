@@ -0,0 +1,60 @@
+//! 在服务的 build.rs 中调用，给 `health::build_info!` 提供 git 提交和构建时间：
+//!
+//! ```ignore
+//! fn main() {
+//!     health_build::emit();
+//! }
+//! ```
+
+use std::{
+    path::PathBuf,
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// 设置 `HEALTH_GIT_HASH` 和 `HEALTH_BUILD_TIME`，提交变化时重新构建。
+/// 不在 git 仓库中或者没有 git 命令时只设置构建时间。
+pub fn emit() {
+    if let Some(hash) = git(&["rev-parse", "--short=12", "HEAD"]) {
+        println!("cargo:rustc-env=HEALTH_GIT_HASH={hash}");
+    }
+    if let Some(git_dir) = git(&["rev-parse", "--git-dir"]).map(PathBuf::from) {
+        println!("cargo:rerun-if-changed={}", git_dir.join("HEAD").display());
+        if let Some(reference) = git(&["symbolic-ref", "-q", "HEAD"]) {
+            println!("cargo:rerun-if-changed={}", git_dir.join(reference).display());
+        }
+    }
+    println!("cargo:rustc-env=HEALTH_BUILD_TIME={}", rfc3339(SystemTime::now()));
+}
+
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8(output.stdout).ok()?.trim().to_owned())
+}
+
+/// UTC 时间，例如 `2024-01-02T03:04:05Z`
+fn rfc3339(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let (days, rem) = (secs / 86400, secs % 86400);
+
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
@@ -0,0 +1,231 @@
+//! 存活、就绪和构建信息接口，各个服务用一行挂载：
+//!
+//! ```ignore
+//! let health = health::Health::new(health::build_info!())
+//!     .check("database", move || { let pool = pool.clone(); async move { ... } })
+//!     .check_url("upstream", "http://127.0.0.1:8080/healthz");
+//! HttpServer::new(move || App::new().configure(health.configure()))
+//! ```
+//!
+//! - `GET /healthz` 进程存活就返回 200
+//! - `GET /readyz` 并发执行所有检查，全部通过返回 200，否则 503
+//! - `GET /version` 包名、版本、git 提交和构建时间，由 `health-build` 在 build.rs 中写入
+//!
+//! 路由需要 `web` 特性（actix-web 4）。检查和响应体不依赖框架，
+//! 其他版本的 actix-web 关闭默认特性，用 [`Health::live`]、[`Health::readiness`]
+//! 和 [`Health::build`] 挂载同样的三个接口。
+
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "web")]
+use actix_web::{guard, http::StatusCode, web, HttpResponse};
+use futures_timer::Delay;
+use futures_util::future::{join_all, select, Either, LocalBoxFuture};
+use serde::Serialize;
+
+/// 检查结果，失败时是错误描述
+pub type CheckResult = Result<(), String>;
+
+type CheckFn = dyn Fn() -> LocalBoxFuture<'static, CheckResult> + Send + Sync;
+
+/// 构建信息，用 [`build_info!`] 在调用方的 crate 中生成
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildInfo {
+    pub name: &'static str,
+    pub version: &'static str,
+    pub git_hash: Option<&'static str>,
+    pub build_time: Option<&'static str>,
+}
+
+/// 调用方 crate 的 [`BuildInfo`]，git 提交和构建时间由 build.rs 中的 `health_build::emit` 设置
+#[macro_export]
+macro_rules! build_info {
+    () => {
+        $crate::BuildInfo {
+            name: env!("CARGO_PKG_NAME"),
+            version: env!("CARGO_PKG_VERSION"),
+            git_hash: option_env!("HEALTH_GIT_HASH"),
+            build_time: option_env!("HEALTH_BUILD_TIME"),
+        }
+    };
+}
+
+/// 健康检查的配置，在 `HttpServer::new` 之外创建，每个 worker 调用 `configure` 注册路由
+#[derive(Clone)]
+pub struct Health {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    build: BuildInfo,
+    checks: Vec<(String, Box<CheckFn>)>,
+    timeout: Duration,
+}
+
+/// 单项检查的结果
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckReport {
+    /// `ok` 或 `error`
+    pub status: &'static str,
+    pub latency_ms: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl CheckReport {
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+impl Health {
+    pub fn new(build: BuildInfo) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                build,
+                checks: Vec::new(),
+                timeout: Duration::from_secs(2),
+            }),
+        }
+    }
+
+    /// 单个检查的超时，默认 2 秒
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.inner_mut().timeout = timeout;
+        self
+    }
+
+    /// 注册就绪检查，例如数据库连接。每次请求 `/readyz` 时在 worker 线程中调用 `check`。
+    pub fn check<F, Fut>(mut self, name: impl Into<String>, check: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = CheckResult> + 'static,
+    {
+        let check: Box<CheckFn> = Box::new(move || Box::pin(check()));
+        self.inner_mut().checks.push((name.into(), check));
+        self
+    }
+
+    /// 上游 URL 返回 2xx 视为就绪
+    #[cfg(feature = "url")]
+    pub fn check_url(self, name: impl Into<String>, url: impl Into<String>) -> Self {
+        let url = url.into();
+        self.check(name, move || {
+            let request = awc::Client::default().get(&url);
+            async move {
+                let res = request.send().await.map_err(|e| e.to_string())?;
+                match res.status().is_success() {
+                    true => Ok(()),
+                    false => Err(format!("upstream returned {}", res.status())),
+                }
+            }
+        })
+    }
+
+    /// actor 能在超时内处理 [`Ping`] 视为就绪，检查邮箱没有堵塞、actor 没有停止
+    #[cfg(feature = "actor")]
+    pub fn check_actor<A>(self, name: impl Into<String>, addr: actix::Addr<A>) -> Self
+    where
+        A: actix::Actor + actix::Handler<Ping>,
+        A::Context: actix::dev::ToEnvelope<A, Ping>,
+    {
+        self.check(name, move || {
+            let request = addr.send(Ping);
+            async move { request.await.map_err(|e| e.to_string()) }
+        })
+    }
+
+    /// 注册 `/healthz`、`/readyz` 和 `/version`：`App::new().configure(health.configure())`
+    #[cfg(feature = "web")]
+    pub fn configure(&self) -> impl FnOnce(&mut web::ServiceConfig) {
+        let health = self.clone();
+        move |cfg| {
+            cfg.app_data(web::Data::new(health))
+                .route("/healthz", route().to(healthz))
+                .route("/readyz", route().to(readyz))
+                .route("/version", route().to(version));
+        }
+    }
+
+    /// 并发执行所有检查，返回是否全部通过和每项的结果
+    pub async fn ready(&self) -> (bool, BTreeMap<String, CheckReport>) {
+        let timeout_after = self.inner.timeout;
+        let checks = self.inner.checks.iter().map(|(name, check)| async move {
+            let start = Instant::now();
+            let result = match select(check(), Delay::new(timeout_after)).await {
+                Either::Left((result, _)) => result,
+                Either::Right(_) => Err(format!("timed out after {}ms", timeout_after.as_millis())),
+            };
+            let report = CheckReport {
+                status: if result.is_ok() { "ok" } else { "error" },
+                latency_ms: start.elapsed().as_millis(),
+                error: result.err(),
+            };
+            (name.clone(), report)
+        });
+        let reports: BTreeMap<_, _> = join_all(checks).await.into_iter().collect();
+        (reports.values().all(CheckReport::is_ok), reports)
+    }
+
+    /// `/healthz` 的响应体，进程能处理请求就是存活
+    pub fn live(&self) -> serde_json::Value {
+        serde_json::json!({ "status": "ok" })
+    }
+
+    /// `/readyz` 的响应体和是否就绪，未就绪时应该返回 503
+    pub async fn readiness(&self) -> (bool, serde_json::Value) {
+        let start = Instant::now();
+        let (ready, checks) = self.ready().await;
+        let body = serde_json::json!({
+            "status": if ready { "ok" } else { "unavailable" },
+            "latency_ms": start.elapsed().as_millis(),
+            "checks": checks,
+        });
+        (ready, body)
+    }
+
+    /// `/version` 返回的构建信息
+    pub fn build(&self) -> &BuildInfo {
+        &self.inner.build
+    }
+
+    /// 只在注册阶段修改，之后 `Health` 才会被克隆到各个 worker
+    fn inner_mut(&mut self) -> &mut Inner {
+        Arc::get_mut(&mut self.inner).expect("Health must be configured before it is cloned")
+    }
+}
+
+/// actor 邮箱检查的消息，actor 实现 `Handler<Ping>` 后用 [`Health::check_actor`] 注册
+#[cfg(feature = "actor")]
+#[derive(actix::Message)]
+#[rtype(result = "()")]
+pub struct Ping;
+
+/// 只匹配 `GET /healthz` 这样的 origin-form 请求；正向代理收到的 `GET http://host/healthz`
+/// 是要转发的请求，不能被这里拦截
+#[cfg(feature = "web")]
+fn route() -> actix_web::Route {
+    web::get().guard(guard::fn_guard(|ctx| ctx.head().uri.scheme().is_none()))
+}
+
+#[cfg(feature = "web")]
+async fn healthz(health: web::Data<Health>) -> HttpResponse {
+    HttpResponse::Ok().json(health.live())
+}
+
+#[cfg(feature = "web")]
+async fn readyz(health: web::Data<Health>) -> HttpResponse {
+    let (ready, body) = health.readiness().await;
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    HttpResponse::build(status).json(body)
+}
+
+#[cfg(feature = "web")]
+async fn version(health: web::Data<Health>) -> HttpResponse {
+    HttpResponse::Ok().json(health.build())
+}
@@ -0,0 +1,520 @@
+use std::{
+    cell::RefCell,
+    error::Error as StdError,
+    fs::{self, File, OpenOptions},
+    io::{self, Write as _},
+    net::IpAddr,
+    path::PathBuf,
+    pin::Pin,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use actix_web::{
+    body::{BodySize, BoxBody, MessageBody},
+    dev::PeerAddr,
+    http::{
+        header::{self, HeaderMap},
+        Method, StatusCode, Version,
+    },
+    web::{self, Bytes, BytesMut},
+    Error, HttpRequest, HttpResponse,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use futures_util::StreamExt as _;
+use serde_json::{json, Value};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use url::Url;
+
+use crate::{
+    config::{AccessLogConfig, CaptureConfig},
+    route::Route,
+    upstream::BodyStream,
+};
+
+/// 访问日志的 `log` target
+const LOG_TARGET: &str = "httpproxy1::access";
+
+/// HAR 中隐藏的头部值
+const REDACTED: &str = "[redacted]";
+
+/// 访问日志和 HAR 抓包，所有 worker 共享。
+pub struct AccessLog {
+    enabled: bool,
+    file: Option<Mutex<File>>,
+    capture: Option<CaptureConfig>,
+    /// 同一毫秒内的 HAR 文件名序号
+    seq: AtomicU64,
+}
+
+impl AccessLog {
+    pub fn new(config: &AccessLogConfig, capture: &CaptureConfig) -> io::Result<Self> {
+        let file = match &config.path {
+            Some(path) if config.enabled => Some(Mutex::new(
+                OpenOptions::new().create(true).append(true).open(path)?,
+            )),
+            _ => None,
+        };
+
+        let capture = if capture.enabled {
+            fs::create_dir_all(&capture.dir)?;
+            log::warn!("capturing traffic to {}", capture.dir.display());
+            let mut capture = capture.clone();
+            for name in &mut capture.redact_headers {
+                name.make_ascii_lowercase();
+            }
+            Some(capture)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            enabled: config.enabled,
+            file,
+            capture,
+            seq: AtomicU64::new(0),
+        })
+    }
+
+    /// 开始记录一次代理请求
+    pub fn start(&self, req: &HttpRequest, route: &Route, peer_addr: Option<PeerAddr>) -> Exchange {
+        let conn = req.connection_info();
+        let path = req.uri().path_and_query().map_or("/", |p| p.as_str());
+
+        Exchange {
+            started: Instant::now(),
+            started_at: OffsetDateTime::now_utc(),
+            client: peer_addr.map(|PeerAddr(addr)| addr.ip()),
+            method: req.method().clone(),
+            path: path.to_owned(),
+            url: format!("{}://{}{path}", conn.scheme(), conn.host()),
+            version: req.version(),
+            route: route.prefix.clone(),
+            backend: route.backend_name(),
+            upstream: None,
+            upstream_latency: None,
+            attempts: 0,
+            cache: None,
+            status: StatusCode::OK,
+            bytes: 0,
+            error: None,
+            aborted: false,
+            capture: self.capture.as_ref().map(|c| Capture {
+                request_headers: req.headers().clone(),
+                request_body: Rc::new(RefCell::new(Captured::new(c.max_body_bytes))),
+                response_headers: HeaderMap::new(),
+                response_body: Rc::new(RefCell::new(Captured::new(c.max_body_bytes))),
+                response_encoding: None,
+            }),
+        }
+    }
+
+    fn finish(&self, exchange: Exchange) {
+        let total = exchange.started.elapsed();
+
+        if self.enabled {
+            let line = exchange.log_line(total).to_string();
+            match &self.file {
+                Some(file) => {
+                    if let Err(e) = writeln!(file.lock().unwrap(), "{line}") {
+                        log::warn!("failed to write access log: {e}");
+                    }
+                }
+                None => log::info!(target: LOG_TARGET, "{line}"),
+            }
+        }
+
+        let Some(config) = &self.capture else {
+            return;
+        };
+        if let Some(har) = exchange.har(total, config) {
+            let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+            let millis = exchange.started_at.unix_timestamp_nanos() / 1_000_000;
+            let path = config.dir.join(format!("{millis}-{seq:06}.har"));
+            actix_web::rt::task::spawn_blocking(move || write_har(path, &har));
+        }
+    }
+}
+
+fn write_har(path: PathBuf, har: &Value) {
+    let result = serde_json::to_vec_pretty(har)
+        .map_err(io::Error::from)
+        .and_then(|data| fs::write(&path, data));
+    if let Err(e) = result {
+        log::warn!("failed to write {}: {e}", path.display());
+    }
+}
+
+/// 一次代理请求，响应体发送完（或客户端断开）时写入日志。
+pub struct Exchange {
+    started: Instant,
+    started_at: OffsetDateTime,
+    client: Option<IpAddr>,
+    method: Method,
+    /// 客户端请求的路径和查询字符串
+    path: String,
+    url: String,
+    version: Version,
+    route: String,
+    backend: &'static str,
+    /// 实际请求的上游地址，缓存命中时没有。
+    pub upstream: Option<Url>,
+    /// 从第一次发送到收到上游响应头，包含重试。
+    pub upstream_latency: Option<Duration>,
+    pub attempts: u32,
+    /// `X-Cache` 头的值
+    pub cache: Option<&'static str>,
+    status: StatusCode,
+    /// 发送给客户端的响应体字节数
+    bytes: u64,
+    error: Option<String>,
+    /// 响应体没有发送完客户端就断开了
+    aborted: bool,
+    capture: Option<Capture>,
+}
+
+/// 抓包内容
+struct Capture {
+    request_headers: HeaderMap,
+    /// 流式请求体在发送给上游时记录
+    request_body: Rc<RefCell<Captured>>,
+    response_headers: HeaderMap,
+    /// 在压缩协商之前记录，不是发给客户端的字节
+    response_body: Rc<RefCell<Captured>>,
+    /// 记录的响应体本身的 `Content-Encoding`（上游已经压缩的响应）
+    response_encoding: Option<String>,
+}
+
+/// 记录的请求体或响应体，超出上限的部分只计数。
+struct Captured {
+    data: BytesMut,
+    size: u64,
+    limit: usize,
+}
+
+impl Captured {
+    fn new(limit: usize) -> Self {
+        Self {
+            data: BytesMut::new(),
+            size: 0,
+            limit,
+        }
+    }
+
+    fn push(&mut self, chunk: &[u8]) {
+        self.size += chunk.len() as u64;
+        let room = self.limit.saturating_sub(self.data.len());
+        self.data.extend_from_slice(&chunk[..room.min(chunk.len())]);
+    }
+
+    /// HAR `postData` / `content`
+    fn har(&self, mime_type: &str) -> Value {
+        let mut content = json!({ "mimeType": mime_type });
+        match std::str::from_utf8(&self.data) {
+            Ok(text) => content["text"] = json!(text),
+            // 截断处切开了一个字符
+            Err(e) if e.error_len().is_none() => {
+                content["text"] = json!(String::from_utf8_lossy(&self.data[..e.valid_up_to()]));
+            }
+            Err(_) => {
+                content["text"] = json!(BASE64.encode(&self.data));
+                content["encoding"] = json!("base64");
+            }
+        }
+        if self.size > self.data.len() as u64 {
+            content["comment"] = json!(format!("truncated to {} bytes", self.data.len()));
+        }
+        content
+    }
+}
+
+impl Exchange {
+    /// 记录内存中的请求体
+    pub fn capture_request(&self, body: &Bytes) {
+        if let Some(capture) = &self.capture {
+            capture.request_body.borrow_mut().push(body);
+        }
+    }
+
+    /// 流式请求体转发给上游的同时记录
+    pub fn tee_request(&self, body: BodyStream) -> BodyStream {
+        match &self.capture {
+            Some(capture) => {
+                let captured = capture.request_body.clone();
+                body.inspect(move |chunk| {
+                    if let Ok(chunk) = chunk {
+                        captured.borrow_mut().push(chunk);
+                    }
+                })
+                .boxed_local()
+            }
+            None => body,
+        }
+    }
+
+    /// 压缩协商之前记录响应体，HAR 中是可读的内容而不是压缩后的字节
+    pub fn tee_response(&mut self, res: HttpResponse) -> HttpResponse {
+        let Some(capture) = &mut self.capture else {
+            return res;
+        };
+        capture.response_encoding = res
+            .headers()
+            .get(header::CONTENT_ENCODING)
+            .and_then(|v| v.to_str().ok())
+            .filter(|v| !v.eq_ignore_ascii_case("identity"))
+            .map(str::to_owned);
+
+        let captured = capture.response_body.clone();
+        res.map_body(|_, body| Teed { body, captured }).map_into_boxed_body()
+    }
+
+    fn log_line(&self, total: Duration) -> Value {
+        json!({
+            "time": format_time(self.started_at),
+            "client": self.client.map(|ip| ip.to_string()),
+            "method": self.method.as_str(),
+            "path": self.path,
+            "route": self.route,
+            "backend": self.backend,
+            "upstream": self.upstream.as_ref().map(Url::as_str),
+            "status": self.status.as_u16(),
+            "bytes": self.bytes,
+            "attempts": self.attempts,
+            "cache": self.cache,
+            "upstream_ms": self.upstream_latency.map(millis),
+            "total_ms": millis(total),
+            "error": self.error,
+            "aborted": self.aborted,
+        })
+    }
+
+    /// HAR 1.2，只有开启抓包时才有。
+    /// http://www.softwareishard.com/blog/har-12-spec/
+    fn har(&self, total: Duration, config: &CaptureConfig) -> Option<Value> {
+        let capture = self.capture.as_ref()?;
+        let wait = self.upstream_latency.unwrap_or_default();
+        let query: Vec<Value> = Url::parse(&self.url)
+            .map(|url| {
+                url.query_pairs()
+                    .map(|(name, value)| json!({ "name": name, "value": value }))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut request = json!({
+            "method": self.method.as_str(),
+            "url": self.url,
+            "httpVersion": format!("{:?}", self.version),
+            "cookies": [],
+            "headers": har_headers(&capture.request_headers, &config.redact_headers),
+            "queryString": query,
+            "headersSize": -1,
+            "bodySize": capture.request_body.borrow().size,
+        });
+        if capture.request_body.borrow().size > 0 {
+            let mime_type = content_type(&capture.request_headers);
+            request["postData"] = capture.request_body.borrow().har(mime_type);
+        }
+
+        let response_body = capture.response_body.borrow();
+        let mut content = response_body.har(content_type(&capture.response_headers));
+        content["size"] = json!(response_body.size);
+        if let Some(encoding) = &capture.response_encoding {
+            let comment = match content["comment"].as_str() {
+                Some(truncated) => format!("{encoding} encoded, {truncated}"),
+                None => format!("{encoding} encoded"),
+            };
+            content["comment"] = json!(comment);
+        }
+
+        let response = json!({
+            "status": self.status.as_u16(),
+            "statusText": self.status.canonical_reason().unwrap_or_default(),
+            "httpVersion": format!("{:?}", self.version),
+            "cookies": [],
+            "headers": har_headers(&capture.response_headers, &config.redact_headers),
+            "content": content,
+            "redirectURL": capture
+                .response_headers
+                .get(header::LOCATION)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default(),
+            "headersSize": -1,
+            "bodySize": self.bytes,
+        });
+
+        let mut entry = json!({
+            "startedDateTime": format_time(self.started_at),
+            "time": millis(total),
+            "request": request,
+            "response": response,
+            "cache": {},
+            "timings": {
+                "send": 0,
+                "wait": millis(wait),
+                "receive": millis(total.saturating_sub(wait)),
+            },
+        });
+        let mut comment = format!("route {} ({})", self.route, self.backend);
+        if let Some(upstream) = &self.upstream {
+            comment.push_str(&format!(" -> {upstream}"));
+        }
+        if let Some(error) = &self.error {
+            comment.push_str(&format!(", error: {error}"));
+        }
+        entry["comment"] = json!(comment);
+
+        Some(json!({
+            "log": {
+                "version": "1.2",
+                "creator": { "name": "httpproxy1", "version": env!("CARGO_PKG_VERSION") },
+                "entries": [entry],
+            }
+        }))
+    }
+}
+
+/// `redact` 中的头部只记录名称
+fn har_headers(headers: &HeaderMap, redact: &[String]) -> Vec<Value> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = match redact.iter().any(|r| r == name.as_str()) {
+                true => REDACTED.into(),
+                false => String::from_utf8_lossy(value.as_bytes()),
+            };
+            json!({ "name": name.as_str(), "value": value })
+        })
+        .collect()
+}
+
+fn content_type(headers: &HeaderMap) -> &str {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+}
+
+fn format_time(time: OffsetDateTime) -> String {
+    time.format(&Rfc3339).unwrap_or_default()
+}
+
+/// 毫秒，保留三位小数
+fn millis(d: Duration) -> f64 {
+    (d.as_secs_f64() * 1_000_000.0).round() / 1000.0
+}
+
+/// 代理结果转换为响应，响应体结束时写入访问日志。
+pub fn record(
+    log: web::Data<AccessLog>,
+    mut exchange: Exchange,
+    result: Result<HttpResponse, Error>,
+) -> HttpResponse {
+    let res = result.unwrap_or_else(|e| {
+        exchange.error = Some(e.to_string());
+        exchange.tee_response(e.error_response())
+    });
+
+    exchange.status = res.status();
+    if let Some(capture) = &mut exchange.capture {
+        capture.response_headers = res.headers().clone();
+    }
+
+    res.map_body(|_, body| Logged {
+        body,
+        exchange: Some(exchange),
+        log,
+    })
+    .map_into_boxed_body()
+}
+
+/// 统计响应体字节数，结束时写日志。
+struct Logged {
+    body: BoxBody,
+    exchange: Option<Exchange>,
+    log: web::Data<AccessLog>,
+}
+
+impl MessageBody for Logged {
+    type Error = Box<dyn StdError>;
+
+    fn size(&self) -> BodySize {
+        self.body.size()
+    }
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.body).poll_next(cx);
+
+        match &poll {
+            Poll::Ready(Some(Ok(chunk))) => {
+                if let Some(exchange) = &mut this.exchange {
+                    exchange.bytes += chunk.len() as u64;
+                }
+            }
+            Poll::Ready(Some(Err(e))) => {
+                if let Some(mut exchange) = this.exchange.take() {
+                    exchange.error = Some(e.to_string());
+                    this.log.finish(exchange);
+                }
+            }
+            Poll::Ready(None) => {
+                if let Some(exchange) = this.exchange.take() {
+                    this.log.finish(exchange);
+                }
+            }
+            Poll::Pending => {}
+        }
+
+        poll
+    }
+}
+
+impl Drop for Logged {
+    fn drop(&mut self) {
+        if let Some(mut exchange) = self.exchange.take() {
+            // 空响应体和 HEAD 响应不会被读取
+            exchange.aborted = exchange.method != Method::HEAD
+                && match self.body.size() {
+                    BodySize::None => false,
+                    BodySize::Sized(len) => exchange.bytes < len,
+                    BodySize::Stream => true,
+                };
+            self.log.finish(exchange);
+        }
+    }
+}
+
+/// 转发响应体的同时记录到抓包
+struct Teed {
+    body: BoxBody,
+    captured: Rc<RefCell<Captured>>,
+}
+
+impl MessageBody for Teed {
+    type Error = Box<dyn StdError>;
+
+    fn size(&self) -> BodySize {
+        self.body.size()
+    }
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.body).poll_next(cx);
+        if let Poll::Ready(Some(Ok(chunk))) = &poll {
+            this.captured.borrow_mut().push(chunk);
+        }
+        poll
+    }
+}
@@ -0,0 +1,702 @@
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap},
+    fs,
+    hash::{Hash, Hasher},
+    io,
+    path::{Path, PathBuf},
+    pin::Pin,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, SystemTime},
+};
+
+use actix_web::{
+    dev::PeerAddr,
+    error,
+    http::{
+        header::{self, HeaderMap, HeaderName, HeaderValue, HttpDate},
+        StatusCode,
+    },
+    web::{self, Bytes, BytesMut},
+    Error, HttpRequest, HttpResponse,
+};
+use futures_util::{stream::LocalBoxStream, Stream, StreamExt as _};
+use serde::{Deserialize, Serialize};
+use crate::{config::CacheConfig, route::Routes};
+
+/// 不写入缓存的响应头
+const SKIP_HEADERS: &[&str] = &[
+    "age",
+    "connection",
+    "content-length",
+    "keep-alive",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+    "x-cache",
+];
+
+/// 允许缓存的状态码
+/// https://www.rfc-editor.org/rfc/rfc9110#section-15.1
+const CACHEABLE_STATUS: &[u16] = &[200, 203, 204, 300, 301, 308, 404, 405, 410, 414, 501];
+
+/// 缓存查找结果
+pub enum Lookup {
+    /// 新鲜，可以直接响应。
+    Fresh(Entry),
+    /// 过期，需要带上 `If-None-Match` / `If-Modified-Since` 到上游验证。
+    Stale(Entry),
+    Miss,
+}
+
+/// 缓存的响应
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Entry {
+    status: u16,
+    headers: Vec<(String, String)>,
+    #[serde(with = "base64_bytes")]
+    body: Bytes,
+    stored_at: SystemTime,
+    /// 新鲜期
+    lifetime: Duration,
+    /// `no-cache` 或只有验证器，每次使用前都要验证。
+    revalidate: bool,
+    #[serde(skip)]
+    tick: u64,
+}
+
+impl Entry {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn etag(&self) -> Option<&str> {
+        self.header("etag")
+    }
+
+    pub fn last_modified(&self) -> Option<&str> {
+        self.header("last-modified")
+    }
+
+    fn age(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(self.stored_at)
+            .unwrap_or_default()
+    }
+
+    fn is_fresh(&self) -> bool {
+        !self.revalidate && self.age() < self.lifetime
+    }
+
+    fn size(&self) -> usize {
+        self.body.len()
+            + self
+                .headers
+                .iter()
+                .map(|(n, v)| n.len() + v.len())
+                .sum::<usize>()
+    }
+
+    /// 用 304 响应的头部更新条目，重新计算新鲜期。
+    fn refresh(&mut self, res_headers: &HeaderMap) {
+        for name in res_headers.keys() {
+            if SKIP_HEADERS.contains(&name.as_str()) {
+                continue;
+            }
+            self.headers.retain(|(n, _)| n != name.as_str());
+            for value in res_headers.get_all(name) {
+                if let Ok(v) = value.to_str() {
+                    self.headers.push((name.as_str().to_owned(), v.to_owned()));
+                }
+            }
+        }
+
+        let freshness = Freshness::of(res_headers).unwrap_or(Freshness {
+            lifetime: Duration::ZERO,
+            revalidate: true,
+        });
+        self.lifetime = freshness.lifetime;
+        self.revalidate = freshness.revalidate;
+        self.stored_at = SystemTime::now();
+    }
+
+    /// 生成客户端响应，客户端的 `If-None-Match` 命中时返回 304。
+    pub fn response(&self, req: &HttpRequest, x_cache: &str) -> HttpResponse {
+        let not_modified = match (self.etag(), req.headers().get(header::IF_NONE_MATCH)) {
+            (Some(etag), Some(inm)) => inm
+                .to_str()
+                .map(|v| v.split(',').any(|t| t.trim() == etag || t.trim() == "*"))
+                .unwrap_or(false),
+            _ => false,
+        };
+
+        let status = if not_modified {
+            StatusCode::NOT_MODIFIED
+        } else {
+            StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK)
+        };
+
+        let mut res = HttpResponse::build(status);
+        for (name, value) in &self.headers {
+            if let (Ok(n), Ok(v)) = (HeaderName::from_str(name), HeaderValue::from_str(value)) {
+                res.append_header((n, v));
+            }
+        }
+        res.insert_header((header::AGE, self.age().as_secs().to_string()))
+            .insert_header(("x-cache", x_cache));
+
+        if not_modified {
+            res.finish()
+        } else {
+            res.body(self.body.clone())
+        }
+    }
+}
+
+/// 响应的新鲜期
+struct Freshness {
+    lifetime: Duration,
+    revalidate: bool,
+}
+
+impl Freshness {
+    /// 共享缓存规则：`s-maxage` > `max-age` > `Expires`，
+    /// 没有明确新鲜期但有验证器时缓存并每次验证。
+    fn of(res_headers: &HeaderMap) -> Option<Freshness> {
+        let cc = CacheControl::parse(res_headers.get_all(header::CACHE_CONTROL));
+        if cc.no_store || cc.private {
+            return None;
+        }
+
+        let lifetime = cc
+            .s_maxage
+            .or(cc.max_age)
+            .map(Duration::from_secs)
+            .or_else(|| {
+                let expires = http_date(res_headers.get(header::EXPIRES))?;
+                let date = http_date(res_headers.get(header::DATE)).unwrap_or_else(SystemTime::now);
+                Some(expires.duration_since(date).unwrap_or_default())
+            });
+
+        match lifetime {
+            Some(lifetime) => Some(Freshness {
+                lifetime,
+                revalidate: cc.no_cache,
+            }),
+            None if res_headers.contains_key(header::ETAG)
+                || res_headers.contains_key(header::LAST_MODIFIED) =>
+            {
+                Some(Freshness {
+                    lifetime: Duration::ZERO,
+                    revalidate: true,
+                })
+            }
+            None => None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct CacheControl {
+    no_store: bool,
+    no_cache: bool,
+    private: bool,
+    max_age: Option<u64>,
+    s_maxage: Option<u64>,
+}
+
+impl CacheControl {
+    fn parse<'a>(values: impl IntoIterator<Item = &'a HeaderValue>) -> Self {
+        let mut cc = CacheControl::default();
+        let directives = values
+            .into_iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','));
+
+        for directive in directives {
+            let (name, arg) = match directive.split_once('=') {
+                Some((n, a)) => (n.trim(), Some(a.trim().trim_matches('"'))),
+                None => (directive.trim(), None),
+            };
+            match name.to_ascii_lowercase().as_str() {
+                "no-store" => cc.no_store = true,
+                "no-cache" => cc.no_cache = true,
+                "private" => cc.private = true,
+                "max-age" => cc.max_age = arg.and_then(|a| a.parse().ok()),
+                "s-maxage" => cc.s_maxage = arg.and_then(|a| a.parse().ok()),
+                _ => {}
+            }
+        }
+        cc
+    }
+}
+
+fn http_date(value: Option<&HeaderValue>) -> Option<SystemTime> {
+    let date = HttpDate::from_str(value?.to_str().ok()?).ok()?;
+    Some(date.into())
+}
+
+/// 请求是否可以使用缓存：只缓存不带认证的 GET。
+pub fn request_cacheable(req: &HttpRequest) -> bool {
+    req.method() == actix_web::http::Method::GET
+        && !req.headers().contains_key(header::AUTHORIZATION)
+        && !CacheControl::parse(req.headers().get_all(header::CACHE_CONTROL)).no_store
+}
+
+/// 客户端要求忽略新鲜期（`no-cache` 或 `max-age=0`）。
+fn request_revalidate(req_headers: &HeaderMap) -> bool {
+    let cc = CacheControl::parse(req_headers.get_all(header::CACHE_CONTROL));
+    cc.no_cache
+        || cc.max_age == Some(0)
+        || req_headers
+            .get(header::PRAGMA)
+            .is_some_and(|v| v.as_bytes().eq_ignore_ascii_case(b"no-cache"))
+}
+
+struct Inner {
+    /// 缓存键 -> `Vary` 头部名称
+    vary: HashMap<String, Vec<String>>,
+    /// 变体键 -> 条目
+    entries: HashMap<String, Entry>,
+    /// 使用时刻 -> 变体键，最小的最久未使用。
+    lru: BTreeMap<u64, String>,
+    used: usize,
+    tick: u64,
+}
+
+impl Inner {
+    /// 变体键：缓存键加上 `Vary` 指定的请求头值。
+    fn variant_key(key: &str, names: &[String], req_headers: &HeaderMap) -> String {
+        let mut vkey = format!("{key}\0");
+        for name in names {
+            let values: Vec<&str> = req_headers
+                .get_all(name.as_str())
+                .filter_map(|v| v.to_str().ok())
+                .collect();
+            vkey.push_str(&format!("{name}={};", values.join(",")));
+        }
+        vkey
+    }
+
+    fn get(&mut self, key: &str, req_headers: &HeaderMap) -> Option<Entry> {
+        let names = self.vary.get(key)?;
+        let vkey = Self::variant_key(key, names, req_headers);
+        let entry = self.entries.get_mut(&vkey)?;
+
+        self.lru.remove(&entry.tick);
+        self.tick += 1;
+        entry.tick = self.tick;
+        self.lru.insert(self.tick, vkey);
+        Some(entry.clone())
+    }
+
+    fn insert(&mut self, key: &str, vary: Vec<String>, vkey: String, mut entry: Entry, max_bytes: usize) {
+        self.remove(&vkey);
+        self.vary.insert(key.to_owned(), vary);
+
+        self.tick += 1;
+        entry.tick = self.tick;
+        self.used += entry.size();
+        self.lru.insert(self.tick, vkey.clone());
+        self.entries.insert(vkey, entry);
+
+        while self.used > max_bytes {
+            match self.lru.pop_first() {
+                Some((_, oldest)) => self.remove(&oldest),
+                None => break,
+            }
+        }
+    }
+
+    fn remove(&mut self, vkey: &str) {
+        if let Some(entry) = self.entries.remove(vkey) {
+            self.used -= entry.size();
+            self.lru.remove(&entry.tick);
+        }
+    }
+
+    /// 删除一个缓存键的所有变体
+    fn purge(&mut self, key: &str) -> usize {
+        self.vary.remove(key);
+        let prefix = format!("{key}\0");
+        let vkeys: Vec<String> = self
+            .entries
+            .keys()
+            .filter(|k| k.starts_with(&prefix))
+            .cloned()
+            .collect();
+        for vkey in &vkeys {
+            self.remove(vkey);
+        }
+        vkeys.len()
+    }
+
+    fn variants(&self, key: &str) -> Vec<(String, Entry)> {
+        let prefix = format!("{key}\0");
+        self.entries
+            .iter()
+            .filter(|(k, _)| k.starts_with(&prefix))
+            .map(|(k, e)| (k.clone(), e.clone()))
+            .collect()
+    }
+}
+
+/// 磁盘上一个缓存键的所有变体
+#[derive(Serialize, Deserialize)]
+struct DiskRecord {
+    vary: Vec<String>,
+    variants: Vec<(String, Entry)>,
+}
+
+/// HTTP 响应缓存，内存 LRU，可选写入磁盘。
+pub struct Cache {
+    inner: Mutex<Inner>,
+    max_bytes: usize,
+    max_entry_bytes: usize,
+    dir: Option<PathBuf>,
+}
+
+impl Cache {
+    pub fn new(config: &CacheConfig) -> io::Result<Self> {
+        if let Some(dir) = &config.dir {
+            fs::create_dir_all(dir)?;
+        }
+
+        Ok(Self {
+            inner: Mutex::new(Inner {
+                vary: HashMap::new(),
+                entries: HashMap::new(),
+                lru: BTreeMap::new(),
+                used: 0,
+                tick: 0,
+            }),
+            max_bytes: config.max_bytes,
+            max_entry_bytes: config.max_entry_bytes.min(config.max_bytes),
+            dir: config.dir.clone(),
+        })
+    }
+
+    pub async fn lookup(&self, key: &str, req: &HttpRequest) -> Lookup {
+        let mut entry = self.inner.lock().unwrap().get(key, req.headers());
+
+        // 内存未命中，从磁盘加载所有变体。
+        if entry.is_none() {
+            if let Some(path) = self.disk_path(key) {
+                let record = web::block(move || read_record(&path)).await.ok().flatten();
+                if let Some(record) = record {
+                    let mut inner = self.inner.lock().unwrap();
+                    for (vkey, e) in record.variants {
+                        inner.insert(key, record.vary.clone(), vkey, e, self.max_bytes);
+                    }
+                    entry = inner.get(key, req.headers());
+                }
+            }
+        }
+
+        match entry {
+            Some(e) if e.is_fresh() && !request_revalidate(req.headers()) => Lookup::Fresh(e),
+            Some(e) if e.etag().is_some() || e.last_modified().is_some() => Lookup::Stale(e),
+            _ => Lookup::Miss,
+        }
+    }
+
+    /// 上游返回 304，刷新过期条目。
+    pub fn revalidated(
+        &self,
+        key: &str,
+        req: &HttpRequest,
+        mut entry: Entry,
+        res_headers: &HeaderMap,
+    ) -> Entry {
+        entry.refresh(res_headers);
+
+        let mut inner = self.inner.lock().unwrap();
+        let vary = inner.vary.get(key).cloned().unwrap_or_default();
+        let vkey = Inner::variant_key(key, &vary, req.headers());
+        inner.insert(key, vary, vkey, entry.clone(), self.max_bytes);
+        drop(inner);
+
+        self.persist(key);
+        entry
+    }
+
+    /// 包装上游响应体，边转发边收集，结束后写入缓存。
+    /// 响应不可缓存时原样转发。
+    pub fn fill<S>(
+        self: Arc<Self>,
+        key: &str,
+        req: &HttpRequest,
+        status: StatusCode,
+        headers: &HeaderMap,
+        stream: S,
+    ) -> impl Stream<Item = Result<Bytes, Error>>
+    where
+        S: Stream<Item = Result<Bytes, Error>> + 'static,
+    {
+        let pending = self.pending(key, req, status, headers);
+        CacheFill {
+            stream: stream.boxed_local(),
+            cache: self,
+            pending,
+        }
+    }
+
+    fn pending(
+        &self,
+        key: &str,
+        req: &HttpRequest,
+        status: StatusCode,
+        headers: &HeaderMap,
+    ) -> Option<Pending> {
+        let content_length = headers
+            .get(header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok()?.parse::<usize>().ok())
+            .unwrap_or(0);
+
+        if !request_cacheable(req)
+            || !CACHEABLE_STATUS.contains(&status.as_u16())
+            || headers.contains_key(header::SET_COOKIE)
+            || content_length > self.max_entry_bytes
+        {
+            return None;
+        }
+
+        let mut vary = Vec::new();
+        for value in headers.get_all(header::VARY) {
+            for name in value.to_str().ok()?.split(',') {
+                let name = name.trim().to_ascii_lowercase();
+                if name == "*" {
+                    return None;
+                }
+                if !name.is_empty() {
+                    vary.push(name);
+                }
+            }
+        }
+        vary.sort();
+        vary.dedup();
+
+        let freshness = Freshness::of(headers)?;
+        let vkey = Inner::variant_key(key, &vary, req.headers());
+
+        let entry = Entry {
+            status: status.as_u16(),
+            headers: headers
+                .iter()
+                .filter(|(n, _)| !SKIP_HEADERS.contains(&n.as_str()))
+                .filter_map(|(n, v)| Some((n.as_str().to_owned(), v.to_str().ok()?.to_owned())))
+                .collect(),
+            body: Bytes::new(),
+            stored_at: SystemTime::now(),
+            lifetime: freshness.lifetime,
+            revalidate: freshness.revalidate,
+            tick: 0,
+        };
+
+        Some(Pending {
+            key: key.to_owned(),
+            vary,
+            vkey,
+            entry,
+            body: BytesMut::new(),
+        })
+    }
+
+    fn store(&self, pending: Pending) {
+        let Pending {
+            key,
+            vary,
+            vkey,
+            mut entry,
+            body,
+        } = pending;
+        entry.body = body.freeze();
+
+        self.inner
+            .lock()
+            .unwrap()
+            .insert(&key, vary, vkey, entry, self.max_bytes);
+        self.persist(&key);
+    }
+
+    /// 删除一个缓存键，返回删除的变体数量。
+    pub async fn purge(&self, key: &str) -> usize {
+        let count = self.inner.lock().unwrap().purge(key);
+        if let Some(path) = self.disk_path(key) {
+            let _ = web::block(move || fs::remove_file(path)).await;
+        }
+        count
+    }
+
+    /// 清空缓存，返回删除的变体数量。
+    pub async fn clear(&self) -> usize {
+        let count = {
+            let mut inner = self.inner.lock().unwrap();
+            let count = inner.entries.len();
+            inner.vary.clear();
+            inner.entries.clear();
+            inner.lru.clear();
+            inner.used = 0;
+            count
+        };
+
+        if let Some(dir) = self.dir.clone() {
+            let _ = web::block(move || -> io::Result<()> {
+                for file in fs::read_dir(dir)? {
+                    let path = file?.path();
+                    if path.extension().is_some_and(|e| e == "json") {
+                        fs::remove_file(path)?;
+                    }
+                }
+                Ok(())
+            })
+            .await;
+        }
+        count
+    }
+
+    /// 缓存键对应的磁盘文件。
+    /// `DefaultHasher` 的结果只在同一个 Rust 版本内稳定，变化后只会导致未命中。
+    fn disk_path(&self, key: &str) -> Option<PathBuf> {
+        let dir = self.dir.as_ref()?;
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        Some(dir.join(format!("{:016x}.json", hasher.finish())))
+    }
+
+    /// 把内存中一个缓存键的所有变体写入磁盘
+    fn persist(&self, key: &str) {
+        let Some(path) = self.disk_path(key) else {
+            return;
+        };
+
+        let record = {
+            let inner = self.inner.lock().unwrap();
+            DiskRecord {
+                vary: inner.vary.get(key).cloned().unwrap_or_default(),
+                variants: inner.variants(key),
+            }
+        };
+
+        actix_web::rt::task::spawn_blocking(move || {
+            if let Err(e) = write_record(&path, &record) {
+                log::warn!("failed to write cache file {}: {e}", path.display());
+            }
+        });
+    }
+}
+
+fn read_record(path: &Path) -> Option<DiskRecord> {
+    let text = fs::read(path).ok()?;
+    serde_json::from_slice(&text).ok()
+}
+
+fn write_record(path: &Path, record: &DiskRecord) -> io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, serde_json::to_vec(record)?)?;
+    fs::rename(tmp, path)
+}
+
+/// 等待写入缓存的响应
+struct Pending {
+    key: String,
+    vary: Vec<String>,
+    vkey: String,
+    entry: Entry,
+    body: BytesMut,
+}
+
+/// 转发上游响应体的同时收集内容
+struct CacheFill {
+    stream: LocalBoxStream<'static, Result<Bytes, Error>>,
+    cache: Arc<Cache>,
+    pending: Option<Pending>,
+}
+
+impl Stream for CacheFill {
+    type Item = Result<Bytes, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let item = futures_util::ready!(self.stream.poll_next_unpin(cx));
+        let max_entry_bytes = self.cache.max_entry_bytes;
+
+        match &item {
+            Some(Ok(chunk)) => {
+                if let Some(pending) = &mut self.pending {
+                    if pending.body.len() + chunk.len() > max_entry_bytes {
+                        self.pending = None;
+                    } else {
+                        pending.body.extend_from_slice(chunk);
+                    }
+                }
+            }
+            // 上游中断，不缓存不完整的响应。
+            Some(Err(_)) => self.pending = None,
+            None => {
+                if let Some(pending) = self.pending.take() {
+                    self.cache.store(pending);
+                }
+            }
+        }
+
+        Poll::Ready(item)
+    }
+}
+
+/// 清除缓存请求参数
+#[derive(Deserialize)]
+pub struct PurgeQuery {
+    /// 客户端请求的路径（可带查询串），为空时清空全部缓存。
+    path: Option<String>,
+}
+
+/// `DELETE {purge_path}?path=/foo` 清除缓存，只允许本机访问。
+/// 缓存键按处理这个路径的路由计算，见 [`Route::cache_key`](crate::route::Route::cache_key)。
+pub async fn purge(
+    query: web::Query<PurgeQuery>,
+    peer_addr: Option<PeerAddr>,
+    routes: web::Data<Routes>,
+    cache: web::Data<Cache>,
+) -> Result<HttpResponse, Error> {
+    if !peer_addr.is_some_and(|PeerAddr(addr)| addr.ip().is_loopback()) {
+        return Err(error::ErrorForbidden("cache purge is only allowed from localhost"));
+    }
+
+    let count = match &query.path {
+        Some(path) => {
+            let (path, query) = match path.split_once('?') {
+                Some((p, q)) => (p, Some(q)),
+                None => (path.as_str(), None),
+            };
+            let route = routes
+                .find(path)
+                .ok_or_else(|| error::ErrorNotFound(format!("no route for {path}")))?;
+            cache.purge(&route.cache_key(path, query)).await
+        }
+        None => cache.clear().await,
+    };
+
+    Ok(HttpResponse::Ok().body(format!("purged {count} entries")))
+}
+
+/// 响应体 base64 编码存储
+mod base64_bytes {
+    use actix_web::web::Bytes;
+    use base64::{engine::general_purpose::STANDARD, Engine as _};
+    use serde::{de::Error as _, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(body: &Bytes, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(body))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Bytes, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        STANDARD.decode(text).map(Bytes::from).map_err(D::Error::custom)
+    }
+}
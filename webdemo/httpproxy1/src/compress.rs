@@ -0,0 +1,227 @@
+use std::{io, pin::Pin};
+
+use actix_http::encoding::{Decoder, Encoder};
+use actix_web::{
+    body::{BodySize, BodyStream, BoxBody, MessageBody},
+    dev::ResponseHead,
+    error::PayloadError,
+    http::{
+        header::{self, ContentEncoding, HeaderMap, HeaderValue},
+        Method, StatusCode,
+    },
+    HttpRequest, HttpResponse,
+};
+
+use crate::config::CompressionConfig;
+
+/// 客户端 `Accept-Encoding`
+/// https://www.rfc-editor.org/rfc/rfc9110#section-12.5.3
+struct AcceptEncoding(Vec<(String, f32)>);
+
+impl AcceptEncoding {
+    /// 没有 `Accept-Encoding` 时返回 `None`，代理不改变响应编码。
+    fn parse(headers: &HeaderMap) -> Option<Self> {
+        let mut codings = Vec::new();
+        for value in headers.get_all(header::ACCEPT_ENCODING) {
+            let Ok(value) = value.to_str() else {
+                continue;
+            };
+            for item in value.split(',') {
+                let mut parts = item.split(';');
+                let coding = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+                if coding.is_empty() {
+                    continue;
+                }
+                let q = parts
+                    .filter_map(|p| p.trim().strip_prefix("q="))
+                    .find_map(|q| q.trim().parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                codings.push((coding, q));
+            }
+        }
+
+        (!codings.is_empty() || headers.contains_key(header::ACCEPT_ENCODING)).then_some(Self(codings))
+    }
+
+    fn q(&self, coding: &str) -> f32 {
+        let find = |name: &str| self.0.iter().find(|(c, _)| c == name).map(|(_, q)| *q);
+        match find(coding).or_else(|| find("*")) {
+            Some(q) => q,
+            // 没有列出时 identity 总是可以接受
+            None if coding == "identity" => 1.0,
+            None => 0.0,
+        }
+    }
+}
+
+/// 路由的压缩设置
+pub struct Compression {
+    enabled: bool,
+    encodings: Vec<ContentEncoding>,
+    min_bytes: u64,
+    content_types: Vec<String>,
+}
+
+impl Compression {
+    pub fn new(config: &CompressionConfig) -> io::Result<Self> {
+        let encodings = config
+            .encodings
+            .iter()
+            .map(|e| match e.parse::<ContentEncoding>() {
+                Ok(ContentEncoding::Identity) | Err(_) => Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unsupported compression encoding {e}"),
+                )),
+                Ok(encoding) => Ok(encoding),
+            })
+            .collect::<io::Result<_>>()?;
+
+        Ok(Self {
+            enabled: config.enabled,
+            encodings,
+            min_bytes: config.min_bytes,
+            content_types: config.content_types.iter().map(|t| t.to_ascii_lowercase()).collect(),
+        })
+    }
+
+    /// 按客户端 `Accept-Encoding` 调整响应编码：客户端不接受的编码先解压，
+    /// 开启压缩时再用客户端接受的编码压缩。
+    pub fn negotiate(&self, req: &HttpRequest, res: HttpResponse) -> HttpResponse {
+        let Some(accept) = AcceptEncoding::parse(req.headers()) else {
+            return res;
+        };
+        // 部分响应的范围对应编码后的内容，不能改变编码。
+        if req.method() == Method::HEAD || res.status() == StatusCode::PARTIAL_CONTENT {
+            return res;
+        }
+
+        res.map_body(|head, body| self.apply(&accept, head, body))
+    }
+
+    fn apply(&self, accept: &AcceptEncoding, head: &mut ResponseHead, mut body: BoxBody) -> BoxBody {
+        let current = head
+            .headers()
+            .get(header::CONTENT_ENCODING)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim().to_ascii_lowercase());
+
+        let mut vary = false;
+        match current.as_deref() {
+            None | Some("identity") => {}
+            Some(coding) if accept.q(coding) > 0.0 => return body,
+            Some(coding) => match coding.parse::<ContentEncoding>() {
+                Ok(encoding) => {
+                    head.headers_mut().remove(header::CONTENT_ENCODING);
+                    head.headers_mut().remove(header::CONTENT_LENGTH);
+                    body = decode(body, encoding);
+                    vary = true;
+                }
+                // 无法解压，原样返回。
+                Err(_) => return body,
+            },
+        }
+
+        if self.enabled && self.compressible(head, &body) {
+            vary = true;
+            if let Some(encoding) = self.choose(accept) {
+                head.headers_mut().remove(header::CONTENT_LENGTH);
+                body = BoxBody::new(Encoder::response(encoding, head, body));
+            }
+        }
+
+        if vary {
+            add_vary(head);
+        }
+        body
+    }
+
+    fn compressible(&self, head: &ResponseHead, body: &BoxBody) -> bool {
+        let headers = head.headers();
+        if head.status.is_informational()
+            || matches!(head.status, StatusCode::NO_CONTENT | StatusCode::NOT_MODIFIED)
+            || headers.contains_key(header::CONTENT_ENCODING)
+        {
+            return false;
+        }
+
+        let no_transform = headers
+            .get_all(header::CACHE_CONTROL)
+            .filter_map(|v| v.to_str().ok())
+            .any(|v| v.to_ascii_lowercase().contains("no-transform"));
+        if no_transform {
+            return false;
+        }
+
+        let content_type = headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(';').next())
+            .map(|v| v.trim().to_ascii_lowercase());
+        let Some(content_type) = content_type else {
+            return false;
+        };
+        let type_matches = self.content_types.iter().any(|t| match t.strip_suffix('*') {
+            Some(prefix) => content_type.starts_with(prefix),
+            None => *t == content_type,
+        });
+        if !type_matches {
+            return false;
+        }
+
+        // 流式响应按上游的 `Content-Length` 判断，未知长度时压缩。
+        let length = match body.size() {
+            BodySize::None => return false,
+            BodySize::Sized(len) => Some(len),
+            BodySize::Stream => headers
+                .get(header::CONTENT_LENGTH)
+                .and_then(|v| v.to_str().ok()?.parse::<u64>().ok()),
+        };
+        length.is_none_or(|len| len >= self.min_bytes)
+    }
+
+    /// 客户端 q 值最高的编码，相同时按配置顺序。
+    fn choose(&self, accept: &AcceptEncoding) -> Option<ContentEncoding> {
+        let mut best: Option<(ContentEncoding, f32)> = None;
+        for encoding in &self.encodings {
+            let q = accept.q(encoding.as_str());
+            if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+                best = Some((*encoding, q));
+            }
+        }
+        best.map(|(encoding, _)| encoding)
+    }
+}
+
+/// `Vary` 加上 `Accept-Encoding`，去掉重复的值（`Encoder` 也会添加）。
+fn add_vary(head: &mut ResponseHead) {
+    let mut tokens: Vec<String> = Vec::new();
+    let values = head
+        .headers()
+        .get_all(header::VARY)
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|v| v.trim().to_owned())
+        .chain(["accept-encoding".to_owned()]);
+    for value in values {
+        if !value.is_empty() && !tokens.iter().any(|t| t.eq_ignore_ascii_case(&value)) {
+            tokens.push(value);
+        }
+    }
+
+    let vary = match tokens.iter().any(|t| t == "*") {
+        true => "*".to_owned(),
+        false => tokens.join(", "),
+    };
+    if let Ok(value) = HeaderValue::from_str(&vary) {
+        head.headers_mut().insert(header::VARY, value);
+    }
+}
+
+fn decode(mut body: BoxBody, encoding: ContentEncoding) -> BoxBody {
+    let stream = futures_util::stream::poll_fn(move |cx| {
+        Pin::new(&mut body)
+            .poll_next(cx)
+            .map(|chunk| chunk.map(|r| r.map_err(|e| PayloadError::Io(io::Error::other(e.to_string())))))
+    });
+    BoxBody::new(BodyStream::new(Decoder::new(stream, encoding)))
+}
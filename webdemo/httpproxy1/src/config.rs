@@ -0,0 +1,481 @@
+use std::{io, path::PathBuf, time::Duration};
+
+use serde::Deserialize;
+use url::Url;
+use webdemo_common::{AppConfig, BaseConfig, LogConfig, ServerConfig};
+
+use crate::{
+    rewrite::RewriteConfig,
+    upstream::{BackendKind, Http2},
+};
+
+/// 默认监听地址
+const DEFAULT_BIND: &str = "0.0.0.0:48083";
+
+/// 代理工作模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// 反向代理，所有请求转发到固定的 `upstream`。
+    Reverse,
+    /// 正向代理，客户端通过 `HTTP_PROXY` 使用，支持 `CONNECT` 隧道。
+    Forward,
+}
+
+/// 代理配置
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub log: LogConfig,
+    pub mode: Mode,
+    /// 反向代理目标
+    pub upstream: Url,
+    /// 正向代理设置
+    pub forward: ForwardConfig,
+    /// 反向代理响应缓存
+    pub cache: CacheConfig,
+    /// 默认上游超时，路由可覆盖。
+    pub timeouts: TimeoutConfig,
+    /// 默认重试策略，路由可覆盖。
+    pub retry: RetryConfig,
+    pub circuit_breaker: CircuitBreakerConfig,
+    /// 默认请求 / 响应改写规则，路由可覆盖。
+    pub rewrite: RewriteConfig,
+    /// 默认请求体上限（字节），超过返回 413，不设置时不限制。路由可覆盖。
+    pub max_body_bytes: Option<u64>,
+    /// 默认客户端 IP 访问控制，路由可覆盖。
+    pub ip_filter: IpFilterConfig,
+    /// 默认限流规则，路由可覆盖。
+    pub rate_limits: Vec<RateLimitConfig>,
+    /// 默认响应压缩设置，路由可覆盖。
+    pub compression: CompressionConfig,
+    /// JSON 访问日志
+    pub access_log: AccessLogConfig,
+    /// HAR 抓包
+    pub capture: CaptureConfig,
+    /// 默认录制 / 回放设置，路由可覆盖。
+    pub mock: MockConfig,
+    /// 反向代理路由，为空时使用 `/using-reqwest`、`/using-awc` 和 `/` 默认路由。
+    pub routes: Vec<RouteConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            server: ServerConfig::new(DEFAULT_BIND),
+            log: LogConfig::default(),
+            mode: Mode::Reverse,
+            upstream: Url::parse("https://baidu.com/").unwrap(),
+            forward: ForwardConfig::default(),
+            cache: CacheConfig::default(),
+            timeouts: TimeoutConfig::default(),
+            retry: RetryConfig::default(),
+            circuit_breaker: CircuitBreakerConfig::default(),
+            rewrite: RewriteConfig::default(),
+            max_body_bytes: None,
+            ip_filter: IpFilterConfig::default(),
+            rate_limits: Vec::new(),
+            compression: CompressionConfig::default(),
+            access_log: AccessLogConfig::default(),
+            capture: CaptureConfig::default(),
+            mock: MockConfig::default(),
+            routes: Vec::new(),
+        }
+    }
+}
+
+/// 正向代理设置
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ForwardConfig {
+    /// 允许访问的目标，为空时允许所有（除 `deny` 之外）。
+    /// 格式 `host`、`host:port`、`*.domain`、`*:port`，或者 IP、CIDR 网段（匹配解析后的地址）。
+    pub allow: Vec<String>,
+    /// 禁止访问的目标，优先于 `allow`。
+    pub deny: Vec<String>,
+    /// 设置后要求客户端提供 `Proxy-Authorization` Basic 认证。
+    pub auth: Option<BasicAuth>,
+}
+
+/// Basic 认证用户
+#[derive(Debug, Clone, Deserialize)]
+pub struct BasicAuth {
+    pub username: String,
+    pub password: String,
+}
+
+/// 响应缓存设置
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    pub enabled: bool,
+    /// 内存缓存总字节数，超出时淘汰最久未使用的条目。
+    pub max_bytes: usize,
+    /// 单个响应的最大字节数，更大的响应不缓存。
+    pub max_entry_bytes: usize,
+    /// 设置后缓存同时写入磁盘目录，内存未命中时从磁盘加载。
+    pub dir: Option<PathBuf>,
+    /// 清除缓存的路径，只接受本机 `DELETE` 请求。
+    pub purge_path: String,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_bytes: 64 * 1024 * 1024,
+            max_entry_bytes: 8 * 1024 * 1024,
+            dir: None,
+            purge_path: "/_httpproxy1/cache".to_owned(),
+        }
+    }
+}
+
+/// 上游超时，单位毫秒。连接、读取超时返回 504。
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct TimeoutConfig {
+    pub connect_ms: u64,
+    /// 等待响应头以及两次读取响应体之间的最长时间
+    pub read_ms: u64,
+    /// 整个请求（含响应体）的最长时间
+    pub total_ms: u64,
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        Self {
+            connect_ms: 5_000,
+            read_ms: 30_000,
+            total_ms: 120_000,
+        }
+    }
+}
+
+impl TimeoutConfig {
+    pub fn connect(&self) -> Duration {
+        Duration::from_millis(self.connect_ms)
+    }
+
+    pub fn read(&self) -> Duration {
+        Duration::from_millis(self.read_ms)
+    }
+
+    pub fn total(&self) -> Duration {
+        Duration::from_millis(self.total_ms)
+    }
+}
+
+/// 幂等请求的重试策略
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// 重试次数，不含第一次请求。
+    pub attempts: u32,
+    /// 退避基数，第 n 次重试随机等待 `[0, backoff_ms * 2^n]`。
+    pub backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    /// 请求体不超过该大小时先读入内存，才能重放重试。
+    pub max_replay_bytes: usize,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            attempts: 2,
+            backoff_ms: 100,
+            max_backoff_ms: 2_000,
+            max_replay_bytes: 64 * 1024,
+        }
+    }
+}
+
+/// 熔断器，每个上游一个。
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct CircuitBreakerConfig {
+    /// 连续失败次数达到后熔断，0 表示不熔断。
+    pub failure_threshold: u32,
+    /// 熔断持续时间，之后放行一个探测请求。
+    pub open_ms: u64,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            open_ms: 30_000,
+        }
+    }
+}
+
+/// 客户端 IP 访问控制，按连接的对端地址匹配，返回 403。
+/// 格式为 IP 或 CIDR，例如 `10.0.0.0/8`、`::1`。
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct IpFilterConfig {
+    /// 为空时允许所有（除 `deny` 之外）
+    pub allow: Vec<String>,
+    /// 优先于 `allow`
+    pub deny: Vec<String>,
+}
+
+/// 限流算法
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitAlgorithm {
+    /// 每个窗口最多 `limit` 个请求
+    FixedWindow,
+    /// 令牌桶，每个窗口补充 `limit` 个令牌，最多积累 `burst` 个。
+    #[default]
+    TokenBucket,
+}
+
+/// 限流维度
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitKey {
+    /// 客户端 IP
+    #[default]
+    Ip,
+    /// `header` 中的 API key，请求没有该头时规则不生效。
+    ApiKey,
+}
+
+/// 限流规则，超过时返回 429 和 `Retry-After`。
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub key: RateLimitKey,
+    /// API key 所在的请求头
+    pub header: String,
+    pub algorithm: RateLimitAlgorithm,
+    /// 每个窗口允许的请求数
+    pub limit: u64,
+    pub window_ms: u64,
+    /// 令牌桶容量，默认等于 `limit`。
+    pub burst: Option<u64>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            key: RateLimitKey::Ip,
+            header: "x-api-key".to_owned(),
+            algorithm: RateLimitAlgorithm::TokenBucket,
+            limit: 100,
+            window_ms: 1_000,
+            burst: None,
+        }
+    }
+}
+
+/// 响应压缩。上游响应的编码客户端不接受时总是解压。
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CompressionConfig {
+    /// 按客户端 `Accept-Encoding` 压缩未压缩的响应
+    pub enabled: bool,
+    /// 支持的编码，客户端 q 值相同时使用靠前的：`zstd`、`br`、`gzip`、`deflate`。
+    pub encodings: Vec<String>,
+    /// 已知长度小于该值的响应不压缩
+    pub min_bytes: u64,
+    /// 压缩的内容类型，`text/*` 匹配所有 `text/` 类型。
+    pub content_types: Vec<String>,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            encodings: ["zstd", "br", "gzip"].map(String::from).to_vec(),
+            min_bytes: 1024,
+            content_types: [
+                "text/*",
+                "application/json",
+                "application/javascript",
+                "application/xml",
+                "image/svg+xml",
+            ]
+            .map(String::from)
+            .to_vec(),
+        }
+    }
+}
+
+/// 反向代理访问日志，每个请求一行 JSON。
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AccessLogConfig {
+    pub enabled: bool,
+    /// 追加写入的文件，不设置时通过 `log` 输出（target `httpproxy1::access`）。
+    pub path: Option<PathBuf>,
+}
+
+impl Default for AccessLogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            path: None,
+        }
+    }
+}
+
+/// 把完整的请求 / 响应写入 HAR 文件，每个请求一个文件。
+/// 请求体和响应体原样记录，只应在本地调试时开启。
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CaptureConfig {
+    pub enabled: bool,
+    pub dir: PathBuf,
+    /// 请求体、响应体各自最多记录的字节数，超出部分截断。
+    pub max_body_bytes: usize,
+    /// 不记录值的头部，默认是认证头和 cookie，设为空时全部原样记录。
+    pub redact_headers: Vec<String>,
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: PathBuf::from("./httpproxy1/har"),
+            max_body_bytes: 1024 * 1024,
+            redact_headers: ["authorization", "proxy-authorization", "cookie", "set-cookie"]
+                .map(str::to_owned)
+                .to_vec(),
+        }
+    }
+}
+
+/// 录制 / 回放模式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MockMode {
+    /// 正常转发
+    #[default]
+    Off,
+    /// 转发并把上游响应保存到 `dir`
+    Record,
+    /// 只使用 `dir` 中的录制，不访问上游。
+    Replay,
+}
+
+/// 回放时找不到录制
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnMiss {
+    /// 返回 `miss_status`
+    #[default]
+    Error,
+    /// 转发给上游（不录制）
+    Passthrough,
+}
+
+/// 录制 / 回放上游响应，用于离线测试。
+/// 录制按方法、路径、查询字符串和 `key_headers` 匹配，不含请求体。
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MockConfig {
+    pub mode: MockMode,
+    pub dir: PathBuf,
+    /// 参与匹配的请求头，例如 `accept`。
+    pub key_headers: Vec<String>,
+    pub on_miss: OnMiss,
+    pub miss_status: u16,
+}
+
+impl Default for MockConfig {
+    fn default() -> Self {
+        Self {
+            mode: MockMode::Off,
+            dir: PathBuf::from("./httpproxy1/recordings"),
+            key_headers: Vec::new(),
+            on_miss: OnMiss::Error,
+            miss_status: 502,
+        }
+    }
+}
+
+impl MockConfig {
+    /// 只回放、不访问网络
+    pub fn offline(&self) -> bool {
+        self.mode == MockMode::Replay && self.on_miss == OnMiss::Error
+    }
+}
+
+/// 反向代理路由
+#[derive(Debug, Clone, Deserialize)]
+pub struct RouteConfig {
+    /// 路径前缀，`/` 匹配其他所有请求。
+    pub prefix: String,
+    /// 转发前去掉前缀
+    #[serde(default)]
+    pub strip_prefix: bool,
+    /// 默认使用顶层 `upstream`
+    pub upstream: Option<Url>,
+    /// 默认使用顶层 `timeouts`
+    pub timeouts: Option<TimeoutConfig>,
+    /// 默认使用顶层 `retry`
+    pub retry: Option<RetryConfig>,
+    /// 上游客户端 `reqwest`（默认）或 `awc`
+    #[serde(default)]
+    pub backend: BackendKind,
+    /// 上游 HTTP/2：`auto`（默认，ALPN 协商）、`always`、`never`
+    #[serde(default)]
+    pub http2: Http2,
+    /// 默认使用顶层 `max_body_bytes`
+    pub max_body_bytes: Option<u64>,
+    /// 默认使用顶层 `ip_filter`
+    pub ip_filter: Option<IpFilterConfig>,
+    /// 默认使用顶层 `rate_limits`
+    pub rate_limits: Option<Vec<RateLimitConfig>>,
+    /// 默认使用顶层 `compression`
+    pub compression: Option<CompressionConfig>,
+    /// 默认使用顶层 `rewrite`
+    pub rewrite: Option<RewriteConfig>,
+    /// 默认使用顶层 `mock`
+    pub mock: Option<MockConfig>,
+}
+
+impl RouteConfig {
+    pub fn new(prefix: &str, strip_prefix: bool) -> Self {
+        Self {
+            prefix: prefix.to_owned(),
+            strip_prefix,
+            upstream: None,
+            timeouts: None,
+            retry: None,
+            backend: BackendKind::default(),
+            http2: Http2::default(),
+            max_body_bytes: None,
+            ip_filter: None,
+            rate_limits: None,
+            compression: None,
+            rewrite: None,
+            mock: None,
+        }
+    }
+}
+
+impl Config {
+    /// `Loader` 的默认值。其他配置项的默认值由 serde 补上，这里只给出 `[server]` 和 `[log]`，
+    /// 配置文件中只写了 `[server] workers = 4` 时也能保留默认监听地址。
+    pub fn defaults() -> BaseConfig {
+        BaseConfig::new(DEFAULT_BIND)
+    }
+
+    pub fn from_toml(text: &str) -> io::Result<Config> {
+        toml::from_str(text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+impl AppConfig for Config {
+    fn server(&self) -> &ServerConfig {
+        &self.server
+    }
+
+    fn log(&self) -> &LogConfig {
+        &self.log
+    }
+}
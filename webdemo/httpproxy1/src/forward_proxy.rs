@@ -0,0 +1,329 @@
+use std::{
+    io,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Instant,
+};
+
+use actix_web::{
+    dev::PeerAddr,
+    rt::time::timeout,
+    error,
+    http::{header, Method, StatusCode},
+    web, Error, HttpRequest, HttpResponse,
+};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use futures_util::{StreamExt as _, TryStreamExt as _};
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use tokio::{
+    io::AsyncWriteExt as _,
+    net::{lookup_host, TcpStream},
+};
+use tokio_util::io::ReaderStream;
+
+use crate::{
+    config::{ForwardConfig, TimeoutConfig},
+    ip_filter::Cidr,
+    pipeline::HOP_BY_HOP,
+    resilience, upstream,
+};
+
+/// 正向代理状态
+pub struct ForwardProxy {
+    destinations: Destinations,
+    /// 期望的 Basic 认证 `username:password`
+    credentials: Option<String>,
+    client: reqwest::Client,
+    timeouts: TimeoutConfig,
+}
+
+impl ForwardProxy {
+    pub fn new(config: &ForwardConfig, timeouts: &TimeoutConfig) -> io::Result<Self> {
+        let parse = |rules: &[String]| {
+            rules
+                .iter()
+                .map(|r| HostRule::parse(r))
+                .collect::<io::Result<Arc<[_]>>>()
+        };
+        let destinations = Destinations {
+            allow: parse(&config.allow)?,
+            deny: parse(&config.deny)?,
+        };
+
+        // 不跟随重定向，也不使用环境变量里的代理（避免转发给自己）。
+        let client = reqwest::Client::builder()
+            .no_proxy()
+            .redirect(reqwest::redirect::Policy::none())
+            .connect_timeout(timeouts.connect())
+            .dns_resolver(Arc::new(destinations.clone()))
+            .build()
+            .map_err(io::Error::other)?;
+
+        Ok(Self {
+            destinations,
+            credentials: config.auth.as_ref().map(|a| format!("{}:{}", a.username, a.password)),
+            client,
+            timeouts: *timeouts,
+        })
+    }
+
+    /// scheme 不区分大小写，凭据按常量时间比较
+    fn authorized(&self, req: &HttpRequest) -> bool {
+        let Some(expected) = &self.credentials else {
+            return true;
+        };
+        let Some((scheme, token)) = req
+            .headers()
+            .get(header::PROXY_AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().split_once(' '))
+        else {
+            return false;
+        };
+        let credentials = STANDARD.decode(token.trim()).unwrap_or_default();
+        scheme.eq_ignore_ascii_case("basic") && constant_time_eq(&credentials, expected.as_bytes())
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// 目标访问控制。主机名规则匹配请求中的主机名，IP 和网段规则匹配解析后的地址，
+/// 所以 `127.1`、`2130706433`、`[::1]` 或解析到内网的域名都会被对应的网段规则拦住。
+#[derive(Clone)]
+struct Destinations {
+    allow: Arc<[HostRule]>,
+    deny: Arc<[HostRule]>,
+}
+
+impl Destinations {
+    /// 先检查 `deny`，`allow` 为空时放行。
+    /// `port` 为 `None` 时是 reqwest 解析域名，端口在发请求之前已经检查过：
+    /// 只看不带端口的 `deny` 规则，`allow` 规则忽略端口。
+    fn permits(&self, host: &str, ip: IpAddr, port: Option<u16>) -> bool {
+        let denied = |r: &HostRule| r.matches(host, ip) && port.map_or(r.port.is_none(), |p| r.matches_port(p));
+        let allowed = |r: &HostRule| r.matches(host, ip) && port.is_none_or(|p| r.matches_port(p));
+        if self.deny.iter().any(denied) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(allowed)
+    }
+
+    /// 解析目标，只返回允许访问的地址，一个都没有时返回 `PermissionDenied`。
+    async fn resolve(&self, host: &str, port: Option<u16>) -> io::Result<Vec<SocketAddr>> {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        let addrs: Vec<SocketAddr> = lookup_host((host.as_str(), port.unwrap_or(0)))
+            .await?
+            .filter(|addr| self.permits(&host, addr.ip(), port))
+            .collect();
+        match addrs.is_empty() {
+            true => Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("destination {host} not allowed"),
+            )),
+            false => Ok(addrs),
+        }
+    }
+}
+
+/// reqwest 连接之前再检查一次解析结果，避免检查之后 DNS 记录换成内网地址
+impl Resolve for Destinations {
+    fn resolve(&self, name: Name) -> Resolving {
+        let destinations = self.clone();
+        Box::pin(async move {
+            let addrs = destinations.resolve(name.as_str(), None).await?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// 访问控制规则：`host`、`host:port`、`*.domain`、`*:port`、`*`，
+/// 以及 IP 或 CIDR 网段，例如 `10.0.0.0/8`、`[::1]:443`
+#[derive(Debug)]
+struct HostRule {
+    host: HostPattern,
+    port: Option<u16>,
+}
+
+#[derive(Debug)]
+enum HostPattern {
+    Any,
+    Exact(String),
+    /// `*.domain` 只匹配子域名，保存为 `.domain`。
+    Suffix(String),
+    /// 匹配解析后的地址
+    Network(Cidr),
+}
+
+impl HostRule {
+    fn parse(rule: &str) -> io::Result<Self> {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid forward host rule: {rule:?}"),
+            )
+        };
+
+        let rule = rule.trim().to_ascii_lowercase();
+        // 带端口的 IPv6 地址需要写成 `[::1]:443`，不带端口时可以直接写 `::1` 或 `fc00::/7`
+        let (host, port) = if let Some(rest) = rule.strip_prefix('[') {
+            let (host, rest) = rest.split_once(']').ok_or_else(invalid)?;
+            (host.to_owned(), rest.strip_prefix(':'))
+        } else {
+            match rule.split_once(':') {
+                Some((host, port)) if !port.contains(':') => (host.to_owned(), Some(port)),
+                _ => (rule.clone(), None),
+            }
+        };
+
+        let port = match port {
+            None | Some("*") => None,
+            Some(p) => Some(p.parse().map_err(|_| invalid())?),
+        };
+        let host = match host.as_str() {
+            "" => return Err(invalid()),
+            "*" => HostPattern::Any,
+            h if h.contains('/') || h.parse::<IpAddr>().is_ok() => HostPattern::Network(h.parse()?),
+            h => match h.strip_prefix('*') {
+                Some(suffix) if suffix.starts_with('.') => HostPattern::Suffix(suffix.to_owned()),
+                Some(_) => return Err(invalid()),
+                None => HostPattern::Exact(h.to_owned()),
+            },
+        };
+
+        Ok(Self { host, port })
+    }
+
+    fn matches(&self, host: &str, ip: IpAddr) -> bool {
+        match &self.host {
+            HostPattern::Any => true,
+            HostPattern::Exact(h) => h == host,
+            HostPattern::Suffix(s) => host.ends_with(s.as_str()),
+            HostPattern::Network(cidr) => cidr.contains(ip),
+        }
+    }
+
+    fn matches_port(&self, port: u16) -> bool {
+        self.port.is_none_or(|p| p == port)
+    }
+}
+
+/// 正向代理入口，处理 `CONNECT` 隧道和 absolute-form 请求。
+pub async fn forward_proxy(
+    req: HttpRequest,
+    payload: web::Payload,
+    method: Method,
+    peer_addr: Option<PeerAddr>,
+    proxy: web::Data<ForwardProxy>,
+) -> Result<HttpResponse, Error> {
+    if !proxy.authorized(&req) {
+        return Ok(HttpResponse::build(StatusCode::PROXY_AUTHENTICATION_REQUIRED)
+            .insert_header((header::PROXY_AUTHENTICATE, "Basic realm=\"httpproxy1\""))
+            .finish());
+    }
+
+    let uri = req.uri();
+    let default_port = match uri.scheme_str() {
+        Some("https") => 443,
+        Some("http") => 80,
+        None if method == Method::CONNECT => 443,
+        _ => {
+            return Err(error::ErrorBadRequest(
+                "forward proxy requires absolute-form or CONNECT requests",
+            ))
+        }
+    };
+    let authority = uri
+        .authority()
+        .ok_or_else(|| error::ErrorBadRequest("missing request authority"))?;
+    let host = authority.host().trim_start_matches('[').trim_end_matches(']');
+    let port = authority.port_u16().unwrap_or(default_port);
+
+    let addrs = match timeout(proxy.timeouts.connect(), proxy.destinations.resolve(host, Some(port))).await {
+        Ok(Ok(addrs)) => addrs,
+        Ok(Err(e)) if e.kind() == io::ErrorKind::PermissionDenied => {
+            log::warn!("forward proxy denied {host}:{port}");
+            return Err(error::ErrorForbidden("destination not allowed"));
+        }
+        Ok(Err(e)) => return Err(error::ErrorBadGateway(e)),
+        Err(_) => return Err(error::ErrorGatewayTimeout("upstream connect timed out")),
+    };
+
+    if method == Method::CONNECT {
+        tunnel(host, &addrs, payload, &proxy.timeouts).await
+    } else {
+        forward_absolute(req, payload, method, peer_addr, &proxy).await
+    }
+}
+
+/// `CONNECT host:port` 打开 TCP 隧道，之后双向原样转发字节。
+/// 只连接检查过的地址，不再重新解析。
+async fn tunnel(
+    host: &str,
+    addrs: &[SocketAddr],
+    mut payload: web::Payload,
+    timeouts: &TimeoutConfig,
+) -> Result<HttpResponse, Error> {
+    let upstream = timeout(timeouts.connect(), TcpStream::connect(addrs))
+        .await
+        .map_err(|_| error::ErrorGatewayTimeout("upstream connect timed out"))?
+        .map_err(error::ErrorBadGateway)?;
+    log::info!("tunnel opened to {host} ({})", addrs[0]);
+
+    let (reader, mut writer) = upstream.into_split();
+
+    actix_web::rt::spawn(async move {
+        while let Some(Ok(chunk)) = payload.next().await {
+            if writer.write_all(&chunk).await.is_err() {
+                break;
+            }
+        }
+        let _ = writer.shutdown().await;
+    });
+
+    Ok(HttpResponse::Ok().streaming(ReaderStream::new(reader)))
+}
+
+/// absolute-form 请求（`GET http://host/path`）转发到请求的源站。
+async fn forward_absolute(
+    req: HttpRequest,
+    payload: web::Payload,
+    method: Method,
+    peer_addr: Option<PeerAddr>,
+    proxy: &ForwardProxy,
+) -> Result<HttpResponse, Error> {
+    let mut forwarded_req = proxy
+        .client
+        .request(method, req.uri().to_string())
+        .timeout(proxy.timeouts.total())
+        .body(upstream::reqwest_body(payload.boxed_local()));
+
+    for (name, value) in req.headers().iter() {
+        if name != header::HOST && !HOP_BY_HOP.contains(&name.as_str()) {
+            forwarded_req = forwarded_req.header(name.clone(), value.clone());
+        }
+    }
+
+    if let Some(PeerAddr(addr)) = peer_addr {
+        forwarded_req = forwarded_req.header("x-forwarded-for", addr.ip().to_string());
+    }
+
+    let deadline = Instant::now() + proxy.timeouts.total();
+    let res = match timeout(proxy.timeouts.read(), forwarded_req.send()).await {
+        Ok(res) => res.map_err(resilience::upstream_error)?,
+        Err(_) => return Err(resilience::read_timeout_error()),
+    };
+
+    let mut client_resp = HttpResponse::build(res.status());
+    for (name, value) in res.headers().iter() {
+        if !HOP_BY_HOP.contains(&name.as_str()) {
+            client_resp.append_header((name.clone(), value.clone()));
+        }
+    }
+
+    let body = res.bytes_stream().map_err(resilience::upstream_error);
+    let body = resilience::read_timeout(body, proxy.timeouts.read(), deadline);
+    Ok(client_resp.streaming(body))
+}
@@ -0,0 +1,93 @@
+use std::{io, sync::Arc};
+
+use actix_web::web;
+
+pub mod access_log;
+pub mod cache;
+pub mod compress;
+pub mod config;
+pub mod forward_proxy;
+pub mod ip_filter;
+pub mod mock;
+pub mod pipeline;
+pub mod rate_limit;
+pub mod resilience;
+pub mod rewrite;
+pub mod route;
+pub mod upstream;
+
+use access_log::AccessLog;
+use cache::Cache;
+use config::Config;
+use rate_limit::{LimiterStore, MemoryStore};
+use route::{Route, Routes};
+
+/// 反向代理共享状态，在 `HttpServer::new` 之外创建，
+/// 每个 worker 调用 `configure` 注册路由。
+pub struct ReverseProxy {
+    routes: web::Data<Routes>,
+    cache: Option<web::Data<Cache>>,
+    purge_path: String,
+    access_log: web::Data<AccessLog>,
+}
+
+impl ReverseProxy {
+    /// 限流状态保存在内存中
+    pub fn new(config: &Config) -> io::Result<Self> {
+        Self::with_limiter_store(config, Arc::new(MemoryStore::default()))
+    }
+
+    /// 使用共享的限流状态存储
+    pub fn with_limiter_store(config: &Config, limiter_store: Arc<dyn LimiterStore>) -> io::Result<Self> {
+        let routes = Route::from_config(config, limiter_store)?
+            .into_iter()
+            .map(web::Data::new)
+            .collect();
+        let routes = web::Data::new(Routes(routes));
+
+        let cache = if config.cache.enabled {
+            log::info!("response cache enabled, purge at {}", config.cache.purge_path);
+            Some(web::Data::new(Cache::new(&config.cache)?))
+        } else {
+            None
+        };
+
+        Ok(Self {
+            routes,
+            cache,
+            purge_path: config.cache.purge_path.clone(),
+            access_log: web::Data::new(AccessLog::new(&config.access_log, &config.capture)?),
+        })
+    }
+
+    pub fn routes(&self) -> impl Iterator<Item = &Route> {
+        self.routes.0.iter().map(|r| r.get_ref())
+    }
+
+    pub fn configure(&self, cfg: &mut web::ServiceConfig) {
+        cfg.app_data(self.routes.clone())
+            .app_data(self.access_log.clone());
+
+        if let Some(cache) = &self.cache {
+            cfg.app_data(cache.clone())
+                .route(&self.purge_path, web::delete().to(cache::purge));
+        }
+
+        // 前缀长的路由先注册，`/` 作为默认服务。
+        for route in &self.routes.0 {
+            let client = web::Data::new(route.client());
+            if route.is_default() {
+                cfg.app_data(route.clone())
+                    .app_data(client)
+                    .default_service(web::to(pipeline::forward));
+            } else {
+                cfg.service(
+                    web::scope(&route.prefix)
+                        .app_data(route.clone())
+                        .app_data(client)
+                        .default_service(web::to(pipeline::forward)),
+                );
+            }
+        }
+    }
+}
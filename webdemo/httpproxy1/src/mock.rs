@@ -0,0 +1,326 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::Arc,
+    task::Poll,
+};
+
+use actix_web::{
+    error,
+    http::{
+        header::{self, HeaderMap, HeaderName, HeaderValue},
+        StatusCode, Version,
+    },
+    web::{Bytes, BytesMut},
+    Error,
+};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use futures_util::{future::LocalBoxFuture, FutureExt as _, StreamExt as _};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{MockConfig, MockMode, OnMiss},
+    upstream::{Body, UpstreamClient, UpstreamRequest, UpstreamResponse},
+};
+
+/// 录制目录，同一路由的所有 worker 共享。
+pub struct Recordings {
+    mode: MockMode,
+    dir: PathBuf,
+    key_headers: Vec<HeaderName>,
+    on_miss: OnMiss,
+    miss_status: StatusCode,
+}
+
+impl Recordings {
+    /// `mode = "off"` 时返回 `None`
+    pub fn new(config: &MockConfig) -> io::Result<Option<Self>> {
+        let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+
+        let miss_status = StatusCode::from_u16(config.miss_status)
+            .map_err(|_| invalid(format!("invalid mock miss_status {}", config.miss_status)))?;
+        let key_headers = config
+            .key_headers
+            .iter()
+            .map(|n| {
+                HeaderName::from_bytes(n.as_bytes())
+                    .map_err(|e| invalid(format!("invalid mock key header {n}: {e}")))
+            })
+            .collect::<io::Result<_>>()?;
+
+        match config.mode {
+            MockMode::Off => return Ok(None),
+            MockMode::Record => fs::create_dir_all(&config.dir)?,
+            MockMode::Replay => {
+                if !config.dir.is_dir() {
+                    log::warn!("mock replay dir {} does not exist", config.dir.display());
+                }
+            }
+        }
+
+        Ok(Some(Self {
+            mode: config.mode,
+            dir: config.dir.clone(),
+            key_headers,
+            on_miss: config.on_miss,
+            miss_status,
+        }))
+    }
+
+    pub fn mode(&self) -> MockMode {
+        self.mode
+    }
+
+    /// 匹配用的请求头
+    fn request_headers(&self, req: &UpstreamRequest) -> BTreeMap<String, String> {
+        self.key_headers
+            .iter()
+            .filter(|name| req.headers.contains_key(*name))
+            .map(|name| {
+                let values: Vec<_> = req
+                    .headers
+                    .get_all(name)
+                    .map(|v| String::from_utf8_lossy(v.as_bytes()).into_owned())
+                    .collect();
+                (name.as_str().to_owned(), values.join(", "))
+            })
+            .collect()
+    }
+
+    /// 录制文件路径：可读的方法和路径，加上完整匹配键的哈希。
+    fn path(&self, req: &UpstreamRequest) -> PathBuf {
+        let mut key = format!("{} {}\n", req.method, path_and_query(req));
+        for (name, value) in self.request_headers(req) {
+            key.push_str(&format!("{name}: {value}\n"));
+        }
+
+        let readable: String = req
+            .url
+            .path()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .take(64)
+            .collect();
+        self.dir
+            .join(format!("{}{readable}-{:016x}.json", req.method, fnv1a(key.as_bytes())))
+    }
+}
+
+fn path_and_query(req: &UpstreamRequest) -> String {
+    match req.url.query() {
+        Some(query) => format!("{}?{query}", req.url.path()),
+        None => req.url.path().to_owned(),
+    }
+}
+
+/// FNV-1a，录制文件可能提交到仓库，文件名需要跨版本稳定。
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// 录制的上游响应，文本响应体直接保存以便查看和修改。
+#[derive(Serialize, Deserialize)]
+struct Recording {
+    method: String,
+    path: String,
+    /// 参与匹配的请求头
+    #[serde(default)]
+    request_headers: BTreeMap<String, String>,
+    status: u16,
+    headers: Vec<(String, String)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body_base64: Option<String>,
+}
+
+impl Recording {
+    fn body(&self) -> Result<Bytes, Error> {
+        match (&self.body, &self.body_base64) {
+            (_, Some(encoded)) => STANDARD
+                .decode(encoded)
+                .map(Bytes::from)
+                .map_err(error::ErrorInternalServerError),
+            (Some(text), None) => Ok(Bytes::from(text.clone())),
+            (None, None) => Ok(Bytes::new()),
+        }
+    }
+
+    fn response(&self) -> Result<UpstreamResponse, Error> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                headers.append(name, value);
+            }
+        }
+
+        let status = StatusCode::from_u16(self.status).map_err(error::ErrorInternalServerError)?;
+        let body = self.body()?;
+        // 录制可能被手动修改过。HEAD 响应没有响应体，保留录制的长度。
+        if !body.is_empty() {
+            headers.insert(header::CONTENT_LENGTH, HeaderValue::from(body.len()));
+        }
+        Ok(UpstreamResponse {
+            status,
+            version: Version::HTTP_11,
+            headers,
+            body: futures_util::stream::once(async move { Ok(body) }).boxed_local(),
+        })
+    }
+}
+
+fn read_recording(path: &Path) -> io::Result<Option<Recording>> {
+    match fs::read(path) {
+        Ok(data) => serde_json::from_slice(&data).map(Some).map_err(io::Error::from),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn write_recording(path: &Path, recording: &Recording) -> io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, serde_json::to_vec_pretty(recording)?)?;
+    fs::rename(tmp, path)
+}
+
+/// 录制或回放的上游客户端，包装路由的真实后端。
+pub struct MockClient {
+    inner: Box<dyn UpstreamClient>,
+    recordings: Arc<Recordings>,
+}
+
+impl MockClient {
+    pub fn new(inner: Box<dyn UpstreamClient>, recordings: Arc<Recordings>) -> Self {
+        Self { inner, recordings }
+    }
+
+    fn replay(&self, req: &UpstreamRequest, body: Body) -> LocalBoxFuture<'static, Result<UpstreamResponse, Error>> {
+        let path = self.recordings.path(req);
+        let miss_status = self.recordings.miss_status;
+        let passthrough = match self.recordings.on_miss {
+            OnMiss::Passthrough => Some(self.inner.send(req, body)),
+            OnMiss::Error => None,
+        };
+        let what = format!("{} {}", req.method, req.url);
+
+        async move {
+            let found = {
+                let path = path.clone();
+                actix_web::rt::task::spawn_blocking(move || read_recording(&path))
+                    .await
+                    .map_err(error::ErrorInternalServerError)?
+            };
+
+            match found {
+                Ok(Some(recording)) => return recording.response(),
+                Ok(None) => {}
+                Err(e) => log::warn!("failed to read recording {}: {e}", path.display()),
+            }
+
+            match passthrough {
+                Some(send) => send.await,
+                None => {
+                    log::warn!("no recording for {what} ({})", path.display());
+                    let mut headers = HeaderMap::new();
+                    headers.insert(HeaderName::from_static("x-mock"), HeaderValue::from_static("MISS"));
+                    Ok(UpstreamResponse {
+                        status: miss_status,
+                        version: Version::HTTP_11,
+                        headers,
+                        body: futures_util::stream::once(async move {
+                            Ok(Bytes::from(format!("no recording for {what}")))
+                        })
+                        .boxed_local(),
+                    })
+                }
+            }
+        }
+        .boxed_local()
+    }
+
+    fn record(&self, req: &UpstreamRequest, body: Body) -> LocalBoxFuture<'static, Result<UpstreamResponse, Error>> {
+        let path = self.recordings.path(req);
+        let recording = Recording {
+            method: req.method.to_string(),
+            path: path_and_query(req),
+            request_headers: self.recordings.request_headers(req),
+            status: 0,
+            headers: Vec::new(),
+            body: None,
+            body_base64: None,
+        };
+        let send = self.inner.send(req, body);
+
+        async move {
+            let mut res = send.await?;
+            let mut pending = Some(Recording {
+                status: res.status.as_u16(),
+                headers: res
+                    .headers
+                    .iter()
+                    .map(|(n, v)| (n.as_str().to_owned(), String::from_utf8_lossy(v.as_bytes()).into_owned()))
+                    .collect(),
+                ..recording
+            });
+
+            // 响应体转发给客户端的同时收集，完整读完后写入文件。
+            let collected = Rc::new(RefCell::new(Some(BytesMut::new())));
+            let sink = collected.clone();
+            let body = std::mem::replace(&mut res.body, futures_util::stream::empty().boxed_local());
+            res.body = body
+                .inspect(move |chunk| {
+                    let mut sink = sink.borrow_mut();
+                    match chunk {
+                        Ok(chunk) => {
+                            if let Some(buf) = sink.as_mut() {
+                                buf.extend_from_slice(chunk);
+                            }
+                        }
+                        // 不完整的响应不录制
+                        Err(_) => *sink = None,
+                    }
+                })
+                .chain(futures_util::stream::poll_fn(move |_| {
+                    if let (Some(buf), Some(mut recording)) = (collected.borrow_mut().take(), pending.take()) {
+                        match std::str::from_utf8(&buf) {
+                            Ok(text) => recording.body = Some(text.to_owned()),
+                            Err(_) => recording.body_base64 = Some(STANDARD.encode(&buf)),
+                        }
+                        let path = path.clone();
+                        actix_web::rt::task::spawn_blocking(move || {
+                            match write_recording(&path, &recording) {
+                                Ok(()) => log::info!("recorded {} {}", recording.method, recording.path),
+                                Err(e) => log::warn!("failed to write recording {}: {e}", path.display()),
+                            }
+                        });
+                    }
+                    Poll::Ready(None)
+                }))
+                .boxed_local();
+
+            Ok(res)
+        }
+        .boxed_local()
+    }
+}
+
+impl UpstreamClient for MockClient {
+    fn send(
+        &self,
+        req: &UpstreamRequest,
+        body: Body,
+    ) -> LocalBoxFuture<'static, Result<UpstreamResponse, Error>> {
+        match self.recordings.mode {
+            MockMode::Replay => self.replay(req, body),
+            MockMode::Record | MockMode::Off => self.record(req, body),
+        }
+    }
+}
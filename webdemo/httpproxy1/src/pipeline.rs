@@ -0,0 +1,253 @@
+use std::time::Instant;
+
+use actix_web::{
+    dev::PeerAddr,
+    error,
+    http::{
+        header::{self, HeaderMap, HeaderName, HeaderValue},
+        StatusCode,
+    },
+    rt::time::timeout,
+    web, Error, HttpRequest, HttpResponse, HttpResponseBuilder,
+};
+
+use futures_util::StreamExt as _;
+
+use crate::{
+    access_log::{self, AccessLog, Exchange},
+    cache::{self, Cache, Lookup},
+    resilience::{self, BodyFault, RequestBody},
+    route::Route,
+    upstream::{UpstreamClient, UpstreamRequest, UpstreamResponse},
+};
+
+/// 逐跳头部，代理不能转发。
+/// https://www.rfc-editor.org/rfc/rfc9110#section-7.6.1
+pub const HOP_BY_HOP: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// `Connection` 头里列出的也是逐跳头部
+fn connection_tokens(headers: &HeaderMap) -> Vec<String> {
+    headers
+        .get_all(header::CONNECTION)
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|t| t.trim().to_ascii_lowercase())
+        .collect()
+}
+
+fn forwardable(name: &HeaderName, connection: &[String]) -> bool {
+    !HOP_BY_HOP.contains(&name.as_str()) && !connection.iter().any(|t| t == name.as_str())
+}
+
+/// 客户端请求转换为上游请求：改写 URL，去掉逐跳头部和 `Host`，加上 `X-Forwarded-*`。
+pub fn upstream_request(req: &HttpRequest, route: &Route, peer_addr: Option<PeerAddr>) -> UpstreamRequest {
+    let connection = connection_tokens(req.headers());
+    let mut headers = HeaderMap::new();
+    for (name, value) in req.headers().iter() {
+        if name != header::HOST && forwardable(name, &connection) {
+            headers.append(name.clone(), value.clone());
+        }
+    }
+
+    // TODO: 此示例只实现 X-Forwarded-* 代理头
+    // X-Forwarded-For header but not the official Forwarded one.
+    if let Some(PeerAddr(addr)) = peer_addr {
+        let forwarded_for = match req.headers().get("x-forwarded-for").and_then(|v| v.to_str().ok()) {
+            Some(prior) => format!("{prior}, {}", addr.ip()),
+            None => addr.ip().to_string(),
+        };
+        if let Ok(value) = HeaderValue::from_str(&forwarded_for) {
+            headers.insert(HeaderName::from_static("x-forwarded-for"), value);
+        }
+    }
+
+    let conn = req.connection_info();
+    for (name, value) in [("x-forwarded-proto", conn.scheme()), ("x-forwarded-host", conn.host())] {
+        let name = HeaderName::from_static(name);
+        if !headers.contains_key(&name) {
+            if let Ok(value) = HeaderValue::from_str(value) {
+                headers.insert(name, value);
+            }
+        }
+    }
+
+    route.rewrite.request(&mut headers);
+
+    UpstreamRequest {
+        method: req.method().clone(),
+        url: route.upstream_url(req),
+        headers,
+    }
+}
+
+/// 上游响应转换为客户端响应，去掉逐跳头部。
+pub fn client_response(res: &UpstreamResponse) -> HttpResponseBuilder {
+    let connection = connection_tokens(&res.headers);
+    let mut client_resp = HttpResponse::build(res.status);
+    for (name, value) in res.headers.iter() {
+        if forwardable(name, &connection) {
+            client_resp.append_header((name.clone(), value.clone()));
+        }
+    }
+    client_resp
+}
+
+/// 反向代理入口，路由选择的后端（`awc` 或 `reqwest`）共用这一流程：
+/// 缓存、熔断、重试、超时、压缩，响应结束后写访问日志。
+pub async fn forward(
+    req: HttpRequest,
+    payload: web::Payload,
+    peer_addr: Option<PeerAddr>,
+    route: web::Data<Route>,
+    client: web::Data<Box<dyn UpstreamClient>>,
+    cache: Option<web::Data<Cache>>,
+    access_log: web::Data<AccessLog>,
+) -> HttpResponse {
+    let mut exchange = access_log.start(&req, &route, peer_addr);
+    let client = client.as_ref().as_ref();
+    let result = proxy(&req, payload, peer_addr, &route, client, cache, &mut exchange)
+        .await
+        .map(|mut res| {
+            route.rewrite.redirects(&req, &route.target(), res.headers_mut());
+            let res = exchange.tee_response(res);
+            route.compression.negotiate(&req, res)
+        });
+    access_log::record(access_log, exchange, result)
+}
+
+async fn proxy(
+    req: &HttpRequest,
+    payload: web::Payload,
+    peer_addr: Option<PeerAddr>,
+    route: &Route,
+    client: &dyn UpstreamClient,
+    cache: Option<web::Data<Cache>>,
+    exchange: &mut Exchange,
+) -> Result<HttpResponse, Error> {
+    let client_ip = peer_addr.map(|PeerAddr(addr)| addr.ip());
+    if !route.ip_filter.is_empty() && !client_ip.is_some_and(|ip| route.ip_filter.allows(ip)) {
+        return Err(error::ErrorForbidden("client address not allowed"));
+    }
+    if let Err(res) = route.rate_limiter.check(req, client_ip).await {
+        return Ok(res);
+    }
+
+    resilience::check_content_length(req, route.max_body_bytes)?;
+
+    let mut upstream_req = upstream_request(req, route, peer_addr);
+    let deadline = Instant::now() + route.timeouts.total();
+
+    // 新鲜的缓存直接响应，过期的带上验证器请求上游。
+    let cache_key = route.cache_key(req.uri().path(), req.uri().query());
+    let mut stale = None;
+    if let Some(cache) = cache.as_ref().filter(|_| cache::request_cacheable(req)) {
+        match cache.lookup(&cache_key, req).await {
+            Lookup::Fresh(entry) => {
+                exchange.cache = Some("HIT");
+                return Ok(entry.response(req, "HIT"));
+            }
+            Lookup::Stale(entry) => {
+                if let Some(etag) = entry.etag().and_then(|v| HeaderValue::from_str(v).ok()) {
+                    upstream_req.headers.insert(header::IF_NONE_MATCH, etag);
+                }
+                if let Some(lm) = entry.last_modified().and_then(|v| HeaderValue::from_str(v).ok()) {
+                    upstream_req.headers.insert(header::IF_MODIFIED_SINCE, lm);
+                }
+                stale = Some(entry);
+            }
+            Lookup::Miss => {}
+        }
+    }
+
+    // 上游熔断时直接返回 503
+    if let Err(retry_after) = route.breaker.acquire() {
+        return Ok(resilience::circuit_open(retry_after));
+    }
+
+    // 请求体可以重放时读入内存，失败后重试；否则流式转发只发送一次。
+    // 流式请求体超过上限或客户端断开时，上游请求中止并返回对应的错误。
+    let fault = BodyFault::default();
+    let mut body = if resilience::replayable(req, &route.retry) {
+        // 没有 `Content-Length` 的请求没经过 `check_content_length`，读取时同样检查路由的上限
+        let limit = route.max_body_bytes.unwrap_or(u64::MAX).min(route.retry.max_replay_bytes as u64);
+        let body = resilience::read_body(payload, limit).await?;
+        exchange.capture_request(&body);
+        RequestBody::Replay(body)
+    } else {
+        let stream = exchange.tee_request(payload.boxed_local());
+        RequestBody::Stream(Some(fault.watch(stream, route.max_body_bytes)))
+    };
+
+    exchange.upstream = Some(upstream_req.url.clone());
+    let sent = Instant::now();
+    let mut res = loop {
+        exchange.attempts += 1;
+        let wait = route.timeouts.read().min(deadline.saturating_duration_since(Instant::now()));
+        let result = match timeout(wait, client.send(&upstream_req, body.take())).await {
+            Ok(result) => result,
+            Err(_) => Err(resilience::read_timeout_error()),
+        };
+
+        if let (Err(_), Some(e)) = (&result, fault.error()) {
+            return Err(e);
+        }
+
+        let failed = match &result {
+            Ok(res) => resilience::retryable_status(res.status),
+            Err(_) => true,
+        };
+        if !failed {
+            route.breaker.success();
+            break result?;
+        }
+        route.breaker.failure();
+
+        if !body.replayable() || exchange.attempts > route.retry.attempts {
+            break result?;
+        }
+
+        let wait = resilience::backoff(&route.retry, exchange.attempts);
+        log::warn!("retrying {} in {wait:?}, attempt {}", upstream_req.url, exchange.attempts);
+        actix_web::rt::time::sleep(wait).await;
+
+        if let Err(retry_after) = route.breaker.acquire() {
+            return Ok(resilience::circuit_open(retry_after));
+        }
+    };
+    exchange.upstream_latency = Some(sent.elapsed());
+
+    if let (Some(cache), Some(entry)) = (&cache, stale) {
+        if res.status == StatusCode::NOT_MODIFIED {
+            exchange.cache = Some("HIT");
+            let entry = cache.revalidated(&cache_key, req, entry, &res.headers);
+            return Ok(entry.response(req, "HIT"));
+        }
+    }
+
+    // 缓存保存的是改写后的响应，依赖客户端 `Host` 的重定向在 `forward` 中改写
+    route.rewrite.response(&route.target(), &mut res);
+    let mut client_resp = client_response(&res);
+
+    let body = resilience::read_timeout(res.body, route.timeouts.read(), deadline);
+    match cache {
+        Some(cache) => {
+            exchange.cache = Some("MISS");
+            client_resp.insert_header(("x-cache", "MISS"));
+            let body = cache
+                .into_inner()
+                .fill(&cache_key, req, res.status, &res.headers, body);
+            Ok(client_resp.streaming(body))
+        }
+        None => Ok(client_resp.streaming(body)),
+    }
+}
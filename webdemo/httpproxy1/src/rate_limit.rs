@@ -0,0 +1,191 @@
+use std::{
+    collections::HashMap,
+    io,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use actix_web::{http::header, HttpRequest, HttpResponse};
+use futures_util::future::{self, LocalBoxFuture};
+
+use crate::config::{RateLimitAlgorithm, RateLimitConfig, RateLimitKey};
+
+/// 内存存储超过这么多个键时清理过期的状态
+const SWEEP_THRESHOLD: usize = 10_000;
+
+/// 限流规则
+#[derive(Debug, Clone, Copy)]
+pub struct Quota {
+    pub algorithm: RateLimitAlgorithm,
+    /// 每个窗口的请求数
+    pub limit: u64,
+    pub window: Duration,
+    /// 令牌桶容量
+    pub burst: u64,
+}
+
+/// 限流状态存储，默认在内存中。换成共享存储（例如 Redis）后多个代理实例共用配额。
+pub trait LimiterStore: Send + Sync {
+    /// 消耗 `key` 的一个配额，超限时返回需要等待的时间。
+    fn acquire(&self, key: String, quota: Quota) -> LocalBoxFuture<'_, Result<(), Duration>>;
+}
+
+enum Bucket {
+    Window { start: Instant, count: u64 },
+    Tokens { tokens: f64, updated: Instant },
+}
+
+impl Bucket {
+    fn new(quota: &Quota, now: Instant) -> Self {
+        match quota.algorithm {
+            RateLimitAlgorithm::FixedWindow => Bucket::Window { start: now, count: 0 },
+            RateLimitAlgorithm::TokenBucket => Bucket::Tokens {
+                tokens: quota.burst as f64,
+                updated: now,
+            },
+        }
+    }
+
+    fn acquire(&mut self, quota: &Quota, now: Instant) -> Result<(), Duration> {
+        match self {
+            Bucket::Window { start, count } => {
+                if now.duration_since(*start) >= quota.window {
+                    *start = now;
+                    *count = 0;
+                }
+                if *count < quota.limit {
+                    *count += 1;
+                    Ok(())
+                } else {
+                    Err(quota.window - now.duration_since(*start))
+                }
+            }
+            Bucket::Tokens { tokens, updated } => {
+                let per_sec = quota.limit as f64 / quota.window.as_secs_f64();
+                *tokens = (*tokens + now.duration_since(*updated).as_secs_f64() * per_sec)
+                    .min(quota.burst as f64);
+                *updated = now;
+                if *tokens >= 1.0 {
+                    *tokens -= 1.0;
+                    Ok(())
+                } else {
+                    Err(Duration::from_secs_f64((1.0 - *tokens) / per_sec))
+                }
+            }
+        }
+    }
+
+    /// 状态已经回到初始值，可以丢弃。
+    fn idle(&self, quota: &Quota, now: Instant) -> bool {
+        match self {
+            Bucket::Window { start, .. } => now.duration_since(*start) >= quota.window,
+            Bucket::Tokens { tokens, updated } => {
+                let per_sec = quota.limit as f64 / quota.window.as_secs_f64();
+                tokens + now.duration_since(*updated).as_secs_f64() * per_sec >= quota.burst as f64
+            }
+        }
+    }
+}
+
+/// 内存中的限流状态，所有 worker 共享。
+#[derive(Default)]
+pub struct MemoryStore {
+    buckets: Mutex<HashMap<String, (Bucket, Quota)>>,
+}
+
+impl LimiterStore for MemoryStore {
+    fn acquire(&self, key: String, quota: Quota) -> LocalBoxFuture<'_, Result<(), Duration>> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.len() >= SWEEP_THRESHOLD {
+            buckets.retain(|_, (bucket, quota)| !bucket.idle(quota, now));
+        }
+
+        let (bucket, _) = buckets
+            .entry(key)
+            .or_insert_with(|| (Bucket::new(&quota, now), quota));
+        let result = bucket.acquire(&quota, now);
+        Box::pin(future::ready(result))
+    }
+}
+
+/// 一条限流规则
+struct Rule {
+    key: RateLimitKey,
+    header: header::HeaderName,
+    quota: Quota,
+}
+
+/// 路由的限流规则，所有规则都通过才放行。
+pub struct RateLimiter {
+    /// 区分不同路由的状态
+    scope: String,
+    rules: Vec<Rule>,
+    store: Arc<dyn LimiterStore>,
+}
+
+impl RateLimiter {
+    pub fn new(scope: &str, configs: &[RateLimitConfig], store: Arc<dyn LimiterStore>) -> io::Result<Self> {
+        let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+
+        let rules = configs
+            .iter()
+            .map(|config| {
+                if config.limit == 0 || config.window_ms == 0 {
+                    return Err(invalid(format!("rate limit for {scope} needs limit and window_ms > 0")));
+                }
+                let header = header::HeaderName::from_bytes(config.header.as_bytes())
+                    .map_err(|e| invalid(format!("invalid rate limit header {}: {e}", config.header)))?;
+
+                Ok(Rule {
+                    key: config.key,
+                    header,
+                    quota: Quota {
+                        algorithm: config.algorithm,
+                        limit: config.limit,
+                        window: Duration::from_millis(config.window_ms),
+                        burst: config.burst.unwrap_or(config.limit).max(1),
+                    },
+                })
+            })
+            .collect::<io::Result<_>>()?;
+
+        Ok(Self {
+            scope: scope.to_owned(),
+            rules,
+            store,
+        })
+    }
+
+    /// 检查所有规则，超限时返回 429 响应。
+    pub async fn check(&self, req: &HttpRequest, client: Option<IpAddr>) -> Result<(), HttpResponse> {
+        for (i, rule) in self.rules.iter().enumerate() {
+            let key = match rule.key {
+                RateLimitKey::Ip => client.map(|ip| format!("ip:{ip}")),
+                RateLimitKey::ApiKey => req
+                    .headers()
+                    .get(&rule.header)
+                    .map(|v| format!("key:{}", String::from_utf8_lossy(v.as_bytes()))),
+            };
+            let Some(key) = key else {
+                continue;
+            };
+
+            let key = format!("{}#{i}|{key}", self.scope);
+            if let Err(retry_after) = self.store.acquire(key, rule.quota).await {
+                return Err(too_many_requests(retry_after));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 429，`Retry-After` 向上取整到秒。
+pub fn too_many_requests(retry_after: Duration) -> HttpResponse {
+    let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    HttpResponse::TooManyRequests()
+        .insert_header((header::RETRY_AFTER, secs.max(1).to_string()))
+        .body("rate limit exceeded")
+}
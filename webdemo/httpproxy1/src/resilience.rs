@@ -0,0 +1,300 @@
+use std::{
+    cell::Cell,
+    rc::Rc,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use actix_web::{
+    error::{self, PayloadError},
+    http::{header, Method, StatusCode},
+    web::{self, Bytes, BytesMut},
+    Error, HttpRequest, HttpResponse,
+};
+use futures_util::{Stream, StreamExt as _};
+use rand::Rng;
+
+use crate::{
+    config::{CircuitBreakerConfig, RetryConfig},
+    upstream::{Body, BodyStream},
+};
+
+/// 上游返回这些状态码时可以重试，并计入熔断失败。
+const RETRYABLE_STATUS: &[StatusCode] = &[
+    StatusCode::BAD_GATEWAY,
+    StatusCode::SERVICE_UNAVAILABLE,
+    StatusCode::GATEWAY_TIMEOUT,
+];
+
+pub fn retryable_status(status: StatusCode) -> bool {
+    RETRYABLE_STATUS.contains(&status)
+}
+
+/// 幂等方法才允许重试
+/// https://www.rfc-editor.org/rfc/rfc9110#section-9.2.2
+pub fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE | Method::PUT | Method::DELETE
+    )
+}
+
+/// 请求体可以读入内存重放：幂等、非 chunked，并且 `Content-Length` 不超过 `max_replay_bytes`。
+/// 没有 `Content-Length` 时（例如 HTTP/2）只有通常不带请求体的方法才重放，其他请求流式转发。
+pub fn replayable(req: &HttpRequest, retry: &RetryConfig) -> bool {
+    let headers = req.headers();
+    let length = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok()?.parse::<usize>().ok());
+    let fits = match length {
+        Some(length) => length <= retry.max_replay_bytes,
+        None => matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE),
+    };
+
+    retry.attempts > 0
+        && is_idempotent(req.method())
+        && !headers.contains_key(header::TRANSFER_ENCODING)
+        && fits
+}
+
+/// 读取整个请求体，超过 `limit` 字节返回 413
+pub async fn read_body(mut payload: web::Payload, limit: u64) -> Result<Bytes, Error> {
+    let mut body = BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk?;
+        if (body.len() + chunk.len()) as u64 > limit {
+            return Err(too_large(limit));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body.freeze())
+}
+
+/// `Content-Length` 超过路由的请求体上限时直接返回 413
+pub fn check_content_length(req: &HttpRequest, limit: Option<u64>) -> Result<(), Error> {
+    let length = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok()?.parse::<u64>().ok());
+
+    match (length, limit) {
+        (Some(length), Some(limit)) if length > limit => Err(too_large(limit)),
+        _ => Ok(()),
+    }
+}
+
+fn too_large(limit: u64) -> Error {
+    error::ErrorPayloadTooLarge(format!("request body exceeds {limit} bytes"))
+}
+
+/// 流式请求体出错的原因。上游请求因此失败时返回给客户端对应的错误，
+/// 不算上游故障，不重试也不计入熔断。
+#[derive(Clone, Default)]
+pub struct BodyFault(Rc<Cell<Option<Fault>>>);
+
+#[derive(Clone, Copy)]
+enum Fault {
+    TooLarge(u64),
+    /// 客户端断开或发送了错误的请求体
+    Client,
+}
+
+impl BodyFault {
+    /// 统计请求体字节数，超过 `limit` 时以错误结束，上游请求随之中止。
+    pub fn watch(&self, body: BodyStream, limit: Option<u64>) -> BodyStream {
+        let fault = self.clone();
+        let mut received = 0u64;
+
+        body.map(move |chunk| match chunk {
+            Ok(chunk) => {
+                received += chunk.len() as u64;
+                match limit {
+                    Some(limit) if received > limit => {
+                        fault.0.set(Some(Fault::TooLarge(limit)));
+                        Err(PayloadError::Overflow)
+                    }
+                    _ => Ok(chunk),
+                }
+            }
+            Err(e) => {
+                fault.0.set(Some(Fault::Client));
+                Err(e)
+            }
+        })
+        .boxed_local()
+    }
+
+    pub fn error(&self) -> Option<Error> {
+        self.0.get().map(|fault| match fault {
+            Fault::TooLarge(limit) => too_large(limit),
+            Fault::Client => error::ErrorBadRequest("request body aborted by client"),
+        })
+    }
+}
+
+/// 转发给上游的请求体
+pub enum RequestBody {
+    /// 已读入内存，每次重试都重新发送。
+    Replay(Bytes),
+    /// 流式转发，只能发送一次。
+    Stream(Option<BodyStream>),
+}
+
+impl RequestBody {
+    pub fn replayable(&self) -> bool {
+        matches!(self, RequestBody::Replay(_))
+    }
+
+    pub fn take(&mut self) -> Body {
+        match self {
+            RequestBody::Replay(bytes) => Body::Bytes(bytes.clone()),
+            RequestBody::Stream(payload) => match payload.take() {
+                Some(payload) => Body::Stream(payload),
+                None => Body::Bytes(Bytes::new()),
+            },
+        }
+    }
+}
+
+/// 第 n 次重试的等待时间，full jitter 指数退避。
+pub fn backoff(retry: &RetryConfig, attempt: u32) -> Duration {
+    let ceiling = retry
+        .backoff_ms
+        .saturating_mul(1 << attempt.min(16))
+        .min(retry.max_backoff_ms);
+    Duration::from_millis(rand::thread_rng().gen_range(0..=ceiling))
+}
+
+/// 上游错误：超时 504，其他 502。
+pub fn upstream_error(e: reqwest::Error) -> Error {
+    if e.is_timeout() {
+        error::ErrorGatewayTimeout(e)
+    } else {
+        error::ErrorBadGateway(e)
+    }
+}
+
+/// `awc` 上游错误：超时 504，其他 502。
+pub fn awc_error(e: awc::error::SendRequestError) -> Error {
+    use awc::error::{ConnectError, SendRequestError};
+
+    match e {
+        SendRequestError::Timeout | SendRequestError::Connect(ConnectError::Timeout) => {
+            error::ErrorGatewayTimeout(e)
+        }
+        e => error::ErrorBadGateway(e),
+    }
+}
+
+pub fn read_timeout_error() -> Error {
+    error::ErrorGatewayTimeout("upstream read timed out")
+}
+
+/// 熔断时直接返回 503
+pub fn circuit_open(retry_after: Duration) -> HttpResponse {
+    HttpResponse::ServiceUnavailable()
+        .insert_header((header::RETRY_AFTER, retry_after.as_secs().max(1).to_string()))
+        .body("upstream circuit open")
+}
+
+/// 响应体两次读取之间超过 `idle`，或者超过整个请求的 `deadline` 时中断。
+pub fn read_timeout<S>(
+    stream: S,
+    idle: Duration,
+    deadline: Instant,
+) -> impl Stream<Item = Result<Bytes, Error>>
+where
+    S: Stream<Item = Result<Bytes, Error>> + Unpin,
+{
+    futures_util::stream::unfold(Some(stream), move |stream| async move {
+        let mut stream = stream?;
+        let wait = idle.min(deadline.saturating_duration_since(Instant::now()));
+        match actix_web::rt::time::timeout(wait, stream.next()).await {
+            Ok(Some(Ok(chunk))) => Some((Ok(chunk), Some(stream))),
+            Ok(Some(Err(e))) => Some((Err(e), None)),
+            Ok(None) => None,
+            Err(_) => Some((Err(read_timeout_error()), None)),
+        }
+    })
+}
+
+enum State {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    /// 放行了一个探测请求
+    HalfOpen { since: Instant },
+}
+
+/// 熔断器：连续失败达到阈值后打开，打开期间直接拒绝；
+/// 到期后放行一个探测请求，成功关闭，失败重新打开。
+pub struct CircuitBreaker {
+    upstream: String,
+    config: CircuitBreakerConfig,
+    state: Mutex<State>,
+}
+
+impl CircuitBreaker {
+    pub fn new(upstream: String, config: CircuitBreakerConfig) -> Self {
+        Self {
+            upstream,
+            config,
+            state: Mutex::new(State::Closed { failures: 0 }),
+        }
+    }
+
+    fn open_duration(&self) -> Duration {
+        Duration::from_millis(self.config.open_ms)
+    }
+
+    /// 是否放行请求，拒绝时返回建议的等待时间。
+    pub fn acquire(&self) -> Result<(), Duration> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        match *state {
+            State::Closed { .. } => Ok(()),
+            State::Open { until } if now < until => Err(until - now),
+            // 探测请求没有结果（例如客户端断开）时，超时后再放行一个。
+            State::HalfOpen { since } if now.duration_since(since) < self.open_duration() => {
+                Err(self.open_duration() - now.duration_since(since))
+            }
+            _ => {
+                log::info!("circuit half-open for {}", self.upstream);
+                *state = State::HalfOpen { since: now };
+                Ok(())
+            }
+        }
+    }
+
+    pub fn success(&self) {
+        let mut state = self.state.lock().unwrap();
+        if !matches!(*state, State::Closed { .. }) {
+            log::info!("circuit closed for {}", self.upstream);
+        }
+        *state = State::Closed { failures: 0 };
+    }
+
+    pub fn failure(&self) {
+        if self.config.failure_threshold == 0 {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let open = match *state {
+            State::Closed { failures } if failures + 1 < self.config.failure_threshold => {
+                *state = State::Closed {
+                    failures: failures + 1,
+                };
+                false
+            }
+            State::Open { .. } => false,
+            _ => true,
+        };
+
+        if open {
+            log::warn!("circuit open for {}", self.upstream);
+            *state = State::Open {
+                until: Instant::now() + self.open_duration(),
+            };
+        }
+    }
+}
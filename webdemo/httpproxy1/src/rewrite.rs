@@ -0,0 +1,347 @@
+use std::{collections::BTreeMap, io};
+
+use actix_web::{
+    http::header::{self, HeaderMap, HeaderName, HeaderValue},
+    web::{Bytes, BytesMut},
+    Error, HttpRequest,
+};
+use futures_util::{stream::LocalBoxStream, StreamExt as _};
+use serde::Deserialize;
+use url::Url;
+
+use crate::upstream::UpstreamResponse;
+
+/// 默认改写响应体的内容类型
+const DEFAULT_BODY_TYPES: &[&str] = &["text/html", "application/json"];
+
+/// 改写规则配置
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RewriteConfig {
+    /// 转发给上游的请求头
+    pub request_headers: HeaderRules,
+    /// 返回给客户端的响应头
+    pub response_headers: HeaderRules,
+    /// 指向上游的 `Location` / `Content-Location` 改写为代理地址
+    pub redirects: bool,
+    /// 去掉 `Set-Cookie` 指向上游的 `Domain`，`Path` 加上路由前缀
+    pub cookies: bool,
+    /// 流式查找替换响应体
+    pub body: Vec<BodyRule>,
+    /// 改写响应体的内容类型，默认 `text/html` 和 `application/json`
+    pub body_content_types: Vec<String>,
+}
+
+/// 头部规则，按 remove、set、add 顺序执行。
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct HeaderRules {
+    /// 覆盖同名头部
+    pub set: BTreeMap<String, String>,
+    /// 追加头部
+    pub add: BTreeMap<String, String>,
+    pub remove: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BodyRule {
+    pub find: String,
+    pub replace: String,
+}
+
+/// 编译后的头部规则
+#[derive(Default)]
+struct Headers {
+    set: Vec<(HeaderName, HeaderValue)>,
+    add: Vec<(HeaderName, HeaderValue)>,
+    remove: Vec<HeaderName>,
+}
+
+impl Headers {
+    fn new(rules: &HeaderRules) -> io::Result<Self> {
+        let pairs = |map: &BTreeMap<String, String>| {
+            map.iter()
+                .map(|(n, v)| Ok((header_name(n)?, header_value(v)?)))
+                .collect::<io::Result<Vec<_>>>()
+        };
+
+        Ok(Self {
+            set: pairs(&rules.set)?,
+            add: pairs(&rules.add)?,
+            remove: rules
+                .remove
+                .iter()
+                .map(|n| header_name(n))
+                .collect::<io::Result<_>>()?,
+        })
+    }
+
+    fn apply(&self, headers: &mut HeaderMap) {
+        for name in &self.remove {
+            headers.remove(name);
+        }
+        for (name, value) in &self.set {
+            headers.insert(name.clone(), value.clone());
+        }
+        for (name, value) in &self.add {
+            headers.append(name.clone(), value.clone());
+        }
+    }
+}
+
+fn invalid(e: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid rewrite rule: {e}"))
+}
+
+fn header_name(name: &str) -> io::Result<HeaderName> {
+    HeaderName::from_bytes(name.as_bytes()).map_err(invalid)
+}
+
+fn header_value(value: &str) -> io::Result<HeaderValue> {
+    HeaderValue::from_str(value).map_err(invalid)
+}
+
+/// 路由的改写规则
+#[derive(Default)]
+pub struct Rewrite {
+    request_headers: Headers,
+    response_headers: Headers,
+    redirects: bool,
+    cookies: bool,
+    body: Vec<(Vec<u8>, Vec<u8>)>,
+    body_content_types: Vec<String>,
+}
+
+/// 改写响应需要的路由信息
+pub struct Target<'a> {
+    /// 上游地址
+    pub upstream: &'a Url,
+    /// 转发时去掉的路由前缀，响应里需要加回来。
+    pub prefix: &'a str,
+}
+
+impl Rewrite {
+    pub fn new(config: &RewriteConfig) -> io::Result<Self> {
+        if config.body.iter().any(|r| r.find.is_empty()) {
+            return Err(invalid("body rule with empty `find`"));
+        }
+
+        let body_content_types = if config.body_content_types.is_empty() {
+            DEFAULT_BODY_TYPES.iter().map(|t| t.to_string()).collect()
+        } else {
+            config
+                .body_content_types
+                .iter()
+                .map(|t| t.to_ascii_lowercase())
+                .collect()
+        };
+
+        Ok(Self {
+            request_headers: Headers::new(&config.request_headers)?,
+            response_headers: Headers::new(&config.response_headers)?,
+            redirects: config.redirects,
+            cookies: config.cookies,
+            body: config
+                .body
+                .iter()
+                .map(|r| (r.find.as_bytes().to_vec(), r.replace.as_bytes().to_vec()))
+                .collect(),
+            body_content_types,
+        })
+    }
+
+    /// 有响应体规则时要求上游不压缩，否则浏览器发来的请求拿回的几乎都是压缩过的响应体，
+    /// 规则不会生效。返回给客户端时由路由的压缩设置重新编码。
+    pub fn request(&self, headers: &mut HeaderMap) {
+        if !self.body.is_empty() {
+            headers.insert(header::ACCEPT_ENCODING, HeaderValue::from_static("identity"));
+        }
+        self.request_headers.apply(headers);
+    }
+
+    /// 指向上游的 `Location` / `Content-Location` 改写为客户端访问的代理地址。
+    /// 结果取决于请求的 `Host`，在缓存之后执行，缓存命中的响应同样要改写。
+    pub fn redirects(&self, req: &HttpRequest, target: &Target<'_>, headers: &mut HeaderMap) {
+        if !self.redirects {
+            return;
+        }
+
+        let conn = req.connection_info();
+        let proxy_origin = format!("{}://{}", conn.scheme(), conn.host());
+        for name in [header::LOCATION, header::CONTENT_LOCATION] {
+            let location = headers
+                .get(&name)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| rewrite_location(v, target, &proxy_origin))
+                .and_then(|v| HeaderValue::from_str(&v).ok());
+            if let Some(location) = location {
+                headers.insert(name, location);
+            }
+        }
+    }
+
+    /// 改写上游响应的 `Set-Cookie`、头部和响应体，结果与客户端无关，可以缓存。
+    pub fn response(&self, target: &Target<'_>, res: &mut UpstreamResponse) {
+        if self.cookies {
+            let cookies: Vec<HeaderValue> = res
+                .headers
+                .get_all(header::SET_COOKIE)
+                .map(|v| match v.to_str() {
+                    Ok(cookie) => HeaderValue::from_str(&rewrite_cookie(cookie, target))
+                        .unwrap_or_else(|_| v.clone()),
+                    Err(_) => v.clone(),
+                })
+                .collect();
+            res.headers.remove(header::SET_COOKIE);
+            for cookie in cookies {
+                res.headers.append(header::SET_COOKIE, cookie);
+            }
+        }
+
+        self.response_headers.apply(&mut res.headers);
+
+        if self.rewrites_body(&res.headers) {
+            // 长度会变化，改为流式输出。
+            res.headers.remove(header::CONTENT_LENGTH);
+            let body = std::mem::replace(&mut res.body, futures_util::stream::empty().boxed_local());
+            res.body = replace_stream(body, self.body.clone());
+        }
+    }
+
+    /// 只改写未压缩、内容类型匹配的响应体
+    fn rewrites_body(&self, headers: &HeaderMap) -> bool {
+        if self.body.is_empty() || headers.contains_key(header::CONTENT_ENCODING) {
+            return false;
+        }
+
+        let content_type = headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(';').next())
+            .map(|v| v.trim().to_ascii_lowercase());
+
+        content_type.is_some_and(|ct| self.body_content_types.contains(&ct))
+    }
+}
+
+/// 指向上游的绝对地址改写为代理地址，相对地址加上路由前缀。
+fn rewrite_location(location: &str, target: &Target<'_>, proxy_origin: &str) -> Option<String> {
+    if let Ok(url) = Url::parse(location) {
+        if url.origin() != target.upstream.origin() {
+            return None;
+        }
+        let rest = &url[url::Position::BeforePath..];
+        return Some(format!("{proxy_origin}{}{rest}", target.prefix));
+    }
+
+    if location.starts_with('/') && !location.starts_with("//") && !target.prefix.is_empty() {
+        return Some(format!("{}{location}", target.prefix));
+    }
+    None
+}
+
+/// 去掉指向上游的 `Domain`，让 cookie 属于代理主机；`Path` 加上路由前缀。
+fn rewrite_cookie(cookie: &str, target: &Target<'_>) -> String {
+    let upstream_host = target.upstream.host_str().unwrap_or_default();
+
+    let mut parts = Vec::new();
+    for (i, part) in cookie.split(';').enumerate() {
+        let attr = part.trim();
+        if i > 0 {
+            if let Some((name, value)) = attr.split_once('=') {
+                let name = name.trim();
+                let value = value.trim();
+                if name.eq_ignore_ascii_case("domain") {
+                    let domain = value.trim_start_matches('.');
+                    if upstream_host == domain || upstream_host.ends_with(&format!(".{domain}")) {
+                        continue;
+                    }
+                } else if name.eq_ignore_ascii_case("path") && !target.prefix.is_empty() {
+                    let path = value.trim_end_matches('/');
+                    parts.push(format!("Path={}{path}", target.prefix));
+                    continue;
+                }
+            }
+        }
+        parts.push(attr.to_owned());
+    }
+    parts.join("; ")
+}
+
+/// 流式查找替换，跨分块的匹配保留尾部等下一块，不缓冲整个响应体。
+fn replace_stream(
+    body: LocalBoxStream<'static, Result<Bytes, Error>>,
+    rules: Vec<(Vec<u8>, Vec<u8>)>,
+) -> LocalBoxStream<'static, Result<Bytes, Error>> {
+    let replacer = Replacer {
+        rules,
+        pending: BytesMut::new(),
+    };
+
+    futures_util::stream::unfold(Some((body, replacer)), |state| async move {
+        let (mut body, mut replacer) = state?;
+        loop {
+            match body.next().await {
+                Some(Ok(chunk)) => {
+                    let out = replacer.feed(&chunk, false);
+                    if !out.is_empty() {
+                        return Some((Ok(out), Some((body, replacer))));
+                    }
+                }
+                Some(Err(e)) => return Some((Err(e), None)),
+                None => {
+                    let out = replacer.feed(&[], true);
+                    return (!out.is_empty()).then(|| (Ok(out), None));
+                }
+            }
+        }
+    })
+    .boxed_local()
+}
+
+struct Replacer {
+    rules: Vec<(Vec<u8>, Vec<u8>)>,
+    /// 可能是匹配开头的尾部
+    pending: BytesMut,
+}
+
+impl Replacer {
+    fn feed(&mut self, chunk: &[u8], last: bool) -> Bytes {
+        self.pending.extend_from_slice(chunk);
+        let buf = &self.pending[..];
+
+        let mut out = BytesMut::with_capacity(buf.len());
+        let mut i = 0;
+        'scan: while i < buf.len() {
+            let rest = &buf[i..];
+
+            // 跳到下一个可能匹配的位置
+            let skip = rest
+                .iter()
+                .position(|b| self.rules.iter().any(|(find, _)| find[0] == *b))
+                .unwrap_or(rest.len());
+            if skip > 0 {
+                out.extend_from_slice(&rest[..skip]);
+                i += skip;
+                continue;
+            }
+
+            for (find, replace) in &self.rules {
+                if rest.starts_with(find) {
+                    out.extend_from_slice(replace);
+                    i += find.len();
+                    continue 'scan;
+                }
+            }
+            // 剩余部分可能是某个匹配的开头，等待更多数据。
+            if !last && self.rules.iter().any(|(find, _)| find.len() > rest.len() && find.starts_with(rest)) {
+                break;
+            }
+            out.extend_from_slice(&buf[i..i + 1]);
+            i += 1;
+        }
+
+        let _ = self.pending.split_to(i);
+        out.freeze()
+    }
+}
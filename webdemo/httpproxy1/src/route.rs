@@ -0,0 +1,211 @@
+use std::{collections::HashMap, io, sync::Arc};
+
+use actix_web::{web, HttpRequest};
+use url::Url;
+
+use crate::{
+    compress::Compression,
+    config::{CircuitBreakerConfig, Config, RetryConfig, RouteConfig, TimeoutConfig},
+    ip_filter::IpFilter,
+    mock::{MockClient, Recordings},
+    rate_limit::{LimiterStore, RateLimiter},
+    resilience::CircuitBreaker,
+    rewrite::{Rewrite, Target},
+    upstream::{AwcClient, BackendKind, Http2, ReqwestClient, UpstreamClient},
+};
+
+/// 默认路由前缀，分别使用两种上游客户端。
+const REQWEST_PREFIX: &str = "/using-reqwest";
+const AWC_PREFIX: &str = "/using-awc";
+
+/// 反向代理路由
+pub struct Route {
+    pub prefix: String,
+    pub strip_prefix: bool,
+    pub upstream: Url,
+    pub timeouts: TimeoutConfig,
+    pub retry: RetryConfig,
+    pub http2: Http2,
+    /// 请求体上限，超过返回 413。
+    pub max_body_bytes: Option<u64>,
+    pub ip_filter: IpFilter,
+    pub rate_limiter: RateLimiter,
+    pub compression: Compression,
+    pub rewrite: Rewrite,
+    /// 同一上游的路由共用熔断器
+    pub breaker: Arc<CircuitBreaker>,
+    /// 录制 / 回放上游响应
+    pub recordings: Option<Arc<Recordings>>,
+    backend: Backend,
+}
+
+enum Backend {
+    Awc,
+    /// `reqwest` 客户端可以跨 worker 共享连接池
+    Reqwest(ReqwestClient),
+}
+
+impl Route {
+    /// 按配置创建所有路由，前缀长的排在前面。限流状态保存在 `limiter_store`。
+    pub fn from_config(config: &Config, limiter_store: Arc<dyn LimiterStore>) -> io::Result<Vec<Route>> {
+        let defaults = [
+            RouteConfig::new(REQWEST_PREFIX, true),
+            RouteConfig {
+                backend: BackendKind::Awc,
+                ..RouteConfig::new(AWC_PREFIX, true)
+            },
+            RouteConfig::new("/", false),
+        ];
+        let route_configs = if config.routes.is_empty() {
+            &defaults[..]
+        } else {
+            &config.routes[..]
+        };
+
+        let mut breakers: HashMap<String, Arc<CircuitBreaker>> = HashMap::new();
+        let mut routes = Vec::new();
+
+        for rc in route_configs {
+            let upstream = rc.upstream.clone().unwrap_or_else(|| config.upstream.clone());
+            let timeouts = rc.timeouts.unwrap_or(config.timeouts);
+
+            let mock = rc.mock.as_ref().unwrap_or(&config.mock);
+            let recordings = Recordings::new(mock)?.map(Arc::new);
+
+            // 只回放时没有真实上游，不熔断也不重试。
+            let origin = upstream.origin().ascii_serialization();
+            let (breaker, retry) = if mock.offline() {
+                let no_breaker = CircuitBreakerConfig {
+                    failure_threshold: 0,
+                    ..config.circuit_breaker
+                };
+                let no_retry = RetryConfig {
+                    attempts: 0,
+                    ..config.retry
+                };
+                (Arc::new(CircuitBreaker::new(origin, no_breaker)), no_retry)
+            } else {
+                let breaker = breakers
+                    .entry(origin.clone())
+                    .or_insert_with(|| Arc::new(CircuitBreaker::new(origin, config.circuit_breaker)))
+                    .clone();
+                (breaker, rc.retry.unwrap_or(config.retry))
+            };
+
+            let backend = match rc.backend {
+                BackendKind::Awc => {
+                    if rc.http2 == Http2::Always {
+                        log::warn!("awc has no h2c support, route {} uses ALPN only", rc.prefix);
+                    }
+                    Backend::Awc
+                }
+                BackendKind::Reqwest => Backend::Reqwest(
+                    ReqwestClient::new(&timeouts, rc.http2).map_err(io::Error::other)?,
+                ),
+            };
+
+            let prefix = rc.prefix.trim_end_matches('/');
+            routes.push(Route {
+                prefix: if prefix.is_empty() { "/" } else { prefix }.to_owned(),
+                strip_prefix: rc.strip_prefix,
+                upstream,
+                timeouts,
+                retry,
+                http2: rc.http2,
+                max_body_bytes: rc.max_body_bytes.or(config.max_body_bytes),
+                ip_filter: IpFilter::new(rc.ip_filter.as_ref().unwrap_or(&config.ip_filter))?,
+                rate_limiter: RateLimiter::new(
+                    &rc.prefix,
+                    rc.rate_limits.as_ref().unwrap_or(&config.rate_limits),
+                    limiter_store.clone(),
+                )?,
+                compression: Compression::new(rc.compression.as_ref().unwrap_or(&config.compression))?,
+                rewrite: Rewrite::new(rc.rewrite.as_ref().unwrap_or(&config.rewrite))?,
+                breaker,
+                recordings,
+                backend,
+            });
+        }
+
+        routes.sort_by_key(|r| std::cmp::Reverse(r.prefix.len()));
+        Ok(routes)
+    }
+
+    /// 上游客户端，每个 worker 调用一次。
+    pub fn client(&self) -> Box<dyn UpstreamClient> {
+        let client: Box<dyn UpstreamClient> = match &self.backend {
+            Backend::Awc => Box::new(AwcClient::new(&self.timeouts, self.http2)),
+            Backend::Reqwest(client) => Box::new(client.clone()),
+        };
+
+        match &self.recordings {
+            Some(recordings) => Box::new(MockClient::new(client, recordings.clone())),
+            None => client,
+        }
+    }
+
+    pub fn backend_name(&self) -> &'static str {
+        match self.backend {
+            Backend::Awc => "awc",
+            Backend::Reqwest(_) => "reqwest",
+        }
+    }
+
+    /// 改写响应时需要加回的前缀
+    pub fn target(&self) -> Target<'_> {
+        Target {
+            upstream: &self.upstream,
+            prefix: match self.strip_prefix && !self.is_default() {
+                true => &self.prefix,
+                false => "",
+            },
+        }
+    }
+
+    /// 匹配其他所有请求的路由
+    pub fn is_default(&self) -> bool {
+        self.prefix == "/"
+    }
+
+    /// 路径是否属于这个路由，和 `web::scope` 的匹配方式相同
+    pub fn matches(&self, path: &str) -> bool {
+        self.is_default()
+            || path
+                .strip_prefix(self.prefix.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
+
+    /// 请求对应的上游 URL
+    pub fn upstream_url(&self, req: &HttpRequest) -> Url {
+        self.upstream_url_for(req.uri().path(), req.uri().query())
+    }
+
+    /// 客户端路径的缓存键：路由前缀加上游 URL。同一个上游的两个路由改写规则可能不同，
+    /// 缓存的是改写后的响应，不能共用条目。
+    pub fn cache_key(&self, path: &str, query: Option<&str>) -> String {
+        format!("{} {}", self.prefix, self.upstream_url_for(path, query))
+    }
+
+    /// 客户端路径对应的上游 URL
+    pub fn upstream_url_for(&self, path: &str, query: Option<&str>) -> Url {
+        let path = match self.strip_prefix && !self.is_default() {
+            true => path.strip_prefix(self.prefix.as_str()).unwrap_or(path),
+            false => path,
+        };
+
+        let mut new_url = self.upstream.clone();
+        new_url.set_path(path);
+        new_url.set_query(query);
+        new_url
+    }
+}
+
+/// 所有路由，前缀长的排在前面
+pub struct Routes(pub Vec<web::Data<Route>>);
+
+impl Routes {
+    /// 处理这个路径的路由，和请求分发的结果相同
+    pub fn find(&self, path: &str) -> Option<&Route> {
+        self.0.iter().map(|r| r.get_ref()).find(|r| r.matches(path))
+    }
+}
@@ -0,0 +1,224 @@
+use actix_web::{
+    error::{self, PayloadError},
+    http::{header::HeaderMap, Method, StatusCode, Version},
+    web::Bytes,
+    Error,
+};
+use awc::Connector;
+use futures_util::{
+    future::LocalBoxFuture,
+    stream::LocalBoxStream,
+    FutureExt as _, StreamExt as _, TryStreamExt as _,
+};
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use url::Url;
+
+use crate::{config::TimeoutConfig, resilience};
+
+/// 转发给上游的请求，请求体单独传入以便重试时重放。
+pub struct UpstreamRequest {
+    pub method: Method,
+    pub url: Url,
+    pub headers: HeaderMap,
+}
+
+/// 流式请求体，客户端的 payload 可能被包装（例如抓包）。
+pub type BodyStream = LocalBoxStream<'static, Result<Bytes, PayloadError>>;
+
+pub enum Body {
+    Bytes(Bytes),
+    Stream(BodyStream),
+}
+
+/// 上游响应
+pub struct UpstreamResponse {
+    pub status: StatusCode,
+    pub version: Version,
+    pub headers: HeaderMap,
+    pub body: LocalBoxStream<'static, Result<Bytes, Error>>,
+}
+
+/// 上游客户端。错误已经转换为 502 / 504。
+pub trait UpstreamClient {
+    fn send(&self, req: &UpstreamRequest, body: Body)
+        -> LocalBoxFuture<'static, Result<UpstreamResponse, Error>>;
+}
+
+/// 上游客户端实现
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    /// `awc`，支持 TLS ALPN 协商 HTTP/2，不支持明文 h2c。
+    Awc,
+    /// `reqwest`，支持 ALPN 协商和明文 h2c（`http2 = "always"`）。
+    #[default]
+    Reqwest,
+}
+
+/// 上游 HTTP/2 策略
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Http2 {
+    /// https 上游通过 ALPN 协商
+    #[default]
+    Auto,
+    /// 总是使用 HTTP/2，`reqwest` 对 http 上游使用 prior knowledge。
+    Always,
+    /// 只用 HTTP/1.1
+    Never,
+}
+
+/// 请求体通道容量（块数），上游读得慢时停止读取客户端。
+const BODY_CHANNEL_CAPACITY: usize = 8;
+
+/// `reqwest` 要求请求体 `Send`，通过有界通道转发，上游读得慢时反压客户端。
+/// 上游放弃请求（接收端被丢弃）或客户端出错时停止读取。
+pub fn reqwest_body(mut body: BodyStream) -> reqwest::Body {
+    let (tx, rx) = mpsc::channel(BODY_CHANNEL_CAPACITY);
+
+    actix_web::rt::spawn(async move {
+        while let Some(chunk) = body.next().await {
+            let failed = chunk.is_err();
+            if tx.send(chunk).await.is_err() || failed {
+                break;
+            }
+        }
+    });
+
+    reqwest::Body::wrap_stream(ReceiverStream::new(rx))
+}
+
+/// `reqwest` 后端，客户端可以跨 worker 共享。
+#[derive(Clone)]
+pub struct ReqwestClient {
+    client: reqwest::Client,
+}
+
+impl ReqwestClient {
+    pub fn new(timeouts: &TimeoutConfig, http2: Http2) -> reqwest::Result<Self> {
+        // 和 `awc` 一样不解压，响应编码由代理统一处理。
+        let builder = reqwest::Client::builder()
+            .connect_timeout(timeouts.connect())
+            .redirect(reqwest::redirect::Policy::none())
+            .no_gzip()
+            .no_brotli()
+            .no_deflate();
+
+        let builder = match http2 {
+            Http2::Auto => builder,
+            Http2::Always => builder.http2_prior_knowledge(),
+            Http2::Never => builder.http1_only(),
+        };
+
+        Ok(Self {
+            client: builder.build()?,
+        })
+    }
+}
+
+impl UpstreamClient for ReqwestClient {
+    fn send(
+        &self,
+        req: &UpstreamRequest,
+        body: Body,
+    ) -> LocalBoxFuture<'static, Result<UpstreamResponse, Error>> {
+        let body = match body {
+            Body::Bytes(bytes) => reqwest::Body::from(bytes),
+            Body::Stream(stream) => reqwest_body(stream),
+        };
+
+        let mut forwarded_req = self
+            .client
+            .request(req.method.clone(), req.url.clone())
+            .body(body);
+        for (name, value) in req.headers.iter() {
+            forwarded_req = forwarded_req.header(name.clone(), value.clone());
+        }
+
+        async move {
+            let res = forwarded_req
+                .send()
+                .await
+                .map_err(resilience::upstream_error)?;
+
+            let mut headers = HeaderMap::new();
+            for (name, value) in res.headers() {
+                headers.append(name.clone(), value.clone());
+            }
+
+            Ok(UpstreamResponse {
+                status: res.status(),
+                version: res.version(),
+                headers,
+                body: res
+                    .bytes_stream()
+                    .map_err(resilience::upstream_error)
+                    .boxed_local(),
+            })
+        }
+        .boxed_local()
+    }
+}
+
+/// `awc` 后端，客户端不能跨线程，每个 worker 创建一个。
+pub struct AwcClient {
+    client: awc::Client,
+}
+
+impl AwcClient {
+    pub fn new(timeouts: &TimeoutConfig, http2: Http2) -> Self {
+        let version = match http2 {
+            Http2::Never => Version::HTTP_11,
+            Http2::Auto | Http2::Always => Version::HTTP_2,
+        };
+
+        // 超时由代理流程统一处理，这里只设置连接超时。
+        let client = awc::Client::builder()
+            .connector(
+                Connector::new()
+                    .timeout(timeouts.connect())
+                    .max_http_version(version),
+            )
+            .disable_timeout()
+            .disable_redirects()
+            .no_default_headers()
+            .finish();
+
+        Self { client }
+    }
+}
+
+impl UpstreamClient for AwcClient {
+    fn send(
+        &self,
+        req: &UpstreamRequest,
+        body: Body,
+    ) -> LocalBoxFuture<'static, Result<UpstreamResponse, Error>> {
+        let mut forwarded_req = self
+            .client
+            .request(req.method.clone(), req.url.as_str())
+            .no_decompress();
+        for (name, value) in req.headers.iter() {
+            forwarded_req = forwarded_req.append_header((name.clone(), value.clone()));
+        }
+
+        let send = match body {
+            Body::Bytes(bytes) => forwarded_req.send_body(bytes),
+            Body::Stream(payload) => forwarded_req.send_stream(payload),
+        };
+
+        async move {
+            let res = send.await.map_err(resilience::awc_error)?;
+
+            Ok(UpstreamResponse {
+                status: res.status(),
+                version: res.version(),
+                headers: res.headers().clone(),
+                body: res.map_err(error::ErrorBadGateway).boxed_local(),
+            })
+        }
+        .boxed_local()
+    }
+}
@@ -0,0 +1,619 @@
+//! 反向代理集成测试，每个用例分别使用 `reqwest` 和 `awc` 后端跑一遍。
+
+use std::{net::SocketAddr, time::Duration};
+
+use actix_web::{
+    dev::{Service, ServiceResponse},
+    http::{header, StatusCode},
+    test, web, App, HttpRequest, HttpResponse, HttpServer,
+};
+use httpproxy1::{config::Config, ReverseProxy};
+use serde_json::{json, Value};
+
+const BACKENDS: &[&str] = &["reqwest", "awc"];
+
+/// 上游原样返回请求内容
+async fn echo(req: HttpRequest, body: web::Bytes) -> HttpResponse {
+    let headers: serde_json::Map<String, Value> = req
+        .headers()
+        .iter()
+        .map(|(n, v)| (n.to_string(), json!(v.to_str().unwrap_or_default())))
+        .collect();
+
+    HttpResponse::Ok()
+        .insert_header(("x-upstream", "yes"))
+        .insert_header(("keep-alive", "timeout=5"))
+        .append_header(("set-cookie", "a=1"))
+        .append_header(("set-cookie", "b=2"))
+        .json(json!({
+            "method": req.method().as_str(),
+            "path": req.path(),
+            "query": req.query_string(),
+            "headers": headers,
+            "body_len": body.len(),
+            "body": String::from_utf8_lossy(&body[..body.len().min(64)]),
+        }))
+}
+
+async fn slow() -> HttpResponse {
+    actix_web::rt::time::sleep(Duration::from_secs(2)).await;
+    HttpResponse::Ok().body("slow")
+}
+
+async fn status(code: web::Path<u16>) -> HttpResponse {
+    HttpResponse::build(StatusCode::from_u16(*code).unwrap()).finish()
+}
+
+/// 重定向到上游自己的绝对地址，可以缓存。`connection_info` 会用代理加的 `X-Forwarded-Host`，这里取 `Host`
+async fn redirect(req: HttpRequest) -> HttpResponse {
+    let host = req.headers().get("host").unwrap().to_str().unwrap();
+    HttpResponse::MovedPermanently()
+        .insert_header(("location", format!("http://{host}/echo/done?x=1")))
+        .insert_header(("cache-control", "max-age=60"))
+        .finish()
+}
+
+async fn cookie() -> HttpResponse {
+    HttpResponse::Ok()
+        .append_header(("set-cookie", "sid=1; Domain=127.0.0.1; Path=/echo/; HttpOnly"))
+        .append_header(("set-cookie", "theme=dark; Domain=example.com; Path=/"))
+        .finish()
+}
+
+/// 分块输出，要替换的文本跨在分块边界上，最后一块以不完整的匹配结尾
+async fn chunked() -> HttpResponse {
+    let chunks = ["hel", "lo wo", "rld, hello", " world; hello w"]
+        .map(|chunk| Ok::<_, actix_web::Error>(web::Bytes::from_static(chunk.as_bytes())));
+    HttpResponse::Ok()
+        .content_type("text/plain")
+        .streaming(futures_util::stream::iter(chunks))
+}
+
+/// 启动上游服务器，返回监听地址
+fn start_upstream() -> SocketAddr {
+    let server = HttpServer::new(|| {
+        App::new()
+            .app_data(web::PayloadConfig::new(8 * 1024 * 1024))
+            .route("/echo/{tail:.*}", web::to(echo))
+            .route("/slow", web::get().to(slow))
+            .route("/status/{code}", web::get().to(status))
+            .route("/redirect", web::get().to(redirect))
+            .route("/cookie", web::get().to(cookie))
+            .route("/chunked", web::get().to(chunked))
+    })
+    .workers(1)
+    .bind(("127.0.0.1", 0))
+    .unwrap();
+
+    let addr = server.addrs()[0];
+    actix_web::rt::spawn(server.run());
+    addr
+}
+
+/// 代理配置：`/api` 去掉前缀转发到上游，`/limited` 限制请求体大小，
+/// `/guarded` 有 IP 访问控制和限流，`/compressed` 压缩响应，`/rewritten` 改写重定向、cookie 和响应体，
+/// `/dead` 转发到不可达地址。
+fn proxy_config(backend: &str, upstream: SocketAddr) -> Config {
+    Config::from_toml(&format!(
+        r#"
+        upstream = "http://{upstream}/"
+
+        [timeouts]
+        read_ms = 500
+
+        [circuit_breaker]
+        failure_threshold = 2
+        open_ms = 60000
+
+        [[routes]]
+        prefix = "/api"
+        strip_prefix = true
+        backend = "{backend}"
+        retry = {{ attempts = 0 }}
+
+        [[routes]]
+        prefix = "/limited"
+        strip_prefix = true
+        backend = "{backend}"
+        max_body_bytes = 1024
+
+        [[routes]]
+        prefix = "/guarded"
+        strip_prefix = true
+        backend = "{backend}"
+        ip_filter = {{ deny = ["10.0.0.0/8"] }}
+        rate_limits = [{{ key = "api_key", algorithm = "fixed_window", limit = 2, window_ms = 60000 }}]
+
+        [[routes]]
+        prefix = "/compressed"
+        strip_prefix = true
+        backend = "{backend}"
+        compression = {{ enabled = true, encodings = ["gzip", "br"], min_bytes = 16 }}
+
+        [[routes]]
+        prefix = "/rewritten"
+        strip_prefix = true
+        backend = "{backend}"
+        retry = {{ attempts = 0 }}
+        rewrite = {{ redirects = true, cookies = true, body = [{{ find = "hello world", replace = "HI" }}], body_content_types = ["text/plain"] }}
+
+        [[routes]]
+        prefix = "/dead"
+        strip_prefix = true
+        backend = "{backend}"
+        upstream = "http://127.0.0.1:1/"
+        retry = {{ attempts = 0 }}
+        "#
+    ))
+    .unwrap()
+}
+
+async fn proxy_app(
+    backend: &str,
+    upstream: SocketAddr,
+) -> impl Service<actix_http::Request, Response = ServiceResponse, Error = actix_web::Error> {
+    let proxy = ReverseProxy::new(&proxy_config(backend, upstream)).unwrap();
+    test::init_service(App::new().configure(|cfg| proxy.configure(cfg))).await
+}
+
+#[actix_web::test]
+async fn rewrites_path_and_query() {
+    let upstream = start_upstream();
+    for backend in BACKENDS {
+        let app = proxy_app(backend, upstream).await;
+        let req = test::TestRequest::get().uri("/api/echo/a/b?x=1&y=2").to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+
+        assert_eq!(body["method"], "GET", "{backend}");
+        assert_eq!(body["path"], "/echo/a/b", "{backend}");
+        assert_eq!(body["query"], "x=1&y=2", "{backend}");
+    }
+}
+
+#[actix_web::test]
+async fn forwards_request_bodies() {
+    let upstream = start_upstream();
+    for backend in BACKENDS {
+        let app = proxy_app(backend, upstream).await;
+
+        // 小请求体读入内存（可重放）
+        let req = test::TestRequest::put()
+            .uri("/api/echo/small")
+            .set_payload("hello")
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["body"], "hello", "{backend}");
+
+        // 大请求体流式转发
+        let big = vec![b'x'; 512 * 1024];
+        let req = test::TestRequest::post()
+            .uri("/api/echo/big")
+            .set_payload(big.clone())
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["method"], "POST", "{backend}");
+        assert_eq!(body["body_len"], big.len(), "{backend}");
+
+        // 没有 Content-Length（例如 HTTP/2）的幂等请求超过重放上限时流式转发，不返回 413
+        let mut req = test::TestRequest::put()
+            .uri("/compressed/echo/unsized")
+            .set_payload(big.clone())
+            .to_request();
+        req.headers_mut().remove(header::CONTENT_LENGTH);
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["method"], "PUT", "{backend}");
+        assert_eq!(body["body_len"], big.len(), "{backend}");
+    }
+}
+
+#[actix_web::test]
+async fn rejects_oversized_request_bodies() {
+    let upstream = start_upstream();
+    for backend in BACKENDS {
+        let app = proxy_app(backend, upstream).await;
+
+        let req = test::TestRequest::post()
+            .uri("/limited/echo/big")
+            .set_payload(vec![b'x'; 2048])
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE, "{backend}");
+
+        // 没有 Content-Length 时读入内存重放的请求体同样受路由上限约束
+        let mut req = test::TestRequest::get()
+            .uri("/limited/echo/unsized")
+            .set_payload(vec![b'x'; 2048])
+            .to_request();
+        req.headers_mut().remove(header::CONTENT_LENGTH);
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE, "{backend}");
+        assert_eq!(test::read_body(res).await, "request body exceeds 1024 bytes", "{backend}");
+
+        let req = test::TestRequest::post()
+            .uri("/limited/echo/small")
+            .set_payload(vec![b'x'; 512])
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["body_len"], 512, "{backend}");
+    }
+}
+
+#[actix_web::test]
+async fn filters_and_rate_limits_clients() {
+    let upstream = start_upstream();
+    for backend in BACKENDS {
+        let app = proxy_app(backend, upstream).await;
+        let request = |peer: &str, key: Option<&str>| {
+            let mut req = test::TestRequest::get()
+                .uri("/guarded/echo/")
+                .peer_addr(peer.parse().unwrap());
+            if let Some(key) = key {
+                req = req.insert_header(("x-api-key", key));
+            }
+            req.to_request()
+        };
+
+        let res = test::call_service(&app, request("10.1.2.3:5000", None)).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN, "{backend}");
+
+        for _ in 0..2 {
+            let res = test::call_service(&app, request("127.0.0.1:5000", Some("k1"))).await;
+            assert_eq!(res.status(), StatusCode::OK, "{backend}");
+        }
+        let res = test::call_service(&app, request("127.0.0.1:5000", Some("k1"))).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS, "{backend}");
+        assert!(res.headers().contains_key("retry-after"), "{backend}");
+
+        // 其他 API key 和没有 key 的请求不受影响
+        let res = test::call_service(&app, request("127.0.0.1:5000", Some("k2"))).await;
+        assert_eq!(res.status(), StatusCode::OK, "{backend}");
+        let res = test::call_service(&app, request("127.0.0.1:5000", None)).await;
+        assert_eq!(res.status(), StatusCode::OK, "{backend}");
+    }
+}
+
+#[actix_web::test]
+async fn negotiates_response_compression() {
+    let upstream = start_upstream();
+    for backend in BACKENDS {
+        let app = proxy_app(backend, upstream).await;
+
+        let req = test::TestRequest::get()
+            .uri("/compressed/echo/x")
+            .insert_header(("accept-encoding", "br;q=0.5, gzip"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.headers().get("content-encoding").unwrap(), "gzip", "{backend}");
+        assert_eq!(res.headers().get("vary").unwrap(), "accept-encoding", "{backend}");
+        let body = test::read_body(res).await;
+        assert_eq!(&body[..2], b"\x1f\x8b", "{backend}");
+
+        // 没有 Accept-Encoding 时不压缩
+        let req = test::TestRequest::get().uri("/compressed/echo/x").to_request();
+        let res = test::call_service(&app, req).await;
+        assert!(!res.headers().contains_key("content-encoding"), "{backend}");
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["path"], "/echo/x", "{backend}");
+    }
+}
+
+#[actix_web::test]
+async fn forwards_headers_and_x_forwarded_for() {
+    let upstream = start_upstream();
+    for backend in BACKENDS {
+        let app = proxy_app(backend, upstream).await;
+        let req = test::TestRequest::get()
+            .uri("/api/echo/headers")
+            .peer_addr("10.0.0.2:5000".parse().unwrap())
+            .insert_header(("x-test", "1"))
+            .insert_header(("x-forwarded-for", "10.0.0.1"))
+            .insert_header(("proxy-connection", "keep-alive"))
+            .insert_header(("connection", "x-drop-me"))
+            .insert_header(("x-drop-me", "1"))
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        let headers = &body["headers"];
+
+        assert_eq!(headers["x-test"], "1", "{backend}");
+        assert_eq!(headers["x-forwarded-for"], "10.0.0.1, 10.0.0.2", "{backend}");
+        assert_eq!(headers["host"], upstream.to_string(), "{backend}");
+        assert!(headers.get("proxy-connection").is_none(), "{backend}");
+        assert!(headers.get("x-drop-me").is_none(), "{backend}");
+    }
+}
+
+#[actix_web::test]
+async fn passes_response_headers_and_status() {
+    let upstream = start_upstream();
+    for backend in BACKENDS {
+        let app = proxy_app(backend, upstream).await;
+
+        let req = test::TestRequest::get().uri("/api/echo/").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK, "{backend}");
+        assert_eq!(res.headers().get("x-upstream").unwrap(), "yes", "{backend}");
+        assert_eq!(res.headers().get_all("set-cookie").count(), 2, "{backend}");
+        assert!(res.headers().get("keep-alive").is_none(), "{backend}");
+
+        let req = test::TestRequest::get().uri("/api/status/404").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND, "{backend}");
+    }
+}
+
+#[actix_web::test]
+async fn rewrites_redirects_cookies_and_bodies() {
+    let upstream = start_upstream();
+    for backend in BACKENDS {
+        let app = proxy_app(backend, upstream).await;
+
+        let req = test::TestRequest::get()
+            .uri("/rewritten/redirect")
+            .insert_header(("host", "proxy.test"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::MOVED_PERMANENTLY, "{backend}");
+        assert_eq!(
+            res.headers().get("location").unwrap(),
+            "http://proxy.test/rewritten/echo/done?x=1",
+            "{backend}"
+        );
+
+        // 上游的 Domain 去掉，其他域名保留，Path 加上路由前缀
+        let req = test::TestRequest::get().uri("/rewritten/cookie").to_request();
+        let res = test::call_service(&app, req).await;
+        let cookies: Vec<_> = res.headers().get_all("set-cookie").map(|v| v.to_str().unwrap()).collect();
+        assert_eq!(
+            cookies,
+            ["sid=1; Path=/rewritten/echo; HttpOnly", "theme=dark; Domain=example.com; Path=/rewritten"],
+            "{backend}"
+        );
+
+        // 跨分块的匹配也会替换，结尾不完整的匹配原样输出
+        let req = test::TestRequest::get()
+            .uri("/rewritten/chunked")
+            .insert_header(("accept-encoding", "gzip"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert!(!res.headers().contains_key("content-encoding"), "{backend}");
+        assert_eq!(test::read_body(res).await, "HI, HI; hello w", "{backend}");
+
+        // 有响应体规则时要求上游不压缩
+        let req = test::TestRequest::get()
+            .uri("/rewritten/echo/x")
+            .insert_header(("accept-encoding", "gzip, br"))
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["headers"]["accept-encoding"], "identity", "{backend}");
+    }
+}
+
+#[actix_web::test]
+async fn rewrites_cached_redirects_per_host() {
+    let upstream = start_upstream();
+    for backend in BACKENDS {
+        let config = Config::from_toml(&format!(
+            r#"
+            upstream = "http://{upstream}/"
+            cache = {{ enabled = true }}
+
+            [[routes]]
+            prefix = "/rewritten"
+            strip_prefix = true
+            backend = "{backend}"
+            rewrite = {{ redirects = true }}
+            "#
+        ))
+        .unwrap();
+        let proxy = ReverseProxy::new(&config).unwrap();
+        let app = test::init_service(App::new().configure(|cfg| proxy.configure(cfg))).await;
+
+        // 缓存的是上游原来的 Location，每个客户端拿到的是自己访问的地址
+        for (host, cache) in [("a.test", "MISS"), ("b.test", "HIT")] {
+            let req = test::TestRequest::get()
+                .uri("/rewritten/redirect")
+                .insert_header(("host", host))
+                .to_request();
+            let res = test::call_service(&app, req).await;
+            assert_eq!(res.headers().get("x-cache").unwrap(), cache, "{backend}");
+            assert_eq!(
+                res.headers().get("location").unwrap().to_str().unwrap(),
+                format!("http://{host}/rewritten/echo/done?x=1"),
+                "{backend}"
+            );
+            // 响应体读完才写入缓存
+            test::read_body(res).await;
+        }
+    }
+}
+
+#[actix_web::test]
+async fn slow_upstream_returns_504() {
+    let upstream = start_upstream();
+    for backend in BACKENDS {
+        let app = proxy_app(backend, upstream).await;
+        let req = test::TestRequest::get().uri("/api/slow").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::GATEWAY_TIMEOUT, "{backend}");
+    }
+}
+
+#[actix_web::test]
+async fn unreachable_upstream_opens_circuit() {
+    let upstream = start_upstream();
+    for backend in BACKENDS {
+        let app = proxy_app(backend, upstream).await;
+
+        for _ in 0..2 {
+            let req = test::TestRequest::get().uri("/dead/x").to_request();
+            let res = test::call_service(&app, req).await;
+            assert_eq!(res.status(), StatusCode::BAD_GATEWAY, "{backend}");
+        }
+
+        let req = test::TestRequest::get().uri("/dead/x").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE, "{backend}");
+        assert!(res.headers().contains_key("retry-after"), "{backend}");
+    }
+}
+
+#[actix_web::test]
+async fn records_and_replays_upstream() {
+    let upstream = start_upstream();
+    for backend in BACKENDS {
+        let dir = std::env::temp_dir().join(format!("httpproxy1-recordings-{}-{backend}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let mock_config = |mode: &str, upstream: &str| {
+            Config::from_toml(&format!(
+                r#"
+                upstream = "http://{upstream}/"
+                mock = {{ mode = "{mode}", dir = "{}", key_headers = ["accept"] }}
+
+                [[routes]]
+                prefix = "/api"
+                strip_prefix = true
+                backend = "{backend}"
+                "#,
+                dir.display()
+            ))
+            .unwrap()
+        };
+
+        // 录制
+        let proxy = ReverseProxy::new(&mock_config("record", &upstream.to_string())).unwrap();
+        let app = test::init_service(App::new().configure(|cfg| proxy.configure(cfg))).await;
+        let req = test::TestRequest::get()
+            .uri("/api/echo/rec?x=1")
+            .insert_header(("accept", "application/json"))
+            .to_request();
+        let recorded: Value = test::call_and_read_body_json(&app, req).await;
+
+        // 录制文件在后台写入
+        for _ in 0..50 {
+            if std::fs::read_dir(&dir).map_or(0, |d| d.count()) > 0 {
+                break;
+            }
+            actix_web::rt::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        // 回放不访问上游
+        let proxy = ReverseProxy::new(&mock_config("replay", "127.0.0.1:1")).unwrap();
+        let app = test::init_service(App::new().configure(|cfg| proxy.configure(cfg))).await;
+        let req = test::TestRequest::get()
+            .uri("/api/echo/rec?x=1")
+            .insert_header(("accept", "application/json"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK, "{backend}");
+        assert_eq!(res.headers().get("x-upstream").unwrap(), "yes", "{backend}");
+        let replayed: Value = test::read_body_json(res).await;
+        assert_eq!(replayed, recorded, "{backend}");
+
+        // 请求头不同，未命中
+        let req = test::TestRequest::get()
+            .uri("/api/echo/rec?x=1")
+            .insert_header(("accept", "text/html"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_GATEWAY, "{backend}");
+        assert_eq!(res.headers().get("x-mock").unwrap(), "MISS", "{backend}");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
+
+#[actix_web::test]
+async fn writes_access_log_and_har() {
+    let upstream = start_upstream();
+    for backend in BACKENDS {
+        let dir = std::env::temp_dir().join(format!("httpproxy1-capture-{}-{backend}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let log_path = dir.join("access.log");
+        let har_dir = dir.join("har");
+
+        let config = Config::from_toml(&format!(
+            r#"
+            upstream = "http://{upstream}/"
+            access_log = {{ enabled = true, path = "{}" }}
+            capture = {{ enabled = true, dir = "{}" }}
+
+            [[routes]]
+            prefix = "/compressed"
+            strip_prefix = true
+            backend = "{backend}"
+            compression = {{ enabled = true, encodings = ["gzip"], min_bytes = 16 }}
+            "#,
+            log_path.display(),
+            har_dir.display()
+        ))
+        .unwrap();
+        let proxy = ReverseProxy::new(&config).unwrap();
+        let app = test::init_service(App::new().configure(|cfg| proxy.configure(cfg))).await;
+
+        let req = test::TestRequest::get()
+            .uri("/compressed/echo/har?x=1")
+            .insert_header(("authorization", "Bearer secret"))
+            .insert_header(("cookie", "session=secret"))
+            .insert_header(("accept-encoding", "gzip"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.headers().get("content-encoding").unwrap(), "gzip", "{backend}");
+        let sent = test::read_body(res).await;
+
+        // 访问日志在响应体发送完时写入
+        let log = std::fs::read_to_string(&log_path).unwrap();
+        let line: Value = serde_json::from_str(log.lines().last().unwrap()).unwrap();
+        assert_eq!(line["method"], "GET", "{backend}");
+        assert_eq!(line["path"], "/compressed/echo/har?x=1", "{backend}");
+        assert_eq!(line["route"], "/compressed", "{backend}");
+        assert_eq!(line["backend"], *backend, "{backend}");
+        assert_eq!(line["upstream"], format!("http://{upstream}/echo/har?x=1"), "{backend}");
+        assert_eq!(line["status"], 200, "{backend}");
+        assert_eq!(line["attempts"], 1, "{backend}");
+        assert_eq!(line["bytes"], sent.len(), "{backend}");
+        assert!(line["total_ms"].is_number(), "{backend}");
+
+        // HAR 在后台写入
+        let mut har = None;
+        for _ in 0..50 {
+            if let Some(entry) = std::fs::read_dir(&har_dir).unwrap().flatten().next() {
+                har = std::fs::read(entry.path()).ok().and_then(|data| serde_json::from_slice::<Value>(&data).ok());
+                if har.is_some() {
+                    break;
+                }
+            }
+            actix_web::rt::time::sleep(Duration::from_millis(20)).await;
+        }
+        let har = har.expect("HAR file written");
+        let entry = &har["log"]["entries"][0];
+        let header = |headers: &Value, name: &str| -> Vec<String> {
+            headers
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter(|h| h["name"] == name)
+                .map(|h| h["value"].as_str().unwrap().to_owned())
+                .collect()
+        };
+
+        // 认证头和 cookie 默认不记录值
+        let request = &entry["request"];
+        assert_eq!(request["queryString"], json!([{ "name": "x", "value": "1" }]), "{backend}");
+        assert_eq!(header(&request["headers"], "authorization"), ["[redacted]"], "{backend}");
+        assert_eq!(header(&request["headers"], "cookie"), ["[redacted]"], "{backend}");
+        let response = &entry["response"];
+        assert_eq!(header(&response["headers"], "set-cookie"), ["[redacted]", "[redacted]"], "{backend}");
+        assert_eq!(header(&response["headers"], "content-encoding"), ["gzip"], "{backend}");
+
+        // 响应体是压缩之前的内容
+        let content = &response["content"];
+        assert!(content.get("encoding").is_none(), "{backend}");
+        let body: Value = serde_json::from_str(content["text"].as_str().unwrap()).unwrap();
+        assert_eq!(body["path"], "/echo/har", "{backend}");
+        assert_eq!(response["bodySize"], sent.len(), "{backend}");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
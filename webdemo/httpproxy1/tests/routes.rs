@@ -0,0 +1,295 @@
+//! 反向代理的默认路由、缓存清除和正向代理的集成测试，代理和上游都监听真实端口。
+
+use std::net::SocketAddr;
+
+use actix_web::{http::StatusCode, web, App, HttpRequest, HttpResponse};
+use httpproxy1::{
+    config::{Config, ForwardConfig, TimeoutConfig},
+    forward_proxy::{self, ForwardProxy},
+    ReverseProxy,
+};
+use serde_json::json;
+use tokio::{
+    io::{AsyncReadExt as _, AsyncWriteExt as _},
+    net::TcpStream,
+};
+use webdemo_common::health::{self, Health};
+use webdemo_testing::{assert, TestServer};
+
+/// 上游返回请求的方法和路径，`/healthz` 也由上游自己处理
+async fn upstream_echo(req: HttpRequest) -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header(("x-upstream", "yes"))
+        .insert_header(("cache-control", "max-age=60"))
+        .json(json!({ "method": req.method().as_str(), "path": req.path(), "query": req.query_string() }))
+}
+
+fn start_upstream() -> TestServer {
+    TestServer::start(|| App::new().default_service(web::to(upstream_echo)))
+}
+
+fn start_reverse_proxy(config: &Config) -> TestServer {
+    let proxy = web::Data::new(ReverseProxy::new(config).unwrap());
+    TestServer::start(move || {
+        App::new()
+            .configure(|cfg| proxy.configure(cfg))
+            .configure(Health::new(health::build_info!()).configure())
+    })
+}
+
+fn start_forward_proxy(config: &ForwardConfig) -> TestServer {
+    let proxy = web::Data::new(ForwardProxy::new(config, &TimeoutConfig::default()).unwrap());
+    TestServer::start(move || {
+        App::new()
+            .app_data(proxy.clone())
+            .default_service(web::to(forward_proxy::forward_proxy))
+            .configure(Health::new(health::build_info!()).configure())
+    })
+}
+
+fn forward_config(toml: &str) -> ForwardConfig {
+    Config::from_toml(&format!("[forward]\n{toml}")).unwrap().forward
+}
+
+fn proxy_client(proxy: SocketAddr) -> reqwest::Client {
+    reqwest::Client::builder()
+        .proxy(reqwest::Proxy::http(format!("http://{proxy}")).unwrap())
+        .build()
+        .unwrap()
+}
+
+#[actix_web::test]
+async fn default_routes_forward_to_upstream() {
+    let upstream = start_upstream();
+    let config = Config::from_toml(&format!("upstream = \"{}\"", upstream.url("/"))).unwrap();
+    let proxy = start_reverse_proxy(&config);
+
+    for (path, forwarded) in [
+        ("/using-reqwest/a/b?x=1", "/a/b"),
+        ("/using-awc/a/b?x=1", "/a/b"),
+        ("/a/b?x=1", "/a/b"),
+    ] {
+        let mut res = proxy.get(path).send().await.unwrap();
+        assert_eq!(assert::header(res.headers(), "x-upstream"), "yes", "{path}");
+        let body = assert::client_json(&mut res, StatusCode::OK).await;
+        assert_eq!(body, json!({ "method": "GET", "path": forwarded, "query": "x=1" }), "{path}");
+    }
+
+    // 代理自己的健康检查优先于默认路由
+    let mut res = proxy.get("/version").send().await.unwrap();
+    assert::json_includes(&assert::client_json(&mut res, StatusCode::OK).await, &json!({ "name": "httpproxy1" }));
+
+    proxy.stop().await;
+    upstream.stop().await;
+}
+
+async fn purge(proxy: &TestServer, query: &str) -> actix_web::web::Bytes {
+    let mut res = proxy
+        .request(actix_web::http::Method::DELETE, &format!("/_httpproxy1/cache{query}"))
+        .send()
+        .await
+        .unwrap();
+    assert::client_body(&mut res, StatusCode::OK).await
+}
+
+#[actix_web::test]
+async fn purges_cache_from_localhost() {
+    let upstream = start_upstream();
+    // 默认上游不可达，`/cached` 路由有自己的上游，清除时要按这个路由计算缓存键
+    let config = Config::from_toml(&format!(
+        "upstream = \"http://127.0.0.1:9/\"\n\
+         [[routes]]\nprefix = \"/cached\"\nstrip_prefix = true\nupstream = \"{}\"\n\
+         [cache]\nenabled = true",
+        upstream.url("/")
+    ))
+    .unwrap();
+    let proxy = start_reverse_proxy(&config);
+
+    assert_eq!(purge(&proxy, "?path=/cached/a").await, "purged 0 entries");
+    let mut res = proxy
+        .request(actix_web::http::Method::DELETE, "/_httpproxy1/cache?path=/other")
+        .send()
+        .await
+        .unwrap();
+    assert::client_body(&mut res, StatusCode::NOT_FOUND).await;
+
+    for expected in ["MISS", "HIT"] {
+        let res = proxy.get("/cached/a?x=1").send().await.unwrap();
+        assert_eq!(assert::header(res.headers(), "x-cache"), expected);
+    }
+    assert_eq!(purge(&proxy, "?path=/cached/a?x=1").await, "purged 1 entries");
+    let res = proxy.get("/cached/a?x=1").send().await.unwrap();
+    assert_eq!(assert::header(res.headers(), "x-cache"), "MISS");
+
+    assert_eq!(purge(&proxy, "").await, "purged 1 entries");
+
+    proxy.stop().await;
+    upstream.stop().await;
+}
+
+#[actix_web::test]
+async fn caches_rewritten_responses_per_route() {
+    let upstream = start_upstream();
+    // 两个路由转发到同一个上游地址，改写规则不同，缓存不能共用
+    let route = |prefix: &str| {
+        format!(
+            "[[routes]]\nprefix = \"/{prefix}\"\nstrip_prefix = true\nupstream = \"{}\"\n\
+             rewrite = {{ response_headers = {{ set = {{ \"x-route\" = \"{prefix}\" }} }} }}\n",
+            upstream.url("/")
+        )
+    };
+    let config = Config::from_toml(&format!(
+        "upstream = \"http://127.0.0.1:9/\"\n{}{}[cache]\nenabled = true",
+        route("a"),
+        route("b")
+    ))
+    .unwrap();
+    let proxy = start_reverse_proxy(&config);
+
+    for (path, expected) in [("/a/x", "MISS"), ("/b/x", "MISS"), ("/a/x", "HIT"), ("/b/x", "HIT")] {
+        let res = proxy.get(path).send().await.unwrap();
+        assert_eq!(assert::header(res.headers(), "x-cache"), expected, "{path}");
+        assert_eq!(assert::header(res.headers(), "x-route"), &path[1..2], "{path}");
+    }
+
+    // 清除只影响处理这个路径的路由
+    assert_eq!(purge(&proxy, "?path=/b/x").await, "purged 1 entries");
+    let res = proxy.get("/a/x").send().await.unwrap();
+    assert_eq!(assert::header(res.headers(), "x-cache"), "HIT");
+    let res = proxy.get("/b/x").send().await.unwrap();
+    assert_eq!(assert::header(res.headers(), "x-cache"), "MISS");
+
+    proxy.stop().await;
+    upstream.stop().await;
+}
+
+#[actix_web::test]
+async fn forwards_absolute_form_requests() {
+    let upstream = start_upstream();
+    let proxy = start_forward_proxy(&ForwardConfig::default());
+    let client = proxy_client(proxy.addr());
+
+    let res = client.get(upstream.url("/hello?x=1")).send().await.unwrap();
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["x-upstream"], "yes");
+    let body: serde_json::Value = serde_json::from_slice(&res.bytes().await.unwrap()).unwrap();
+    assert_eq!(body, json!({ "method": "GET", "path": "/hello", "query": "x=1" }));
+
+    // 发给上游的 /healthz 不能被代理自己的健康检查拦截
+    let res = client.get(upstream.url("/healthz")).send().await.unwrap();
+    assert_eq!(res.headers()["x-upstream"], "yes");
+
+    // 直接访问代理时是代理的健康检查
+    let mut res = proxy.get("/healthz").send().await.unwrap();
+    assert::json_includes(&assert::client_json(&mut res, StatusCode::OK).await, &json!({ "status": "ok" }));
+
+    // 其他 origin-form 请求不是代理请求
+    let mut res = proxy.get("/hello").send().await.unwrap();
+    assert::client_body(&mut res, StatusCode::BAD_REQUEST).await;
+
+    proxy.stop().await;
+    upstream.stop().await;
+}
+
+#[actix_web::test]
+async fn requires_proxy_authorization() {
+    let upstream = start_upstream();
+    let proxy = start_forward_proxy(&forward_config("auth = { username = \"user\", password = \"secret\" }"));
+
+    let res = proxy_client(proxy.addr()).get(upstream.url("/")).send().await.unwrap();
+    assert_eq!(res.status(), 407);
+    assert_eq!(res.headers()["proxy-authenticate"], "Basic realm=\"httpproxy1\"");
+
+    let client = reqwest::Client::builder()
+        .proxy(
+            reqwest::Proxy::http(format!("http://{}", proxy.addr()))
+                .unwrap()
+                .basic_auth("user", "secret"),
+        )
+        .build()
+        .unwrap();
+    let res = client.get(upstream.url("/")).send().await.unwrap();
+    assert_eq!(res.status(), 200);
+
+    // scheme 不区分大小写，错误的密码仍然是 407
+    for (authorization, status) in [("basic dXNlcjpzZWNyZXQ=", 200), ("Basic dXNlcjp3cm9uZw==", 407)] {
+        let res = proxy_client(proxy.addr())
+            .get(upstream.url("/"))
+            .header("proxy-authorization", authorization)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), status, "{authorization}");
+    }
+
+    proxy.stop().await;
+    upstream.stop().await;
+}
+
+/// 直接发送请求行，保留客户端写的主机名
+async fn raw_status(proxy: SocketAddr, request_line: &str) -> String {
+    let mut stream = TcpStream::connect(proxy).await.unwrap();
+    stream
+        .write_all(format!("{request_line}\r\nConnection: close\r\n\r\n").as_bytes())
+        .await
+        .unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+    String::from_utf8_lossy(&response).lines().next().unwrap_or_default().to_owned()
+}
+
+#[actix_web::test]
+async fn denies_blocked_destinations() {
+    let upstream = start_upstream();
+    let port = upstream.addr().port();
+    let proxy = start_forward_proxy(&forward_config(&format!("deny = [\"*:{port}\"]")));
+
+    let res = proxy_client(proxy.addr()).get(upstream.url("/")).send().await.unwrap();
+    assert_eq!(res.status(), 403);
+
+    proxy.stop().await;
+
+    // 网段规则匹配解析后的地址，换一种写法的回环地址也会被拦住
+    let proxy = start_forward_proxy(&forward_config("deny = [\"127.0.0.0/8\", \"::1\"]"));
+    for host in ["127.0.0.1", "127.1", "2130706433", "localhost", "[::1]"] {
+        let status = raw_status(proxy.addr(), &format!("GET http://{host}:{port}/ HTTP/1.1\r\nHost: {host}:{port}")).await;
+        assert_eq!(status, "HTTP/1.1 403 Forbidden", "{host}");
+        let status = raw_status(proxy.addr(), &format!("CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}")).await;
+        assert_eq!(status, "HTTP/1.1 403 Forbidden", "CONNECT {host}");
+    }
+    proxy.stop().await;
+
+    // 只允许的网段之外的地址不能访问
+    let proxy = start_forward_proxy(&forward_config("allow = [\"10.0.0.0/8\"]"));
+    let res = proxy_client(proxy.addr()).get(upstream.url("/")).send().await.unwrap();
+    assert_eq!(res.status(), 403);
+
+    proxy.stop().await;
+    upstream.stop().await;
+}
+
+#[actix_web::test]
+async fn tunnels_connect_requests() {
+    let upstream = start_upstream();
+    let proxy = start_forward_proxy(&ForwardConfig::default());
+
+    let mut stream = TcpStream::connect(proxy.addr()).await.unwrap();
+    let target = upstream.addr();
+    stream
+        .write_all(format!("CONNECT {target} HTTP/1.1\r\nHost: {target}\r\n\r\n").as_bytes())
+        .await
+        .unwrap();
+    stream
+        .write_all(format!("GET /tunneled HTTP/1.1\r\nHost: {target}\r\nConnection: close\r\n\r\n").as_bytes())
+        .await
+        .unwrap();
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+    let response = String::from_utf8_lossy(&response);
+    assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+    assert!(response.contains("\"path\":\"/tunneled\""), "{response}");
+
+    proxy.stop().await;
+    upstream.stop().await;
+}
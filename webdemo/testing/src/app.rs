@@ -0,0 +1,52 @@
+use std::rc::Rc;
+
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    dev::{Service as _, ServiceFactory, ServiceRequest, ServiceResponse},
+    test::{self, TestRequest},
+    App, Error,
+};
+use futures_util::future::LocalBoxFuture;
+
+type CallFn = dyn Fn(actix_http::Request) -> LocalBoxFuture<'static, Result<ServiceResponse, Error>>;
+
+/// 进程内初始化的 `App`，不监听端口，响应体统一转成 `BoxBody`
+pub struct TestApp {
+    call: Box<CallFn>,
+}
+
+impl TestApp {
+    pub async fn new<T, B>(app: App<T>) -> Self
+    where
+        T: ServiceFactory<
+                ServiceRequest,
+                Config = (),
+                Response = ServiceResponse<B>,
+                Error = Error,
+                InitError = (),
+            > + 'static,
+        B: MessageBody + 'static,
+    {
+        let service = Rc::new(test::init_service(app).await);
+        let call = move |req| {
+            let service = service.clone();
+            Box::pin(async move { service.call(req).await.map(ServiceResponse::map_into_boxed_body) })
+                as LocalBoxFuture<'static, _>
+        };
+        Self { call: Box::new(call) }
+    }
+
+    /// 处理请求，中间件或处理函数返回 `Err` 时 panic
+    pub async fn call(&self, req: TestRequest) -> ServiceResponse<BoxBody> {
+        (self.call)(req.to_request()).await.expect("service returned an error")
+    }
+
+    /// 中间件或处理函数返回的 `Err` 不转成响应，直接返回
+    pub async fn try_call(&self, req: TestRequest) -> Result<ServiceResponse<BoxBody>, Error> {
+        (self.call)(req.to_request()).await
+    }
+
+    pub async fn get(&self, path: &str) -> ServiceResponse<BoxBody> {
+        self.call(TestRequest::get().uri(path)).await
+    }
+}
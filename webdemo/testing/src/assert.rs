@@ -0,0 +1,88 @@
+//! 响应检查，失败时 panic 信息中带上状态码和响应体，方便定位
+
+use actix_web::{
+    body::MessageBody,
+    dev::ServiceResponse,
+    http::{header::HeaderMap, StatusCode},
+    test,
+    web::Bytes,
+};
+use serde_json::Value;
+
+/// 检查状态码并返回响应体
+pub async fn body<B: MessageBody>(res: ServiceResponse<B>, status: StatusCode) -> Bytes {
+    let actual = res.status();
+    let body = test::read_body(res).await;
+    assert_eq!(actual, status, "unexpected status, body: {}", String::from_utf8_lossy(&body));
+    body
+}
+
+/// 检查状态码并把响应体解析为 JSON
+pub async fn json<B: MessageBody>(res: ServiceResponse<B>, status: StatusCode) -> Value {
+    parse_json(&body(res, status).await)
+}
+
+/// 真实连接的响应：检查状态码并返回响应体
+pub async fn client_body<S>(res: &mut awc::ClientResponse<S>, status: StatusCode) -> Bytes
+where
+    S: futures_util::Stream<Item = Result<Bytes, awc::error::PayloadError>> + Unpin,
+{
+    let body = res.body().limit(8 * 1024 * 1024).await.expect("read response body");
+    assert_eq!(res.status(), status, "unexpected status, body: {}", String::from_utf8_lossy(&body));
+    body
+}
+
+/// 真实连接的响应：检查状态码并把响应体解析为 JSON
+pub async fn client_json<S>(res: &mut awc::ClientResponse<S>, status: StatusCode) -> Value
+where
+    S: futures_util::Stream<Item = Result<Bytes, awc::error::PayloadError>> + Unpin,
+{
+    parse_json(&client_body(res, status).await)
+}
+
+/// 响应头的值，不存在或不是可见字符时 panic
+pub fn header<'a>(headers: &'a HeaderMap, name: &str) -> &'a str {
+    headers
+        .get(name)
+        .unwrap_or_else(|| panic!("missing header {name}"))
+        .to_str()
+        .unwrap_or_else(|_| panic!("header {name} is not visible ASCII"))
+}
+
+/// `expected` 中的每个字段都出现在 `actual` 中且值相同，`actual` 可以有多余的字段。
+/// 数组要求长度相同，按位置比较。
+pub fn json_includes(actual: &Value, expected: &Value) {
+    if let Err(path) = includes(actual, expected, "$") {
+        panic!(
+            "json mismatch at {path}\n  actual:   {actual}\n  expected: {expected}"
+        );
+    }
+}
+
+fn includes(actual: &Value, expected: &Value, path: &str) -> Result<(), String> {
+    match (actual, expected) {
+        (Value::Object(actual), Value::Object(expected)) => {
+            for (key, expected) in expected {
+                let path = format!("{path}.{key}");
+                match actual.get(key) {
+                    Some(actual) => includes(actual, expected, &path)?,
+                    None => return Err(path),
+                }
+            }
+            Ok(())
+        }
+        (Value::Array(actual), Value::Array(expected)) if actual.len() == expected.len() => {
+            for (i, (actual, expected)) in actual.iter().zip(expected).enumerate() {
+                includes(actual, expected, &format!("{path}[{i}]"))?;
+            }
+            Ok(())
+        }
+        (actual, expected) if actual == expected => Ok(()),
+        _ => Err(path.to_owned()),
+    }
+}
+
+fn parse_json(body: &[u8]) -> Value {
+    serde_json::from_slice(body)
+        .unwrap_or_else(|e| panic!("invalid json ({e}): {}", String::from_utf8_lossy(body)))
+}
@@ -0,0 +1,36 @@
+//! webdemo 各个服务的集成测试工具，只作为 dev-dependency 使用。
+//!
+//! - 不需要网络的用例用 [`TestApp`] 在进程内调用 `App`，用 [`assert`] 中的函数检查响应
+//! - WebSocket 和代理需要真实的连接，用 [`TestServer`] 在随机端口启动同一个 `App`，
+//!   再用 [`TestServer::get`] 等发 HTTP 请求、[`TestServer::ws`] 打开 [`WsClient`]
+//!
+//! ```ignore
+//! #[actix_web::test]
+//! async fn echoes_text() {
+//!     let server = TestServer::start(wsserver1::app);
+//!     let mut ws = server.ws("/ws").await;
+//!     ws.send_text("hello").await;
+//!     assert_eq!(ws.recv_text().await, "hello");
+//! }
+//! ```
+
+use std::{env, path::Path, sync::Once};
+
+mod app;
+pub mod assert;
+mod server;
+mod ws;
+
+pub use app::TestApp;
+pub use server::TestServer;
+pub use ws::WsClient;
+
+/// 切换到 webdemo 工作区目录。服务按 `./wsserver1/static` 这样的相对路径读取文件，
+/// 而 `cargo test` 在各自的 crate 目录中运行测试。
+pub fn enter_workspace() {
+    static ONCE: Once = Once::new();
+    ONCE.call_once(|| {
+        let workspace = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+        env::set_current_dir(workspace).unwrap();
+    });
+}
@@ -0,0 +1,84 @@
+use std::{net::SocketAddr, time::Duration};
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServerHandle, ServiceFactory, ServiceRequest, ServiceResponse},
+    http::Method,
+    App, HttpServer,
+};
+
+use crate::ws::WsClient;
+
+/// 单个请求的超时，避免服务没有响应时测试一直挂起
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 在 `127.0.0.1` 的随机端口上运行的服务，测试结束时随 actix 运行时一起停止
+pub struct TestServer {
+    addr: SocketAddr,
+    handle: ServerHandle,
+    client: awc::Client,
+}
+
+impl TestServer {
+    /// 用和 `Server::run` 相同的 `App` 工厂启动一个 worker 的服务，需要在 actix 运行时中调用
+    pub fn start<F, T, B>(factory: F) -> Self
+    where
+        F: Fn() -> App<T> + Send + Clone + 'static,
+        T: ServiceFactory<
+                ServiceRequest,
+                Config = (),
+                Response = ServiceResponse<B>,
+                Error = actix_web::Error,
+                InitError = (),
+            > + 'static,
+        B: MessageBody + 'static,
+    {
+        let server = HttpServer::new(factory)
+            .workers(1)
+            .disable_signals()
+            .shutdown_timeout(1)
+            .bind(("127.0.0.1", 0))
+            .expect("bind test server");
+        let addr = server.addrs()[0];
+        let server = server.run();
+        let handle = server.handle();
+        actix_web::rt::spawn(server);
+
+        let client = awc::Client::builder()
+            .disable_redirects()
+            .timeout(REQUEST_TIMEOUT)
+            .finish();
+        Self { addr, handle, client }
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// `http://127.0.0.1:<port><path>`
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{path}", self.addr)
+    }
+
+    pub fn request(&self, method: Method, path: &str) -> awc::ClientRequest {
+        self.client.request(method, self.url(path))
+    }
+
+    pub fn get(&self, path: &str) -> awc::ClientRequest {
+        self.request(Method::GET, path)
+    }
+
+    pub fn post(&self, path: &str) -> awc::ClientRequest {
+        self.request(Method::POST, path)
+    }
+
+    /// 打开 WebSocket 连接，握手失败时 panic
+    pub async fn ws(&self, path: &str) -> WsClient {
+        WsClient::connect(&format!("ws://{}{path}", self.addr)).await
+    }
+
+    /// 等待处理中的请求完成后停止
+    pub async fn stop(self) {
+        self.handle.stop(true).await;
+    }
+}
@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+use actix_codec::Framed;
+use actix_web::{rt::time::timeout, web::Bytes};
+use awc::{
+    ws::{Codec, Frame, Message},
+    BoxedSocket,
+};
+use futures_util::{SinkExt as _, StreamExt as _};
+
+/// 等待一条消息的最长时间
+const RECV_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// WebSocket 测试客户端。服务端的心跳 ping 自动回复 pong，不会返回给调用方。
+pub struct WsClient {
+    framed: Framed<BoxedSocket, Codec>,
+}
+
+impl WsClient {
+    pub async fn connect(url: &str) -> Self {
+        let (res, framed) = awc::Client::new()
+            .ws(url)
+            .connect()
+            .await
+            .unwrap_or_else(|e| panic!("connect {url}: {e}"));
+        assert_eq!(res.status().as_u16(), 101, "websocket handshake with {url}");
+        Self { framed }
+    }
+
+    pub async fn send(&mut self, message: Message) {
+        self.framed.send(message).await.expect("send websocket message");
+    }
+
+    pub async fn send_text(&mut self, text: &str) {
+        self.send(Message::Text(text.to_owned().into())).await;
+    }
+
+    pub async fn send_binary(&mut self, bytes: impl Into<Bytes>) {
+        self.send(Message::Binary(bytes.into())).await;
+    }
+
+    pub async fn ping(&mut self, bytes: impl Into<Bytes>) {
+        self.send(Message::Ping(bytes.into())).await;
+    }
+
+    /// 下一帧，跳过服务端的 ping；超时或连接断开时 panic
+    pub async fn recv(&mut self) -> Frame {
+        self.try_recv(RECV_TIMEOUT)
+            .await
+            .unwrap_or_else(|| panic!("no websocket frame within {RECV_TIMEOUT:?}"))
+    }
+
+    /// `wait` 内没有收到消息时返回 `None`
+    pub async fn try_recv(&mut self, wait: Duration) -> Option<Frame> {
+        loop {
+            let frame = timeout(wait, self.framed.next()).await.ok()?;
+            match frame.expect("websocket closed").expect("websocket protocol error") {
+                Frame::Ping(bytes) => self.send(Message::Pong(bytes)).await,
+                frame => return Some(frame),
+            }
+        }
+    }
+
+    /// 下一条文本消息，收到其他帧时 panic
+    pub async fn recv_text(&mut self) -> String {
+        match self.recv().await {
+            Frame::Text(bytes) => String::from_utf8(bytes.to_vec()).expect("utf-8 text frame"),
+            frame => panic!("expected text frame, got {frame:?}"),
+        }
+    }
+
+    /// 依次收到 `expected` 中的文本消息
+    pub async fn expect_texts(&mut self, expected: &[&str]) {
+        for text in expected {
+            assert_eq!(self.recv_text().await, *text);
+        }
+    }
+
+    /// `wait` 内没有收到任何消息
+    pub async fn expect_silence(&mut self, wait: Duration) {
+        if let Some(frame) = self.try_recv(wait).await {
+            panic!("expected no websocket frame, got {frame:?}");
+        }
+    }
+
+    /// 发送关闭帧并等待服务端回复关闭帧
+    pub async fn close(mut self) {
+        self.send(Message::Close(None)).await;
+        loop {
+            match self.recv().await {
+                Frame::Close(_) => return,
+                _ => continue,
+            }
+        }
+    }
+}
@@ -1,24 +1,214 @@
-use actix_web::{get, web, Result, Responder};
+use std::sync::LazyLock;
+
+use actix_web::{get, post, web, Responder};
+use regex::Regex;
 use serde::{Serialize, Deserialize};
+use utoipa::{IntoParams, OpenApi, ToSchema};
+use validator::{Validate, ValidationError};
+
+use super::{
+    auth::{Authenticator, Principal},
+    authz::{Authorize, Require},
+    list::{ListQuery, Listable, Page, SortValue},
+    negotiate::Negotiated,
+    validate::{one_of, Valid},
+    ApiError,
+};
+
+/// `/api/v1` 下的接口文档，由 `openapi` 模块挂到 `/api/v1` 下
+#[derive(OpenApi)]
+#[openapi(paths(index, index1, action1, me, principals))]
+pub struct ApiV1Doc;
+
+/// `/api/v2` 下的接口文档
+#[derive(OpenApi)]
+#[openapi(paths(index_v2, index1, action1, me, principals))]
+pub struct ApiV2Doc;
+
+/// v1 的接口
+pub fn v1(cfg: &mut web::ServiceConfig) {
+    cfg.service(index).service(me).service(principals).service(index1).service(action1);
+}
+
+/// v2：`index.json` 去掉了和 HTTP 状态重复的 `code`，其他接口和 v1 相同
+pub fn v2(cfg: &mut web::ServiceConfig) {
+    cfg.service(index_v2).service(me).service(principals).service(index1).service(action1);
+}
+
+/// `index1` 需要 `api:read`
+const READ: Authorize<Require> = Authorize(Require { roles: &[], scopes: &["api:read"] });
+/// `action1` 需要 `api:write`
+const WRITE: Authorize<Require> = Authorize(Require { roles: &[], scopes: &["api:write"] });
+/// `principals` 需要 admin 角色
+const ADMIN: Authorize<Require> = Authorize(Require { roles: &["admin"], scopes: &[] });
 
-#[derive(Serialize, Deserialize)]
+static CONTROLLER: LazyLock<Regex> = LazyLock::new(|| Regex::new("^[a-z][a-z0-9_]*$").unwrap());
+
+#[derive(Serialize, Deserialize, Validate, IntoParams, ToSchema)]
+#[into_params(parameter_in = Path)]
 struct Url1Param {
+    #[validate(length(min = 1, max = 32), regex(path = *CONTROLLER, message = "lowercase letters, digits and _"))]
+    #[param(min_length = 1, max_length = 32, pattern = "^[a-z][a-z0-9_]*$")]
+    #[schema(min_length = 1, max_length = 32, pattern = "^[a-z][a-z0-9_]*$")]
     controller: String,
+    #[validate(range(min = 0, max = 10000))]
+    #[param(minimum = 0, maximum = 10000)]
+    #[schema(minimum = 0, maximum = 10000)]
     action: i32,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, Validate, ToSchema)]
+struct ActionRequest {
+    #[validate(length(min = 1, max = 64))]
+    name: String,
+    #[validate(custom(function = "action_mode"))]
+    #[schema(example = "sync")]
+    mode: String,
+    #[validate(length(max = 10))]
+    tags: Vec<String>,
+    #[validate(nested)]
+    options: ActionOptions,
+}
+
+#[derive(Serialize, Deserialize, Validate, ToSchema)]
+struct ActionOptions {
+    #[validate(range(max = 5))]
+    retries: u32,
+    #[validate(range(min = 100, max = 60000))]
+    timeout_ms: u64,
+}
+
+fn action_mode(mode: &str) -> Result<(), ValidationError> {
+    one_of(mode, &["sync", "async"])
+}
+
+#[derive(Serialize, ToSchema)]
 struct IndexResponse {
     code: i32,
     message: String,
 }
 
+#[utoipa::path(
+    responses(
+        (status = 200, body = IndexResponse),
+        (status = 401, body = ApiError),
+    ),
+    security(("bearer" = [])),
+)]
 #[get("/index.json")]
 async fn index() -> impl Responder {
-    web::Json(IndexResponse { code: 0, message: "Ok".to_owned() })
+    Negotiated(IndexResponse { code: 0, message: "Ok".to_owned() })
+}
+
+#[derive(Serialize, ToSchema)]
+struct IndexResponseV2 {
+    status: &'static str,
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, body = IndexResponseV2),
+        (status = 401, body = ApiError),
+    ),
+    security(("bearer" = [])),
+)]
+#[get("/index.json")]
+async fn index_v2() -> impl Responder {
+    Negotiated(IndexResponseV2 { status: "ok" })
+}
+
+#[utoipa::path(
+    params(Url1Param),
+    responses(
+        (status = 200, body = Url1Param),
+        (status = 401, body = ApiError),
+        (status = 403, body = ApiError),
+        (status = 422, description = "参数校验失败", body = ApiError),
+    ),
+    security(("bearer" = ["api:read"])),
+)]
+#[get("/{controller}/{action}.json", wrap = "READ")]
+async fn index1(param: Valid<web::Path<Url1Param>>) -> impl Responder {
+    Negotiated(param.into_inner().into_inner())
+}
+
+#[derive(Serialize, ToSchema)]
+struct ActionResponse {
+    #[serde(flatten)]
+    param: Url1Param,
+    request: ActionRequest,
+}
+
+#[utoipa::path(
+    params(Url1Param),
+    request_body = ActionRequest,
+    responses(
+        (status = 200, body = ActionResponse),
+        (status = 400, body = ApiError),
+        (status = 401, body = ApiError),
+        (status = 403, body = ApiError),
+        (status = 406, body = ApiError),
+        (status = 413, body = ApiError),
+        (status = 415, body = ApiError),
+        (status = 422, description = "参数校验失败", body = ApiError),
+    ),
+    security(("bearer" = ["api:write"])),
+)]
+#[post("/{controller}/{action}.json", wrap = "WRITE")]
+async fn action1(param: Valid<web::Path<Url1Param>>, body: Valid<Negotiated<ActionRequest>>) -> impl Responder {
+    Negotiated(ActionResponse {
+        param: param.into_inner().into_inner(),
+        request: body.into_inner().into_inner(),
+    })
+}
+
+/// 当前调用方
+#[utoipa::path(
+    responses(
+        (status = 200, body = Principal),
+        (status = 401, body = ApiError),
+    ),
+    security(("bearer" = [])),
+)]
+#[get("/me.json")]
+async fn me(principal: Principal) -> impl Responder {
+    Negotiated(principal)
+}
+
+impl Listable for Principal {
+    const SORT_FIELDS: &'static [&'static str] = &["subject", "expires_at"];
+    const FILTER_FIELDS: &'static [&'static str] = &["subject", "role", "scope"];
+    const KEY_FIELD: &'static str = "subject";
+
+    fn sort_value(&self, field: &str) -> SortValue {
+        match field {
+            "subject" => self.subject.as_str().into(),
+            // 不过期的排在最后
+            _ => self.expires_at.unwrap_or(u64::MAX).into(),
+        }
+    }
+
+    fn matches(&self, field: &str, value: &str) -> bool {
+        match field {
+            "subject" => self.subject == value,
+            "role" => self.has_role(value),
+            _ => self.has_scope(value),
+        }
+    }
 }
 
-#[get("/{controller}/{action}.json")]
-async fn index1(param: web::Path<Url1Param>) -> impl Responder {
-    web::Json(param.into_inner())
+/// token 文件中的调用方，JWT 的调用方不在其中
+#[utoipa::path(
+    params(ListQuery<Principal>),
+    responses(
+        (status = 200, body = Page<Principal>),
+        (status = 400, body = ApiError),
+        (status = 401, body = ApiError),
+        (status = 403, body = ApiError),
+    ),
+    security(("bearer" = [])),
+)]
+#[get("/principals.json", wrap = "ADMIN")]
+async fn principals(authenticator: web::Data<Authenticator>, query: ListQuery<Principal>) -> impl Responder {
+    Negotiated(query.apply(authenticator.principals().cloned()))
 }
@@ -0,0 +1,261 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::Path,
+    rc::Rc,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header,
+    Error, FromRequest, HttpMessage, HttpRequest,
+};
+use futures_util::future::{self, LocalBoxFuture, Ready};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::ApiError;
+use crate::config::AuthConfig;
+
+/// 通过认证的调用方
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Principal {
+    pub subject: String,
+    pub roles: Vec<String>,
+    pub scopes: Vec<String>,
+    /// 过期时间（Unix 秒）
+    pub expires_at: Option<u64>,
+}
+
+impl Principal {
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|r| r == role)
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == scope)
+    }
+}
+
+/// 处理函数通过参数获取当前调用方，没有经过 `Authentication` 时返回 401
+impl FromRequest for Principal {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let principal = req.extensions().get::<Principal>().cloned();
+        future::ready(principal.ok_or_else(|| {
+            ApiError::unauthorized("missing bearer token")
+                .with_request_id(req)
+                .into()
+        }))
+    }
+}
+
+/// token 文件中的一项
+/// `[{"token": "...", "subject": "alice", "roles": ["admin"], "scopes": ["api:read"], "expires_at": 1893456000}]`
+#[derive(Deserialize)]
+struct KeyEntry {
+    token: String,
+    subject: String,
+    #[serde(default)]
+    roles: Vec<String>,
+    #[serde(default)]
+    scopes: Vec<String>,
+    expires_at: Option<u64>,
+}
+
+/// JWT 的 claims，scope 可以是空格分隔的字符串（RFC 8693）或数组
+#[derive(Deserialize)]
+struct Claims {
+    sub: String,
+    exp: u64,
+    #[serde(default)]
+    roles: Vec<String>,
+    #[serde(default)]
+    scope: Option<String>,
+    #[serde(default)]
+    scopes: Vec<String>,
+}
+
+struct Jwt {
+    key: DecodingKey,
+    validation: Validation,
+}
+
+/// 校验 bearer token：先查 token 文件，再按 JWT 校验
+#[derive(Default)]
+pub struct Authenticator {
+    keys: HashMap<String, Principal>,
+    jwt: Option<Jwt>,
+}
+
+impl Authenticator {
+    pub fn new(config: &AuthConfig) -> io::Result<Self> {
+        let mut auth = Self::default();
+        if let Some(path) = &config.keys_file {
+            auth.load_keys(path)?;
+        }
+
+        let invalid = |e: jsonwebtoken::errors::Error| io::Error::new(io::ErrorKind::InvalidData, e);
+        let jwt = match (&config.jwt_secret, &config.jwt_public_key) {
+            (Some(secret), _) => Some((DecodingKey::from_secret(secret.as_bytes()), Algorithm::HS256)),
+            (None, Some(path)) => {
+                let pem = fs::read(path)?;
+                Some((DecodingKey::from_rsa_pem(&pem).map_err(invalid)?, Algorithm::RS256))
+            }
+            (None, None) => None,
+        };
+        auth.jwt = jwt.map(|(key, algorithm)| {
+            let mut validation = Validation::new(algorithm);
+            validation.set_required_spec_claims(&["exp", "sub"]);
+            if let Some(issuer) = &config.jwt_issuer {
+                validation.set_issuer(&[issuer]);
+            }
+            match &config.jwt_audience {
+                Some(audience) => validation.set_audience(&[audience]),
+                None => validation.validate_aud = false,
+            }
+            Jwt { key, validation }
+        });
+
+        if auth.keys.is_empty() && auth.jwt.is_none() {
+            log::warn!("no API keys or JWT key configured, all /api requests will be rejected");
+        }
+        Ok(auth)
+    }
+
+    fn load_keys(&mut self, path: &Path) -> io::Result<()> {
+        let entries: Vec<KeyEntry> = serde_json::from_slice(&fs::read(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {e}", path.display())))?;
+        log::info!("loaded {} API keys from {}", entries.len(), path.display());
+
+        self.keys = entries
+            .into_iter()
+            .map(|e| {
+                let principal = Principal {
+                    subject: e.subject,
+                    roles: e.roles,
+                    scopes: e.scopes,
+                    expires_at: e.expires_at,
+                };
+                (e.token, principal)
+            })
+            .collect();
+        Ok(())
+    }
+
+    /// token 文件中的调用方，不含 token
+    pub fn principals(&self) -> impl Iterator<Item = &Principal> {
+        self.keys.values()
+    }
+
+    pub fn authenticate(&self, token: &str) -> Result<Principal, ApiError> {
+        if let Some(principal) = self.keys.get(token) {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default();
+            return match principal.expires_at {
+                Some(expires_at) if expires_at <= now => Err(ApiError::invalid_token("token expired")),
+                _ => Ok(principal.clone()),
+            };
+        }
+
+        let Some(jwt) = &self.jwt else {
+            return Err(ApiError::invalid_token("unknown token"));
+        };
+        let claims = jsonwebtoken::decode::<Claims>(token, &jwt.key, &jwt.validation)
+            .map_err(|e| ApiError::invalid_token(e.to_string()))?
+            .claims;
+
+        let mut scopes = claims.scopes;
+        if let Some(scope) = claims.scope {
+            scopes.extend(scope.split_whitespace().map(str::to_owned));
+        }
+        Ok(Principal {
+            subject: claims.sub,
+            roles: claims.roles,
+            scopes,
+            expires_at: Some(claims.exp),
+        })
+    }
+}
+
+/// `Authorization: Bearer <token>` 认证中间件，通过后把 `Principal` 放入请求扩展
+pub struct Authentication {
+    authenticator: Arc<Authenticator>,
+}
+
+impl Authentication {
+    pub fn new(authenticator: Arc<Authenticator>) -> Self {
+        Self { authenticator }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Authentication
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = AuthenticationMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        future::ok(AuthenticationMiddleware {
+            service: Rc::new(service),
+            authenticator: self.authenticator.clone(),
+        })
+    }
+}
+
+pub struct AuthenticationMiddleware<S> {
+    service: Rc<S>,
+    authenticator: Arc<Authenticator>,
+}
+
+impl<S, B> Service<ServiceRequest> for AuthenticationMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let token = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().split_once(' '))
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+            .map(|(_, token)| token.trim());
+
+        let result = match token {
+            Some(token) => self.authenticator.authenticate(token),
+            None => Err(ApiError::unauthorized("missing bearer token")),
+        };
+        let principal = match result {
+            Ok(principal) => principal,
+            Err(e) => {
+                log::info!("{} {} rejected: {}", req.method(), req.path(), e.message);
+                let e = e.with_request_id(req.request());
+                return Box::pin(future::ok(req.error_response(e).map_into_right_body()));
+            }
+        };
+
+        req.extensions_mut().insert(principal);
+        let service = self.service.clone();
+        Box::pin(async move { service.call(req).await.map(ServiceResponse::map_into_left_body) })
+    }
+}
@@ -0,0 +1,179 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::Path,
+    rc::Rc,
+    sync::Arc,
+};
+
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error, HttpMessage,
+};
+use futures_util::future::{self, LocalBoxFuture, Ready};
+use serde::Deserialize;
+
+use super::{auth::Principal, ApiError};
+
+/// 授权规则，拒绝时返回原因
+pub trait Permission: Clone + 'static {
+    fn check(&self, principal: &Principal, req: &ServiceRequest) -> Result<(), String>;
+}
+
+/// 路由上声明的角色或 scope 要求。可以是常量，用在路由宏里（`wrap` 只接受路径）：
+/// `const READ: Authorize<Require> = Authorize(Require { roles: &[], scopes: &["api:read"] });`
+/// `#[get("/x", wrap = "READ")]`
+#[derive(Debug, Clone, Copy)]
+pub struct Require {
+    /// 满足任一角色，为空时不检查
+    pub roles: &'static [&'static str],
+    /// 需要全部 scope
+    pub scopes: &'static [&'static str],
+}
+
+impl Permission for Require {
+    fn check(&self, principal: &Principal, _: &ServiceRequest) -> Result<(), String> {
+        if !self.roles.is_empty() && !self.roles.iter().any(|r| principal.has_role(r)) {
+            return Err(format!("requires one of roles {:?}", self.roles));
+        }
+        match self.scopes.iter().find(|s| !principal.has_scope(s)) {
+            Some(scope) => Err(format!("missing scope {scope}")),
+            None => Ok(()),
+        }
+    }
+}
+
+/// 策略文件中的一条规则
+#[derive(Debug, Deserialize)]
+struct Rule {
+    /// 方法，`*` 匹配所有方法
+    methods: Vec<String>,
+    /// 路径模式，`*` 匹配一段中的任意字符，`**` 匹配任意多段
+    path: String,
+}
+
+impl Rule {
+    fn matches(&self, method: &str, path: &str) -> bool {
+        let method_matches = self
+            .methods
+            .iter()
+            .any(|m| m == "*" || m.eq_ignore_ascii_case(method));
+        let pattern: Vec<_> = self.path.trim_start_matches('/').split('/').collect();
+        let path: Vec<_> = path.trim_start_matches('/').split('/').collect();
+        method_matches && match_segments(&pattern, &path)
+    }
+}
+
+fn match_segments(pattern: &[&str], path: &[&str]) -> bool {
+    match (pattern.split_first(), path.split_first()) {
+        (Some((&"**", rest)), _) => (0..=path.len()).any(|i| match_segments(rest, &path[i..])),
+        (Some((p, pattern)), Some((s, path))) => glob(p, s) && match_segments(pattern, path),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+fn glob(pattern: &str, text: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == text,
+        Some((prefix, rest)) => text.strip_prefix(prefix).is_some_and(|text| {
+            (0..=text.len())
+                .filter(|i| text.is_char_boundary(*i))
+                .any(|i| glob(rest, &text[i..]))
+        }),
+    }
+}
+
+/// 角色到路径、方法的映射，请求需要被调用方的某个角色允许：
+/// `{"roles": {"reader": [{"methods": ["GET"], "path": "/api/**"}]}}`
+#[derive(Debug, Default, Deserialize)]
+pub struct Policy {
+    roles: HashMap<String, Vec<Rule>>,
+}
+
+impl Policy {
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        let policy: Self = serde_json::from_slice(&fs::read(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {e}", path.display())))?;
+        log::info!("loaded policy for {} roles from {}", policy.roles.len(), path.display());
+        Ok(policy)
+    }
+}
+
+impl Permission for Arc<Policy> {
+    fn check(&self, principal: &Principal, req: &ServiceRequest) -> Result<(), String> {
+        let method = req.method().as_str();
+        let path = req.path();
+        let allowed = principal.roles.iter().any(|role| {
+            self.roles
+                .get(role)
+                .is_some_and(|rules| rules.iter().any(|r| r.matches(method, path)))
+        });
+        match allowed {
+            true => Ok(()),
+            false => Err(format!("no role in {:?} permits {method} {path}", principal.roles)),
+        }
+    }
+}
+
+/// 授权中间件，需要在 `Authentication` 之后执行（先 `wrap` 这个），拒绝时返回 403 并记录原因
+#[derive(Clone)]
+pub struct Authorize<P>(pub P);
+
+impl<S, B, P> Transform<S, ServiceRequest> for Authorize<P>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+    P: Permission,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = AuthorizeMiddleware<S, P>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        future::ok(AuthorizeMiddleware {
+            service: Rc::new(service),
+            permission: self.0.clone(),
+        })
+    }
+}
+
+pub struct AuthorizeMiddleware<S, P> {
+    service: Rc<S>,
+    permission: P,
+}
+
+impl<S, B, P> Service<ServiceRequest> for AuthorizeMiddleware<S, P>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+    P: Permission,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let principal = req.extensions().get::<Principal>().cloned();
+        let result = match &principal {
+            Some(principal) => self.permission.check(principal, &req).map_err(|reason| {
+                log::warn!("{} denied {} {}: {reason}", principal.subject, req.method(), req.path());
+                ApiError::forbidden(reason)
+            }),
+            None => Err(ApiError::unauthorized("missing bearer token")),
+        };
+
+        if let Err(e) = result {
+            let e = e.with_request_id(req.request());
+            return Box::pin(future::ok(req.error_response(e).map_into_right_body()));
+        }
+
+        let service = self.service.clone();
+        Box::pin(async move { service.call(req).await.map(ServiceResponse::map_into_left_body) })
+    }
+}
@@ -0,0 +1,227 @@
+use actix_web::{
+    dev::ServiceResponse,
+    error::{self, JsonPayloadError, PathError, QueryPayloadError, UrlencodedError},
+    http::{
+        header::{self, ContentType},
+        StatusCode,
+    },
+    middleware::{ErrorHandlerResponse, ErrorHandlers},
+    web, HttpRequest, HttpResponse,
+};
+use derive_more::{Display, Error};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::negotiate::NegotiatedConfig;
+use crate::config::LimitsConfig;
+
+/// 请求头中的请求 id，返回在错误信息里方便排查
+pub const REQUEST_ID: &str = "x-request-id";
+
+/// 字段级错误
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+/// API 错误，统一的 JSON 格式：
+/// `{"code": "invalid_json", "message": "...", "details": [...], "request_id": "..."}`
+#[derive(Debug, Clone, Display, Serialize, Error, ToSchema)]
+#[display(fmt = "api error {}: {}", code, message)]
+pub struct ApiError {
+    #[serde(skip)]
+    pub status: StatusCode,
+    /// 机器可读的错误码
+    pub code: &'static str,
+    pub message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<FieldError>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+            details: Vec::new(),
+            request_id: None,
+        }
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "bad_request", message)
+    }
+
+    /// 没有 token，401
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, "unauthorized", message)
+    }
+
+    /// token 无效或过期，401
+    pub fn invalid_token(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, "invalid_token", message)
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::FORBIDDEN, "forbidden", message)
+    }
+
+    pub fn not_found() -> Self {
+        Self::new(StatusCode::NOT_FOUND, "not_found", "resource not found")
+    }
+
+    pub fn method_not_allowed() -> Self {
+        Self::new(StatusCode::METHOD_NOT_ALLOWED, "method_not_allowed", "method not allowed")
+    }
+
+    pub fn with_detail(mut self, field: impl Into<String>, message: impl Into<String>) -> Self {
+        self.details.push(FieldError {
+            field: field.into(),
+            message: message.into(),
+        });
+        self
+    }
+
+    /// 带上请求的 `X-Request-Id`
+    pub fn with_request_id(mut self, req: &HttpRequest) -> Self {
+        self.request_id = req
+            .headers()
+            .get(REQUEST_ID)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_owned);
+        self
+    }
+
+    /// serde 的错误信息可以看出缺少或多余的字段时加上字段错误，
+    /// 例如 missing field `name`
+    pub(super) fn deserialize(code: &'static str, message: String) -> Self {
+        let field = ["missing field", "unknown field"].into_iter().find_map(|reason| {
+            let rest = message.strip_prefix(reason)?.strip_prefix(" `")?;
+            Some((rest.split('`').next()?.to_owned(), reason))
+        });
+
+        let error = Self::new(StatusCode::BAD_REQUEST, code, message);
+        match field {
+            Some((field, reason)) => error.with_detail(field, reason),
+            None => error,
+        }
+    }
+}
+
+impl error::ResponseError for ApiError {
+    fn error_response(&self) -> HttpResponse {
+        let mut res = HttpResponse::build(self.status_code());
+        res.insert_header(ContentType::json());
+        // https://www.rfc-editor.org/rfc/rfc6750#section-3
+        if self.status == StatusCode::UNAUTHORIZED {
+            let challenge = match self.code {
+                "invalid_token" => r#"Bearer error="invalid_token""#,
+                _ => "Bearer",
+            };
+            res.insert_header((header::WWW_AUTHENTICATE, challenge));
+        }
+        res.body(serde_json::to_string(&self).unwrap())
+    }
+
+    fn status_code(&self) -> StatusCode {
+        self.status
+    }
+}
+
+impl From<JsonPayloadError> for ApiError {
+    fn from(e: JsonPayloadError) -> Self {
+        match e {
+            JsonPayloadError::OverflowKnownLength { .. } | JsonPayloadError::Overflow { .. } => {
+                Self::new(StatusCode::PAYLOAD_TOO_LARGE, "payload_too_large", e.to_string())
+            }
+            JsonPayloadError::ContentType => Self::new(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "unsupported_media_type",
+                "expected content-type application/json",
+            ),
+            JsonPayloadError::Deserialize(e) => Self::deserialize("invalid_json", e.to_string()),
+            e => Self::new(StatusCode::BAD_REQUEST, "invalid_body", e.to_string()),
+        }
+    }
+}
+
+impl From<PathError> for ApiError {
+    fn from(e: PathError) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "invalid_path", e.to_string())
+    }
+}
+
+impl From<QueryPayloadError> for ApiError {
+    fn from(e: QueryPayloadError) -> Self {
+        Self::deserialize("invalid_query", e.to_string())
+    }
+}
+
+impl From<UrlencodedError> for ApiError {
+    fn from(e: UrlencodedError) -> Self {
+        match e {
+            UrlencodedError::Overflow { .. } => {
+                Self::new(StatusCode::PAYLOAD_TOO_LARGE, "payload_too_large", e.to_string())
+            }
+            UrlencodedError::ContentType => Self::new(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "unsupported_media_type",
+                "expected content-type application/x-www-form-urlencoded",
+            ),
+            UrlencodedError::Parse(e) => Self::deserialize("invalid_form", e.to_string()),
+            e => Self::new(StatusCode::BAD_REQUEST, "invalid_form", e.to_string()),
+        }
+    }
+}
+
+/// 提取器的大小限制，错误使用 `ApiError` 格式，而不是 actix 默认的纯文本
+pub fn configure(cfg: &mut web::ServiceConfig, limits: &LimitsConfig) {
+    cfg.app_data(web::PayloadConfig::new(limits.payload_bytes))
+        .app_data(NegotiatedConfig { limit: limits.json_bytes })
+        .app_data(
+            web::JsonConfig::default()
+                .limit(limits.json_bytes)
+                .error_handler(|e, req| ApiError::from(e).with_request_id(req).into()),
+        )
+        .app_data(
+            web::PathConfig::default()
+                .error_handler(|e, req| ApiError::from(e).with_request_id(req).into()),
+        )
+        .app_data(
+            web::QueryConfig::default()
+                .error_handler(|e, req| ApiError::from(e).with_request_id(req).into()),
+        )
+        .app_data(
+            web::FormConfig::default()
+                .limit(limits.form_bytes)
+                .error_handler(|e, req| ApiError::from(e).with_request_id(req).into()),
+        );
+}
+
+/// 没有匹配的路由
+pub async fn not_found(req: HttpRequest) -> Result<HttpResponse, ApiError> {
+    Err(ApiError::not_found().with_request_id(&req))
+}
+
+/// 路径匹配但方法不匹配时 actix 返回空的 405，换成 `ApiError` 格式并保留 `Allow`
+pub fn error_handlers<B: 'static>() -> ErrorHandlers<B> {
+    ErrorHandlers::new().handler(StatusCode::METHOD_NOT_ALLOWED, |res: ServiceResponse<B>| {
+        // 处理函数自己返回的错误已经是 JSON
+        if res.response().error().is_some() {
+            return Ok(ErrorHandlerResponse::Response(res.map_into_left_body()));
+        }
+
+        let (req, res) = res.into_parts();
+        let mut new = error::ResponseError::error_response(
+            &ApiError::method_not_allowed().with_request_id(&req),
+        );
+        if let Some(allow) = res.headers().get(header::ALLOW) {
+            new.headers_mut().insert(header::ALLOW, allow.clone());
+        }
+        Ok(ErrorHandlerResponse::Response(ServiceResponse::new(req, new).map_into_right_body()))
+    })
+}
@@ -0,0 +1,326 @@
+use actix_files::NamedFile;
+use actix_multipart::{Multipart, MultipartError};
+use actix_web::{
+    delete, get,
+    http::{
+        header::{Charset, ContentDisposition, DispositionParam, DispositionType, ExtendedValue},
+        StatusCode,
+    },
+    post, web, HttpRequest, HttpResponse, Responder,
+};
+use futures_util::TryStreamExt as _;
+use serde::Serialize;
+use utoipa::{OpenApi, ToSchema};
+
+use super::{
+    auth::Principal,
+    authz::{Authorize, Require},
+    list::{ListQuery, Listable, Page, SortValue},
+    negotiate::Negotiated,
+    ApiError,
+};
+use crate::files::{self, FileMeta, FileStore, Upload};
+
+/// 下载和查看需要 `api:read`
+const READ: Authorize<Require> = Authorize(Require {
+    roles: &[],
+    scopes: &["api:read"],
+});
+/// 上传和删除需要 `api:write`
+const WRITE: Authorize<Require> = Authorize(Require {
+    roles: &[],
+    scopes: &["api:write"],
+});
+
+/// 下载响应中的摘要
+const CHECKSUM: &str = "x-checksum-sha256";
+
+/// `/files` 的接口文档，挂在每个版本下
+#[derive(OpenApi)]
+#[openapi(paths(list, upload, metadata, download, remove))]
+pub struct FilesDoc;
+
+/// 所有版本的 `/files` 接口相同
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(list)
+        .service(upload)
+        .service(metadata)
+        .service(download)
+        .service(remove);
+}
+
+impl Listable for FileMeta {
+    const SORT_FIELDS: &'static [&'static str] = &["created_at", "name", "size", "id"];
+    const FILTER_FIELDS: &'static [&'static str] = &["owner", "content_type", "name"];
+    const KEY_FIELD: &'static str = "id";
+    const DEFAULT_SORT: &'static str = "-created_at";
+
+    fn sort_value(&self, field: &str) -> SortValue {
+        match field {
+            "created_at" => self.created_at.into(),
+            "name" => self.name.as_str().into(),
+            "size" => self.size.into(),
+            _ => self.id.as_str().into(),
+        }
+    }
+
+    fn matches(&self, field: &str, value: &str) -> bool {
+        match field {
+            "owner" => self.owner == value,
+            // 不带参数比较，`text/plain` 匹配 `text/plain; charset=utf-8`
+            "content_type" => self.content_type.split(';').next() == Some(value),
+            _ => self.name == value,
+        }
+    }
+}
+
+impl From<MultipartError> for ApiError {
+    fn from(e: MultipartError) -> Self {
+        match e {
+            MultipartError::NoContentType | MultipartError::ParseContentType | MultipartError::Boundary => Self::new(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "unsupported_media_type",
+                "expected content-type multipart/form-data",
+            ),
+            e => Self::new(StatusCode::BAD_REQUEST, "invalid_multipart", e.to_string()),
+        }
+    }
+}
+
+/// 本次上传保存的文件
+#[derive(Serialize, ToSchema)]
+struct UploadResponse {
+    files: Vec<FileMeta>,
+}
+
+/// 上传的文件列表
+#[utoipa::path(
+    params(ListQuery<FileMeta>),
+    responses(
+        (status = 200, body = Page<FileMeta>),
+        (status = 400, body = ApiError),
+        (status = 401, body = ApiError),
+        (status = 403, body = ApiError),
+    ),
+    security(("bearer" = ["api:read"])),
+)]
+#[get("/files", wrap = "READ")]
+async fn list(store: web::Data<FileStore>, query: ListQuery<FileMeta>) -> impl Responder {
+    Negotiated(query.apply(store.list()))
+}
+
+/// `multipart/form-data` 上传，带文件名的字段都作为文件保存，其他字段忽略。
+/// 任何一个文件失败时，本次请求已经保存的文件也会删除。
+#[utoipa::path(
+    request_body(content_type = "multipart/form-data", description = "一个或多个文件字段"),
+    responses(
+        (status = 201, body = UploadResponse),
+        (status = 400, body = ApiError),
+        (status = 401, body = ApiError),
+        (status = 403, body = ApiError),
+        (status = 413, body = ApiError),
+        (status = 415, description = "不是 multipart 请求或文件类型不允许", body = ApiError),
+    ),
+    security(("bearer" = ["api:write"])),
+)]
+#[post("/files", wrap = "WRITE")]
+async fn upload(
+    req: HttpRequest,
+    store: web::Data<FileStore>,
+    principal: Principal,
+    mut payload: Multipart,
+) -> Result<HttpResponse, ApiError> {
+    let mut saved: Vec<FileMeta> = Vec::new();
+    let result: Result<(), ApiError> = async {
+        let mut total = 0;
+        while let Some(mut field) = payload.try_next().await? {
+            let Some(name) = field.content_disposition().get_filename().map(files::sanitize_name) else {
+                while field.try_next().await?.is_some() {}
+                continue;
+            };
+            if saved.len() == store.max_files {
+                return Err(ApiError::new(
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    "too_many_files",
+                    format!("at most {} files per request", store.max_files),
+                ));
+            }
+
+            let remaining = store.max_total_bytes - total;
+            let limit = store.max_file_bytes.min(remaining);
+            let too_large = || match limit < store.max_file_bytes {
+                true => ApiError::new(
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    "upload_too_large",
+                    format!("files in one request exceed {} bytes", store.max_total_bytes),
+                ),
+                false => ApiError::new(
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    "file_too_large",
+                    format!("{name} exceeds {} bytes", store.max_file_bytes),
+                ),
+            };
+            let upload = Upload {
+                name: name.clone(),
+                declared_type: field.content_type().map(ToString::to_string),
+                owner: principal.subject.clone(),
+            };
+            let meta = store.save(upload, &mut field, limit, too_large).await?;
+            total += meta.size;
+            saved.push(meta);
+        }
+        match saved.is_empty() {
+            true => Err(ApiError::bad_request("no files in request")),
+            false => Ok(()),
+        }
+    }
+    .await;
+
+    if let Err(e) = result {
+        for meta in &saved {
+            if let Err(e) = store.delete(&meta.id).await {
+                log::error!("failed to remove {} after a failed upload: {e}", meta.id);
+            }
+        }
+        return Err(e.with_request_id(&req));
+    }
+
+    for meta in &saved {
+        log::info!(
+            "{} uploaded {} ({}, {} bytes) as {}",
+            meta.owner,
+            meta.name,
+            meta.content_type,
+            meta.size,
+            meta.id
+        );
+    }
+    let location = match saved.as_slice() {
+        [meta] => Some(format!("{}/{}", req.path(), meta.id)),
+        _ => None,
+    };
+    let mut res = Negotiated(UploadResponse { files: saved }).respond_to(&req);
+    // 406 等错误保持原来的状态码
+    if res.status() == StatusCode::OK {
+        *res.status_mut() = StatusCode::CREATED;
+        if let Some(location) = location.and_then(|l| l.parse().ok()) {
+            res.headers_mut().insert(actix_web::http::header::LOCATION, location);
+        }
+    }
+    Ok(res)
+}
+
+fn find(store: &FileStore, id: &str, req: &HttpRequest) -> Result<FileMeta, ApiError> {
+    store.get(id).ok_or_else(|| ApiError::not_found().with_request_id(req))
+}
+
+/// 文件的元数据
+#[utoipa::path(
+    params(("id", description = "上传时返回的 id")),
+    responses(
+        (status = 200, body = FileMeta),
+        (status = 401, body = ApiError),
+        (status = 403, body = ApiError),
+        (status = 404, body = ApiError),
+    ),
+    security(("bearer" = ["api:read"])),
+)]
+#[get("/files/{id}/meta", wrap = "READ")]
+async fn metadata(
+    req: HttpRequest,
+    store: web::Data<FileStore>,
+    id: web::Path<String>,
+) -> Result<impl Responder, ApiError> {
+    Ok(Negotiated(find(&store, &id, &req)?))
+}
+
+/// 下载，支持 `Range`、`If-Range` 和条件请求，`Content-Type` 是上传时按内容识别的类型
+#[utoipa::path(
+    params(("id", description = "上传时返回的 id")),
+    responses(
+        (status = 200, description = "文件内容，`X-Checksum-Sha256` 是十六进制的 SHA-256"),
+        (status = 206, description = "`Range` 请求的部分内容"),
+        (status = 401, body = ApiError),
+        (status = 403, body = ApiError),
+        (status = 404, body = ApiError),
+        (status = 416, description = "`Range` 超出文件大小"),
+    ),
+    security(("bearer" = ["api:read"])),
+)]
+#[get("/files/{id}", wrap = "READ")]
+async fn download(
+    req: HttpRequest,
+    store: web::Data<FileStore>,
+    id: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let meta = find(&store, &id, &req)?;
+    let file = NamedFile::open_async(store.path(&meta)).await.map_err(|e| {
+        log::error!("failed to open {}: {e}", meta.id);
+        ApiError::not_found().with_request_id(&req)
+    })?;
+
+    let mut res = file
+        .set_content_type(meta.content_type.parse().unwrap_or(mime::APPLICATION_OCTET_STREAM))
+        .set_content_disposition(attachment(&meta.name))
+        .into_response(&req);
+    if let Ok(checksum) = meta.sha256.parse() {
+        res.headers_mut()
+            .insert(actix_web::http::header::HeaderName::from_static(CHECKSUM), checksum);
+    }
+    Ok(res)
+}
+
+/// 上传者或 admin 角色可以删除
+#[utoipa::path(
+    params(("id", description = "上传时返回的 id")),
+    responses(
+        (status = 204),
+        (status = 401, body = ApiError),
+        (status = 403, body = ApiError),
+        (status = 404, body = ApiError),
+    ),
+    security(("bearer" = ["api:write"])),
+)]
+#[delete("/files/{id}", wrap = "WRITE")]
+async fn remove(
+    req: HttpRequest,
+    store: web::Data<FileStore>,
+    principal: Principal,
+    id: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let meta = find(&store, &id, &req)?;
+    if meta.owner != principal.subject && !principal.has_role("admin") {
+        return Err(ApiError::forbidden("only the owner can delete this file").with_request_id(&req));
+    }
+    match store.delete(&meta.id).await {
+        Ok(_) => {
+            log::info!("{} deleted {} ({})", principal.subject, meta.name, meta.id);
+            Ok(HttpResponse::NoContent().finish())
+        }
+        Err(e) => {
+            log::error!("failed to delete {}: {e}", meta.id);
+            Err(
+                ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "internal", "failed to delete file")
+                    .with_request_id(&req),
+            )
+        }
+    }
+}
+
+/// 非 ASCII 文件名同时给出 `filename*`（RFC 6266）
+fn attachment(name: &str) -> ContentDisposition {
+    let mut parameters = vec![DispositionParam::Filename(
+        name.replace(|c: char| !c.is_ascii() || c == '"', "_"),
+    )];
+    if !name.is_ascii() {
+        parameters.push(DispositionParam::FilenameExt(ExtendedValue {
+            charset: Charset::Ext("UTF-8".to_owned()),
+            language_tag: None,
+            value: name.as_bytes().to_vec(),
+        }));
+    }
+    ContentDisposition {
+        disposition: DispositionType::Attachment,
+        parameters,
+    }
+}
@@ -0,0 +1,451 @@
+use std::{cmp::Ordering, marker::PhantomData};
+
+use actix_web::{dev::Payload, Error, FromRequest, HttpRequest};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use futures_util::future::{self, Ready};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use utoipa::{
+    openapi::{
+        path::{Parameter, ParameterBuilder, ParameterIn},
+        schema::{ObjectBuilder, SchemaType, Type},
+        Required,
+    },
+    IntoParams, ToSchema,
+};
+
+use super::ApiError;
+
+/// 列表接口的资源，声明可以排序和过滤的字段。
+/// 查询参数中不在白名单里的字段返回 400，不会传给数据层。
+pub trait Listable {
+    /// 可以出现在 `sort` 中的字段
+    const SORT_FIELDS: &'static [&'static str];
+    /// 可以出现在 `filter[field]` 中的字段
+    const FILTER_FIELDS: &'static [&'static str];
+    /// 唯一标识一条的字段，总是追加在排序最后，
+    /// 排序值相同的条目顺序固定，游标可以准确定位
+    const KEY_FIELD: &'static str;
+    /// 默认排序，格式和 `sort` 相同，追加在请求的排序之后
+    const DEFAULT_SORT: &'static str = "";
+    const DEFAULT_LIMIT: u32 = 20;
+    const MAX_LIMIT: u32 = 100;
+
+    /// 字段的排序值，字段一定在 `SORT_FIELDS` 中或者是 `KEY_FIELD`
+    fn sort_value(&self, field: &str) -> SortValue;
+
+    /// 按字段比较
+    fn compare(&self, other: &Self, field: &str) -> Ordering {
+        self.sort_value(field).cmp(&other.sort_value(field))
+    }
+
+    /// 是否满足 `filter[field]=value`，字段一定在 `FILTER_FIELDS` 中
+    fn matches(&self, field: &str, value: &str) -> bool;
+}
+
+/// 排序字段，`sort=-field` 表示降序
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortKey {
+    pub field: &'static str,
+    pub descending: bool,
+}
+
+/// 排序值，游标中保存一页边界那一条的排序值
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SortValue {
+    Int(u64),
+    Str(String),
+}
+
+impl From<u64> for SortValue {
+    fn from(value: u64) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<&str> for SortValue {
+    fn from(value: &str) -> Self {
+        Self::Str(value.to_owned())
+    }
+}
+
+/// 页码或游标，同一个请求只能用一种
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Position {
+    /// 从 1 开始的页码
+    Page(u32),
+    /// `cursor=first`，游标分页的第一页
+    First,
+    /// 排在这些排序值之后的一页
+    After(Vec<SortValue>),
+    /// 排在这些排序值之前的一页
+    Before(Vec<SortValue>),
+}
+
+/// 列表接口的查询参数：
+/// `?page=2&limit=20&sort=-expires_at,subject&filter[role]=admin`，
+/// 或者用 `cursor=first` 和之后响应中 `links.next` 的 `cursor` 代替 `page`。
+/// 游标按排序值定位，翻页期间插入或删除条目不会重复或遗漏；
+/// 游标只能用于签发时的排序和过滤条件。
+/// 同一个字段的多个 `filter` 满足任一即可，不同字段需要全部满足。
+pub struct ListQuery<T> {
+    pub sort: Vec<SortKey>,
+    pub filters: Vec<(&'static str, String)>,
+    pub limit: u32,
+    pub position: Position,
+    path: String,
+    /// 生成链接时保留的参数，不含 `page` 和 `cursor`
+    params: Vec<(String, String)>,
+    resource: PhantomData<fn() -> T>,
+}
+
+impl<T: Listable> ListQuery<T> {
+    /// 解析查询字符串，`path` 用于生成响应中的链接
+    pub fn parse(path: &str, query: &str) -> Result<Self, ApiError> {
+        let pairs: Vec<(String, String)> =
+            serde_urlencoded::from_str(query).map_err(|e| ApiError::deserialize("invalid_query", e.to_string()))?;
+        let invalid = |field: &str, message: String| {
+            ApiError::bad_request("invalid list query").with_detail(field, message)
+        };
+
+        let mut sort = None;
+        let mut filters = Vec::new();
+        let mut limit = None;
+        let mut page = None;
+        let mut cursor = None;
+        let mut params = Vec::new();
+        for (name, value) in pairs {
+            match name.as_str() {
+                "sort" => sort = Some(value.clone()),
+                "limit" => {
+                    let n = value.parse::<u32>().ok().filter(|n| (1..=T::MAX_LIMIT).contains(n));
+                    limit = Some(n.ok_or_else(|| {
+                        invalid("limit", format!("must be between 1 and {}", T::MAX_LIMIT))
+                    })?);
+                }
+                "page" => {
+                    let n = value.parse::<u32>().ok().filter(|n| *n >= 1);
+                    page = Some(n.ok_or_else(|| invalid("page", "must be a positive integer".to_owned()))?);
+                    continue;
+                }
+                "cursor" => {
+                    cursor = Some(value);
+                    continue;
+                }
+                _ => {
+                    let Some(field) = name.strip_prefix("filter[").and_then(|f| f.strip_suffix(']')) else {
+                        return Err(invalid(&name, "unknown parameter".to_owned()));
+                    };
+                    let field = allowed(T::FILTER_FIELDS, field)
+                        .ok_or_else(|| invalid(&name, format!("allowed filters: {}", T::FILTER_FIELDS.join(", "))))?;
+                    filters.push((field, value.clone()));
+                }
+            }
+            params.push((name, value));
+        }
+
+        let mut sort = parse_sort(T::SORT_FIELDS, &format!("{},{}", sort.unwrap_or_default(), T::DEFAULT_SORT))
+            .map_err(|field| invalid("sort", format!("cannot sort by `{field}`, allowed: {}", T::SORT_FIELDS.join(", "))))?;
+        if sort.iter().all(|k| k.field != T::KEY_FIELD) {
+            sort.push(SortKey { field: T::KEY_FIELD, descending: false });
+        }
+
+        let position = match (page, cursor) {
+            (Some(_), Some(_)) => return Err(invalid("cursor", "cannot be used with page".to_owned())),
+            (_, Some(cursor)) if cursor == FIRST_CURSOR => Position::First,
+            (_, Some(cursor)) => {
+                let cursor = Cursor::decode(&cursor)
+                    .filter(|c| c.key.len() == sort.len())
+                    .ok_or_else(|| invalid("cursor", "invalid cursor".to_owned()))?;
+                if cursor.query != query_digest(&sort, &filters) {
+                    return Err(invalid("cursor", "cursor was issued for a different sort or filter".to_owned()));
+                }
+                match cursor.before {
+                    false => Position::After(cursor.key),
+                    true => Position::Before(cursor.key),
+                }
+            }
+            (page, None) => Position::Page(page.unwrap_or(1)),
+        };
+
+        Ok(Self {
+            sort,
+            filters,
+            limit: limit.unwrap_or(T::DEFAULT_LIMIT),
+            position,
+            path: path.to_owned(),
+            params,
+            resource: PhantomData,
+        })
+    }
+
+    /// 页码分页跳过的条数，游标分页时为 0
+    pub fn offset(&self) -> usize {
+        match self.position {
+            Position::Page(page) => (page as usize - 1).saturating_mul(self.limit as usize),
+            _ => 0,
+        }
+    }
+
+    /// 按 `sort` 比较一条和游标中的排序值
+    pub fn compare_key(&self, item: &T, key: &[SortValue]) -> Ordering {
+        self.sort.iter().zip(key).fold(Ordering::Equal, |ordering, (sort, value)| {
+            ordering.then_with(|| {
+                let ordering = item.sort_value(sort.field).cmp(value);
+                match sort.descending {
+                    false => ordering,
+                    true => ordering.reverse(),
+                }
+            })
+        })
+    }
+
+    /// 在内存中过滤、排序和分页
+    pub fn apply(&self, items: impl IntoIterator<Item = T>) -> Page<T> {
+        let mut items: Vec<T> = items
+            .into_iter()
+            .filter(|item| {
+                self.filters.iter().all(|(field, _)| {
+                    self.filters
+                        .iter()
+                        .filter(|(f, _)| f == field)
+                        .any(|(_, value)| item.matches(field, value))
+                })
+            })
+            .collect();
+        items.sort_by(|a, b| {
+            self.sort.iter().fold(Ordering::Equal, |ordering, key| {
+                ordering.then_with(|| match key.descending {
+                    false => a.compare(b, key.field),
+                    true => b.compare(a, key.field),
+                })
+            })
+        });
+
+        let total = items.len();
+        let limit = self.limit as usize;
+        let (start, end) = match &self.position {
+            Position::Before(key) => {
+                let end = items.partition_point(|item| self.compare_key(item, key).is_lt());
+                (end.saturating_sub(limit), end)
+            }
+            position => {
+                let start = match position {
+                    Position::After(key) => items.partition_point(|item| self.compare_key(item, key).is_le()),
+                    _ => self.offset().min(total),
+                };
+                (start, start.saturating_add(limit).min(total))
+            }
+        };
+        let items = items.into_iter().skip(start).take(end - start).collect();
+        self.page(items, total, start)
+    }
+
+    /// 数据层已经按 `sort`、`filters` 和位置查出这一页，
+    /// `total` 是过滤后的总数，`before` 是排在这一页之前的条数
+    pub fn page(&self, items: Vec<T>, total: usize, before: usize) -> Page<T> {
+        let has_prev = before > 0;
+        let has_next = before.saturating_add(items.len()) < total;
+
+        let (page, pages, links) = match self.position {
+            Position::Page(page) => {
+                let pages = total.div_ceil(self.limit as usize).max(1);
+                let links = Links {
+                    this: self.link("page", page.to_string()),
+                    first: self.link("page", "1".to_owned()),
+                    prev: has_prev.then(|| self.link("page", (page - 1).to_string())),
+                    next: has_next.then(|| self.link("page", (page + 1).to_string())),
+                    last: Some(self.link("page", pages.to_string())),
+                };
+                (Some(page), Some(pages as u32), links)
+            }
+            ref position => {
+                let this = match position {
+                    Position::After(key) => self.cursor(false, key.clone()),
+                    Position::Before(key) => self.cursor(true, key.clone()),
+                    _ => FIRST_CURSOR.to_owned(),
+                };
+                // 这一页为空时没有边界，回到第一页
+                let edge = |item: Option<&T>, before: bool| match item {
+                    Some(item) => self.cursor(before, self.key(item)),
+                    None => FIRST_CURSOR.to_owned(),
+                };
+                let links = Links {
+                    this: self.link("cursor", this),
+                    first: self.link("cursor", FIRST_CURSOR.to_owned()),
+                    prev: has_prev.then(|| self.link("cursor", edge(items.first(), true))),
+                    next: has_next.then(|| self.link("cursor", edge(items.last(), false))),
+                    last: None,
+                };
+                (None, None, links)
+            }
+        };
+
+        Page {
+            items,
+            total: total as u64,
+            limit: self.limit,
+            page,
+            pages,
+            links,
+        }
+    }
+
+    /// 一条的排序值，顺序和 `sort` 相同
+    fn key(&self, item: &T) -> Vec<SortValue> {
+        self.sort.iter().map(|k| item.sort_value(k.field)).collect()
+    }
+
+    fn cursor(&self, before: bool, key: Vec<SortValue>) -> String {
+        Cursor {
+            query: query_digest(&self.sort, &self.filters),
+            before,
+            key,
+        }
+        .encode()
+    }
+
+    fn link(&self, name: &str, value: String) -> String {
+        let mut params = self.params.clone();
+        params.push((name.to_owned(), value));
+        format!("{}?{}", self.path, serde_urlencoded::to_string(params).unwrap_or_default())
+    }
+}
+
+impl<T: Listable> FromRequest for ListQuery<T> {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        future::ready(Self::parse(req.path(), req.query_string()).map_err(|e| e.with_request_id(req).into()))
+    }
+}
+
+/// 在 OpenAPI 文档中按资源的白名单列出参数
+impl<T: Listable> IntoParams for ListQuery<T> {
+    fn into_params(_: impl Fn() -> Option<ParameterIn>) -> Vec<Parameter> {
+        let param = |name: &str, schema_type: Type, description: String| {
+            ParameterBuilder::new()
+                .name(name)
+                .parameter_in(ParameterIn::Query)
+                .required(Required::False)
+                .description(Some(description))
+                .schema(Some(ObjectBuilder::new().schema_type(SchemaType::new(schema_type))))
+                .build()
+        };
+
+        let mut params = vec![
+            param("page", Type::Integer, "页码，从 1 开始".to_owned()),
+            param("limit", Type::Integer, format!("每页条数，默认 {}，最大 {}", T::DEFAULT_LIMIT, T::MAX_LIMIT)),
+            param(
+                "cursor",
+                Type::String,
+                "`first` 或上一页 `links` 中的游标，不能和 `page` 同时使用".to_owned(),
+            ),
+            param(
+                "sort",
+                Type::String,
+                format!("逗号分隔，`-` 开头表示降序，可选 {}", T::SORT_FIELDS.join(", ")),
+            ),
+        ];
+        params.extend(
+            T::FILTER_FIELDS
+                .iter()
+                .map(|field| param(&format!("filter[{field}]"), Type::String, format!("按 {field} 过滤"))),
+        );
+        params
+    }
+}
+
+/// 列表响应
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// 过滤后的总数
+    pub total: u64,
+    pub limit: u32,
+    /// 使用游标时没有页码
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pages: Option<u32>,
+    pub links: Links,
+}
+
+/// 带上原来的过滤、排序和条数参数的相对链接
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Links {
+    #[serde(rename = "self")]
+    #[schema(rename = "self")]
+    pub this: String,
+    pub first: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prev: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+    /// 使用游标时没有最后一页
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last: Option<String>,
+}
+
+fn allowed(fields: &'static [&'static str], field: &str) -> Option<&'static str> {
+    fields.iter().copied().find(|f| *f == field)
+}
+
+/// 解析失败时返回不允许的字段
+fn parse_sort(fields: &'static [&'static str], sort: &str) -> Result<Vec<SortKey>, String> {
+    let mut keys: Vec<SortKey> = Vec::new();
+    for item in sort.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let (name, descending) = match item.strip_prefix('-') {
+            Some(name) => (name, true),
+            None => (item, false),
+        };
+        let field = allowed(fields, name).ok_or_else(|| name.to_owned())?;
+        if keys.iter().all(|k| k.field != field) {
+            keys.push(SortKey { field, descending });
+        }
+    }
+    Ok(keys)
+}
+
+/// 游标分页第一页的游标
+const FIRST_CURSOR: &str = "first";
+
+/// 游标对客户端不透明，目前是 base64url 编码的 JSON
+#[derive(Serialize, Deserialize)]
+struct Cursor {
+    /// 签发时的排序和过滤条件摘要，条件变了游标就不能再用
+    #[serde(rename = "q")]
+    query: String,
+    /// 边界之前的一页，否则是之后的一页
+    #[serde(rename = "b", default, skip_serializing_if = "std::ops::Not::not")]
+    before: bool,
+    /// 边界那一条的排序值
+    #[serde(rename = "k")]
+    key: Vec<SortValue>,
+}
+
+impl Cursor {
+    fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    fn decode(cursor: &str) -> Option<Self> {
+        serde_json::from_slice(&URL_SAFE_NO_PAD.decode(cursor).ok()?).ok()
+    }
+}
+
+/// 排序和过滤条件的摘要，同一个字段的过滤值不区分顺序
+fn query_digest(sort: &[SortKey], filters: &[(&'static str, String)]) -> String {
+    let mut filters: Vec<_> = filters.iter().collect();
+    filters.sort();
+    let mut hasher = Sha256::new();
+    for key in sort {
+        hasher.update(format!("sort:{}{}\n", if key.descending { "-" } else { "" }, key.field));
+    }
+    for (field, value) in filters {
+        hasher.update(format!("filter:{field}={value}\n"));
+    }
+    hasher.finalize()[..8].iter().map(|b| format!("{b:02x}")).collect()
+}
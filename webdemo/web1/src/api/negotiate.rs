@@ -0,0 +1,247 @@
+use std::ops::{Deref, DerefMut};
+
+use actix_web::{
+    body::BoxBody,
+    dev::Payload,
+    error::ResponseError,
+    http::{header, StatusCode},
+    web::BytesMut,
+    Error, FromRequest, HttpMessage, HttpRequest, HttpResponse, Responder,
+};
+use futures_util::{future::LocalBoxFuture, StreamExt as _};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::ApiError;
+
+/// 支持的格式，没有 `Accept` 或 `Accept: */*` 时使用 JSON
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    MessagePack,
+    Cbor,
+    Yaml,
+}
+
+impl Format {
+    pub const ALL: [Format; 4] = [Format::Json, Format::MessagePack, Format::Cbor, Format::Yaml];
+
+    /// 响应使用的 `Content-Type`
+    pub fn media_type(self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::MessagePack => "application/msgpack",
+            Format::Cbor => "application/cbor",
+            Format::Yaml => "application/yaml",
+        }
+    }
+
+    /// 媒体类型（不含参数）对应的格式，也接受常见的别名
+    pub fn from_media_type(media_type: &str) -> Option<Format> {
+        match media_type.to_ascii_lowercase().as_str() {
+            "application/json" => Some(Format::Json),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => Some(Format::MessagePack),
+            "application/cbor" => Some(Format::Cbor),
+            "application/yaml" | "application/x-yaml" | "text/yaml" => Some(Format::Yaml),
+            _ => None,
+        }
+    }
+
+    /// 按 `Accept` 的 q 值选择响应格式，没有可用的格式时返回 406。
+    /// 多个 `Accept` 头按出现顺序合并成一个列表。
+    pub fn from_accept(req: &HttpRequest) -> Result<Format, ApiError> {
+        if !req.headers().contains_key(header::ACCEPT) {
+            return Ok(Format::Json);
+        }
+
+        let ranges: Vec<(&str, f32)> = req
+            .headers()
+            .get_all(header::ACCEPT)
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|accept| accept.split(','))
+            .filter_map(|item| {
+                let mut parts = item.split(';').map(str::trim);
+                let range = parts.next().filter(|r| !r.is_empty())?;
+                let q = parts
+                    .filter_map(|p| p.strip_prefix("q="))
+                    .find_map(|q| q.parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                Some((range, q))
+            })
+            .collect();
+        // q=0 明确拒绝的格式，通配符不能选中
+        let rejected: Vec<_> = ranges
+            .iter()
+            .filter(|(_, q)| *q <= 0.0)
+            .filter_map(|(range, _)| Format::from_media_type(range))
+            .collect();
+        let mut ranges: Vec<_> = ranges.into_iter().filter(|(_, q)| *q > 0.0).collect();
+        // 稳定排序，q 相同时保持客户端给出的顺序
+        ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let wildcard = |prefix: &str| {
+            Format::ALL
+                .into_iter()
+                .find(|f| f.media_type().starts_with(prefix) && !rejected.contains(f))
+        };
+        ranges
+            .into_iter()
+            .find_map(|(range, _)| match range {
+                "*/*" => wildcard(""),
+                "application/*" => wildcard("application/"),
+                // text/yaml 是 YAML 的别名
+                "text/*" => Some(Format::Yaml).filter(|f| !rejected.contains(f)),
+                range => Format::from_media_type(range).filter(|f| !rejected.contains(f)),
+            })
+            .ok_or_else(|| {
+                let supported: Vec<_> = Format::ALL.iter().map(|f| f.media_type()).collect();
+                ApiError::new(
+                    StatusCode::NOT_ACCEPTABLE,
+                    "not_acceptable",
+                    format!("supported media types: {}", supported.join(", ")),
+                )
+            })
+    }
+
+    /// 按 `Content-Type` 选择请求体格式，不支持时返回 415
+    pub fn from_content_type(req: &HttpRequest) -> Result<Format, ApiError> {
+        Format::from_media_type(req.content_type()).ok_or_else(|| {
+            let supported: Vec<_> = Format::ALL.iter().map(|f| f.media_type()).collect();
+            ApiError::new(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "unsupported_media_type",
+                format!("expected content-type {}", supported.join(", ")),
+            )
+        })
+    }
+
+    pub fn serialize<T: Serialize>(self, value: &T) -> Result<Vec<u8>, String> {
+        match self {
+            Format::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+            Format::MessagePack => rmp_serde::to_vec_named(value).map_err(|e| e.to_string()),
+            Format::Cbor => {
+                let mut buf = Vec::new();
+                ciborium::into_writer(value, &mut buf).map_err(|e| e.to_string())?;
+                Ok(buf)
+            }
+            Format::Yaml => serde_yaml::to_string(value).map(String::into_bytes).map_err(|e| e.to_string()),
+        }
+    }
+
+    pub fn deserialize<T: DeserializeOwned>(self, body: &[u8]) -> Result<T, String> {
+        match self {
+            Format::Json => serde_json::from_slice(body).map_err(|e| e.to_string()),
+            Format::MessagePack => rmp_serde::from_slice(body).map_err(|e| e.to_string()),
+            Format::Cbor => ciborium::from_reader(body).map_err(|e| e.to_string()),
+            Format::Yaml => serde_yaml::from_slice(body).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+/// 请求体大小上限，没有配置时使用 `LimitsConfig::json_bytes` 的默认值
+#[derive(Debug, Clone, Copy)]
+pub struct NegotiatedConfig {
+    pub limit: usize,
+}
+
+impl Default for NegotiatedConfig {
+    fn default() -> Self {
+        Self { limit: 2 * 1024 * 1024 }
+    }
+}
+
+/// 按 `Accept` / `Content-Type` 协商格式的 `web::Json` 替代：
+/// 作为返回值时按 `Accept` 编码，作为参数时按 `Content-Type` 解码。
+/// 支持 JSON、MessagePack、CBOR 和 YAML，错误仍然是 JSON 格式的 `ApiError`。
+pub struct Negotiated<T>(pub T);
+
+impl<T> Negotiated<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Negotiated<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Negotiated<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: Serialize> Responder for Negotiated<T> {
+    type Body = BoxBody;
+
+    fn respond_to(self, req: &HttpRequest) -> HttpResponse {
+        let format = match Format::from_accept(req) {
+            Ok(format) => format,
+            Err(e) => return e.with_request_id(req).error_response(),
+        };
+        match format.serialize(&self.0) {
+            Ok(body) => HttpResponse::Ok()
+                .content_type(format.media_type())
+                .insert_header((header::VARY, "accept"))
+                .body(body),
+            Err(e) => {
+                log::error!("failed to encode {}: {e}", format.media_type());
+                ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "internal", "failed to encode response")
+                    .with_request_id(req)
+                    .error_response()
+            }
+        }
+    }
+}
+
+impl<T: DeserializeOwned + 'static> FromRequest for Negotiated<T> {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        let mut payload = payload.take();
+        let limit = req
+            .app_data::<NegotiatedConfig>()
+            .copied()
+            .unwrap_or_default()
+            .limit;
+
+        Box::pin(async move {
+            let format = Format::from_content_type(&req).map_err(|e| e.with_request_id(&req))?;
+
+            let too_large = || {
+                ApiError::new(
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    "payload_too_large",
+                    format!("payload is larger than allowed (limit: {limit} bytes)"),
+                )
+                .with_request_id(&req)
+            };
+            let length = req
+                .headers()
+                .get(header::CONTENT_LENGTH)
+                .and_then(|v| v.to_str().ok()?.parse::<usize>().ok());
+            if length.is_some_and(|length| length > limit) {
+                return Err(too_large().into());
+            }
+
+            let mut body = BytesMut::new();
+            while let Some(chunk) = payload.next().await {
+                let chunk = chunk.map_err(|e| ApiError::bad_request(e.to_string()).with_request_id(&req))?;
+                if body.len() + chunk.len() > limit {
+                    return Err(too_large().into());
+                }
+                body.extend_from_slice(&chunk);
+            }
+
+            format
+                .deserialize(&body)
+                .map(Negotiated)
+                .map_err(|e| ApiError::deserialize("invalid_body", e).with_request_id(&req).into())
+        })
+    }
+}
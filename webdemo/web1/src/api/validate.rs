@@ -0,0 +1,116 @@
+use std::ops::Deref;
+
+use actix_web::{dev::Payload, http::StatusCode, Error, FromRequest, HttpRequest};
+use futures_util::future::LocalBoxFuture;
+use validator::{Validate, ValidationError, ValidationErrors, ValidationErrorsKind};
+
+use super::{error::FieldError, ApiError};
+
+/// 提取后再校验的包装，`E` 可以是 `web::Path`、`web::Query`、`web::Json` 或 `web::Form`：
+/// `async fn handler(param: Valid<web::Path<Param>>)`，`Param` 用 `#[derive(Validate)]` 声明规则。
+/// 校验失败时返回 422，`details` 中列出所有违反的规则。
+pub struct Valid<E>(pub E);
+
+impl<E> Valid<E> {
+    pub fn into_inner(self) -> E {
+        self.0
+    }
+}
+
+impl<E: Deref> Deref for Valid<E> {
+    type Target = E::Target;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<E> FromRequest for Valid<E>
+where
+    E: FromRequest + Deref + 'static,
+    E::Target: Validate,
+{
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        let extract = E::from_request(&req, payload);
+        Box::pin(async move {
+            let value = extract.await.map_err(Into::into)?;
+            match value.validate() {
+                Ok(()) => Ok(Valid(value)),
+                Err(errors) => Err(ApiError::from(errors).with_request_id(&req).into()),
+            }
+        })
+    }
+}
+
+impl From<ValidationErrors> for ApiError {
+    fn from(errors: ValidationErrors) -> Self {
+        let mut details = Vec::new();
+        flatten("", &errors, &mut details);
+
+        let mut error = ApiError::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "validation_failed",
+            format!("{} validation errors", details.len()),
+        );
+        error.details = details;
+        error
+    }
+}
+
+/// 嵌套结构用 `a.b`，列表用 `a[0]` 表示字段路径，按字段名排序
+fn flatten(prefix: &str, errors: &ValidationErrors, out: &mut Vec<FieldError>) {
+    let mut fields: Vec<_> = errors.errors().iter().collect();
+    fields.sort_by(|a, b| a.0.cmp(b.0));
+
+    for (field, kind) in fields {
+        let path = match prefix {
+            "" => field.to_string(),
+            prefix => format!("{prefix}.{field}"),
+        };
+        match kind {
+            ValidationErrorsKind::Field(errors) => out.extend(errors.iter().map(|e| FieldError {
+                field: path.clone(),
+                message: describe(e),
+            })),
+            ValidationErrorsKind::Struct(errors) => flatten(&path, errors, out),
+            ValidationErrorsKind::List(items) => {
+                for (i, errors) in items {
+                    flatten(&format!("{path}[{i}]"), errors, out);
+                }
+            }
+        }
+    }
+}
+
+/// 自定义消息，或者规则名加参数，例如 `length (max=32, min=1)`
+fn describe(error: &ValidationError) -> String {
+    if let Some(message) = &error.message {
+        return message.to_string();
+    }
+
+    let mut params: Vec<_> = error
+        .params
+        .iter()
+        .filter(|(name, _)| *name != "value")
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect();
+    params.sort();
+    match params.is_empty() {
+        true => error.code.to_string(),
+        false => format!("{} ({})", error.code, params.join(", ")),
+    }
+}
+
+/// 枚举值校验，在 `#[validate(custom(function = ...))]` 的函数中使用
+pub fn one_of(value: &str, allowed: &'static [&'static str]) -> Result<(), ValidationError> {
+    if allowed.contains(&value) {
+        return Ok(());
+    }
+    let mut error = ValidationError::new("one_of");
+    error.add_param("allowed".into(), &allowed);
+    Err(error)
+}
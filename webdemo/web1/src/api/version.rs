@@ -0,0 +1,354 @@
+use std::{
+    collections::HashMap,
+    fmt, io,
+    rc::Rc,
+    str::FromStr,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::{
+        header::{self, HeaderName, HeaderValue, HttpDate},
+        uri::{PathAndQuery, Uri},
+        StatusCode,
+    },
+    web, Error, Responder,
+};
+use futures_util::future::{self, LocalBoxFuture, Ready};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::{apiparam, files, negotiate::Negotiated, ApiError};
+use crate::config::ApiConfig;
+
+/// 请求和响应中的版本头
+pub const API_VERSION: HeaderName = HeaderName::from_static("api-version");
+
+/// `/api` 的版本，每个版本有自己的一组处理函数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ApiVersion {
+    V1,
+    V2,
+}
+
+impl ApiVersion {
+    pub const ALL: [ApiVersion; 2] = [ApiVersion::V1, ApiVersion::V2];
+    pub const LATEST: ApiVersion = ApiVersion::V2;
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "v1",
+            ApiVersion::V2 => "v2",
+        }
+    }
+
+    /// 挂在 `/api/{version}` 下的路由
+    pub fn configure(self, cfg: &mut web::ServiceConfig) {
+        match self {
+            ApiVersion::V1 => apiparam::v1(cfg),
+            ApiVersion::V2 => apiparam::v2(cfg),
+        }
+        // 新增的资源在所有版本中相同
+        files::configure(cfg);
+    }
+}
+
+impl fmt::Display for ApiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// `v2` 或 `2`
+impl FromStr for ApiVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let number = s.strip_prefix(['v', 'V']).unwrap_or(s);
+        ApiVersion::ALL
+            .into_iter()
+            .find(|v| &v.as_str()[1..] == number)
+            .ok_or_else(|| {
+                let supported: Vec<_> = ApiVersion::ALL.iter().map(|v| v.as_str()).collect();
+                format!("unsupported API version {s:?}, supported: {}", supported.join(", "))
+            })
+    }
+}
+
+/// 已弃用版本的响应头
+struct Deprecation {
+    deprecated_at: HttpDate,
+    sunset: Option<HttpDate>,
+    link: Option<String>,
+}
+
+/// 从配置解析的版本设置，所有 worker 共享
+pub struct Versions {
+    default: ApiVersion,
+    deprecations: HashMap<ApiVersion, Deprecation>,
+}
+
+impl Versions {
+    /// 配置已经在加载时校验过，这里的错误只是保险
+    pub fn new(config: &ApiConfig) -> io::Result<Self> {
+        let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidInput, e);
+        let date = |s: &str| HttpDate::from_str(s).map_err(|_| invalid(format!("invalid HTTP date {s:?}")));
+
+        let mut deprecations = HashMap::new();
+        for (version, deprecation) in &config.deprecations {
+            let version = version.parse().map_err(invalid)?;
+            deprecations.insert(
+                version,
+                Deprecation {
+                    deprecated_at: date(&deprecation.deprecated_at)?,
+                    sunset: deprecation.sunset.as_deref().map(date).transpose()?,
+                    link: deprecation.link.clone(),
+                },
+            );
+        }
+        Ok(Self {
+            default: config.default_version.parse().map_err(invalid)?,
+            deprecations,
+        })
+    }
+
+    /// 过了 `Sunset` 时间的版本返回 410
+    fn is_sunset(&self, version: ApiVersion) -> bool {
+        let sunset = self.deprecations.get(&version).and_then(|d| d.sunset);
+        sunset.is_some_and(|sunset| SystemTime::from(sunset) <= SystemTime::now())
+    }
+
+    /// `Api-Version`，弃用的版本加上 `Deprecation`（RFC 9745）、`Sunset`（RFC 8594）和 `Link`
+    fn add_headers(&self, version: ApiVersion, headers: &mut header::HeaderMap) {
+        headers.insert(API_VERSION, HeaderValue::from_static(version.as_str()));
+        let Some(deprecation) = self.deprecations.get(&version) else {
+            return;
+        };
+
+        let deprecated_at = SystemTime::from(deprecation.deprecated_at)
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        headers.insert(HeaderName::from_static("deprecation"), format!("@{deprecated_at}").parse().unwrap());
+        if let Some(sunset) = deprecation.sunset {
+            headers.insert(HeaderName::from_static("sunset"), sunset.to_string().parse().unwrap());
+        }
+        let successor = format!("</api/{}>; rel=\"successor-version\"", ApiVersion::LATEST);
+        headers.append(header::LINK, successor.parse().unwrap());
+        if let Some(link) = deprecation.link.as_deref().and_then(|l| format!("<{l}>; rel=\"deprecation\"").parse().ok()) {
+            headers.append(header::LINK, link);
+        }
+    }
+}
+
+/// 版本选择中间件，挂在 `App` 上，在路由之前执行：
+/// - `/api/v2/...` 直接使用 URL 中的版本
+/// - 其他 `/api/...` 按 `Api-Version: 2` 请求头或 `Accept: application/json; version=2` 选择版本，
+///   都没有时使用 `api.default_version`，然后改写成 `/api/v2/...` 再路由
+///
+/// 授权策略和访问日志看到的都是改写后的路径。
+pub struct Versioning(pub Arc<Versions>);
+
+impl<S, B> Transform<S, ServiceRequest> for Versioning
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = VersioningMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        future::ok(VersioningMiddleware {
+            service: Rc::new(service),
+            versions: self.0.clone(),
+        })
+    }
+}
+
+pub struct VersioningMiddleware<S> {
+    service: Rc<S>,
+    versions: Arc<Versions>,
+}
+
+impl<S, B> Service<ServiceRequest> for VersioningMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        // `/api` 本身是版本列表
+        let Some(rest) = req.path().strip_prefix("/api/").map(str::to_owned) else {
+            return Box::pin(async move { service.call(req).await.map(ServiceResponse::map_into_left_body) });
+        };
+
+        let selected = select(&req, &rest).map(|(version, from_url)| (version.unwrap_or(self.versions.default), from_url));
+        let (version, from_url) = match selected {
+            Ok(selected) => selected,
+            Err(e) => {
+                let e = e.with_request_id(req.request());
+                return Box::pin(future::ok(req.error_response(e).map_into_right_body()));
+            }
+        };
+        if self.versions.is_sunset(version) {
+            let e = ApiError::new(StatusCode::GONE, "version_sunset", format!("API {version} is no longer available"))
+                .with_request_id(req.request());
+            let mut res = req.error_response(e);
+            self.versions.add_headers(version, res.headers_mut());
+            return Box::pin(future::ok(res.map_into_right_body()));
+        }
+        if !from_url {
+            rewrite(&mut req, &format!("/api/{version}/{rest}"));
+        }
+
+        let versions = self.versions.clone();
+        Box::pin(async move {
+            let mut res = service.call(req).await?;
+            let headers = res.headers_mut();
+            versions.add_headers(version, headers);
+            if !from_url {
+                add_vary(headers, &["api-version", "accept"]);
+            }
+            Ok(res.map_into_left_body())
+        })
+    }
+}
+
+/// URL 中的版本和请求头中的版本，第二项表示是否来自 URL。
+/// 多处指定了不同的版本时返回 400。
+fn select(req: &ServiceRequest, rest: &str) -> Result<(Option<ApiVersion>, bool), ApiError> {
+    let unsupported = |message: String| ApiError::new(StatusCode::BAD_REQUEST, "unsupported_version", message);
+
+    let segment = rest.split('/').next().unwrap_or_default();
+    let url = match segment.strip_prefix('v') {
+        Some(n) if !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()) => {
+            Some(segment.parse::<ApiVersion>().map_err(|_| {
+                ApiError::new(StatusCode::NOT_FOUND, "unsupported_version", format!("API {segment} does not exist"))
+            })?)
+        }
+        _ => None,
+    };
+
+    let header = req
+        .headers()
+        .get(API_VERSION)
+        .map(|v| v.to_str().unwrap_or_default().parse::<ApiVersion>())
+        .transpose()
+        .map_err(unsupported)?;
+    let accept = req
+        .headers()
+        .get_all(header::ACCEPT)
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .flat_map(|range| range.split(';').skip(1))
+        .filter_map(|param| param.trim().strip_prefix("version="))
+        .map(|v| v.trim_matches('"').parse::<ApiVersion>())
+        .next()
+        .transpose()
+        .map_err(unsupported)?;
+
+    let mut requested = [url, header, accept].into_iter().flatten();
+    let version = requested.next();
+    if let Some(other) = requested.find(|v| Some(*v) != version) {
+        return Err(ApiError::bad_request(format!(
+            "conflicting API versions {} and {other}",
+            version.unwrap()
+        )));
+    }
+    Ok((version, url.is_some()))
+}
+
+/// 按请求头选择的版本，缓存需要区分这些请求头；已经有的不重复添加
+fn add_vary(headers: &mut header::HeaderMap, names: &[&'static str]) {
+    let existing: Vec<String> = headers
+        .get_all(header::VARY)
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|v| v.trim().to_ascii_lowercase())
+        .collect();
+    for name in names.iter().filter(|name| !existing.iter().any(|e| e == *name || e == "*")) {
+        headers.append(header::VARY, HeaderValue::from_static(name));
+    }
+}
+
+/// 和 `NormalizePath` 一样改写路径，保留查询字符串
+fn rewrite(req: &mut ServiceRequest, path: &str) {
+    let mut parts = req.head().uri.clone().into_parts();
+    let path_and_query = match parts.path_and_query.as_ref().and_then(PathAndQuery::query) {
+        Some(query) => format!("{path}?{query}"),
+        None => path.to_owned(),
+    };
+    let Ok(path_and_query) = PathAndQuery::from_str(&path_and_query) else {
+        return;
+    };
+    parts.path_and_query = Some(path_and_query);
+    if let Ok(uri) = Uri::from_parts(parts) {
+        req.match_info_mut().get_mut().update(&uri);
+        req.head_mut().uri = uri;
+    }
+}
+
+/// 版本列表
+#[derive(Serialize, ToSchema)]
+pub struct VersionList {
+    /// 没有指定版本时使用的版本
+    default: &'static str,
+    versions: Vec<VersionInfo>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct VersionInfo {
+    version: &'static str,
+    url: String,
+    /// `current`、`supported`、`deprecated` 或 `sunset`
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deprecated_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sunset: Option<String>,
+    /// 迁移说明
+    #[serde(skip_serializing_if = "Option::is_none")]
+    link: Option<String>,
+}
+
+/// 支持的 API 版本，不需要认证
+#[utoipa::path(get, path = "/api", responses((status = 200, body = VersionList)), tag = "api")]
+pub async fn list(versions: web::Data<Versions>) -> impl Responder {
+    let versions = versions.into_inner();
+    Negotiated(VersionList {
+        default: versions.default.as_str(),
+        versions: ApiVersion::ALL
+            .into_iter()
+            .map(|version| {
+                let deprecation = versions.deprecations.get(&version);
+                let status = match deprecation {
+                    _ if versions.is_sunset(version) => "sunset",
+                    Some(_) => "deprecated",
+                    None if version == ApiVersion::LATEST => "current",
+                    None => "supported",
+                };
+                VersionInfo {
+                    version: version.as_str(),
+                    url: format!("/api/{version}"),
+                    status,
+                    deprecated_at: deprecation.map(|d| d.deprecated_at.to_string()),
+                    sunset: deprecation.and_then(|d| d.sunset).map(|s| s.to_string()),
+                    link: deprecation.and_then(|d| d.link.clone()),
+                }
+            })
+            .collect(),
+    })
+}
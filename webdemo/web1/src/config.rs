@@ -0,0 +1,404 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use actix_web::{
+    cookie::Cookie,
+    http::header::{HeaderName, HeaderValue, HttpDate},
+};
+use serde::{Deserialize, Serialize};
+use webdemo_common::{AppConfig, LogConfig, ServerConfig, TlsConfig};
+
+use crate::api::version::ApiVersion;
+
+/// web1 配置，按默认值、配置文件、环境变量、命令行参数的顺序覆盖。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub log: LogConfig,
+    pub cors: CorsConfig,
+    pub security_headers: SecurityHeadersConfig,
+    pub csrf: CsrfConfig,
+    pub limits: LimitsConfig,
+    pub auth: AuthConfig,
+    pub features: FeaturesConfig,
+    pub health: HealthConfig,
+    pub api: ApiConfig,
+    pub files: FilesConfig,
+    /// `url_for` 使用的外部资源，名称到 URL
+    pub external_resources: BTreeMap<String, String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            server: ServerConfig {
+                workers: 4,
+                tls: TlsConfig {
+                    enabled: false,
+                    bind: vec!["0.0.0.0:48443".to_owned()],
+                    cert: PathBuf::from("./web1/cert.pem"),
+                    key: PathBuf::from("./web1/key.pem"),
+                },
+                ..ServerConfig::new("0.0.0.0:48080")
+            },
+            log: LogConfig::default(),
+            cors: CorsConfig::default(),
+            security_headers: SecurityHeadersConfig::default(),
+            csrf: CsrfConfig::default(),
+            limits: LimitsConfig::default(),
+            auth: AuthConfig::default(),
+            features: FeaturesConfig::default(),
+            health: HealthConfig::default(),
+            api: ApiConfig::default(),
+            files: FilesConfig::default(),
+            external_resources: BTreeMap::from([("baidu".to_owned(), "https://baidu.com".to_owned())]),
+        }
+    }
+}
+
+/// 跨域设置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    pub enabled: bool,
+    /// 允许的来源，`*` 允许所有来源（不能和 `allow_credentials` 同时使用）
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    /// 浏览器脚本可以读取的响应头
+    pub expose_headers: Vec<String>,
+    pub allow_credentials: bool,
+    /// 预检请求的缓存时间
+    pub max_age_secs: Option<usize>,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            allowed_origins: Vec::new(),
+            allowed_methods: ["GET", "POST", "PUT", "DELETE"].map(str::to_owned).to_vec(),
+            allowed_headers: ["authorization", "content-type", "x-csrf-token", "api-version"].map(str::to_owned).to_vec(),
+            expose_headers: Vec::new(),
+            allow_credentials: false,
+            max_age_secs: Some(3600),
+        }
+    }
+}
+
+/// 安全响应头，`None` 不发送。`X-Content-Type-Options: nosniff` 总是发送。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SecurityHeadersConfig {
+    pub enabled: bool,
+    /// `Strict-Transport-Security` 的 max-age，只在 HTTPS 响应中发送
+    pub hsts_max_age_secs: Option<u64>,
+    pub hsts_include_subdomains: bool,
+    pub content_security_policy: Option<String>,
+    /// `X-Frame-Options`，`DENY` 或 `SAMEORIGIN`
+    pub frame_options: Option<String>,
+    pub referrer_policy: Option<String>,
+}
+
+impl Default for SecurityHeadersConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            hsts_max_age_secs: Some(31_536_000),
+            hsts_include_subdomains: true,
+            // swagger-ui 使用内联样式和 data: 图片
+            content_security_policy: Some(
+                "default-src 'self'; img-src 'self' data:; style-src 'self' 'unsafe-inline'; frame-ancestors 'none'"
+                    .to_owned(),
+            ),
+            frame_options: Some("DENY".to_owned()),
+            referrer_policy: Some("no-referrer".to_owned()),
+        }
+    }
+}
+
+/// double-submit cookie CSRF 保护，检查非 GET 的表单请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CsrfConfig {
+    pub enabled: bool,
+    pub cookie_name: String,
+    /// 脚本提交时放 token 的请求头
+    pub header_name: String,
+    /// urlencoded 表单中放 token 的字段
+    pub field_name: String,
+}
+
+impl Default for CsrfConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            cookie_name: "csrf_token".to_owned(),
+            header_name: "x-csrf-token".to_owned(),
+            field_name: "csrf_token".to_owned(),
+        }
+    }
+}
+
+/// 请求体大小上限（字节）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    pub json_bytes: usize,
+    pub form_bytes: usize,
+    pub payload_bytes: usize,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            json_bytes: 2 * 1024 * 1024,
+            form_bytes: 16 * 1024,
+            payload_bytes: 256 * 1024,
+        }
+    }
+}
+
+/// `/api` 认证和授权
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// token 文件，见 keys.example.json
+    pub keys_file: Option<PathBuf>,
+    /// HS256 密钥
+    pub jwt_secret: Option<String>,
+    /// RS256 公钥 PEM 文件
+    pub jwt_public_key: Option<PathBuf>,
+    pub jwt_issuer: Option<String>,
+    pub jwt_audience: Option<String>,
+    /// 角色策略文件，见 policy.example.json
+    pub policy_file: Option<PathBuf>,
+}
+
+/// 功能开关
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeaturesConfig {
+    /// `/openapi.json` 和 `/swagger-ui/`
+    pub swagger_ui: bool,
+    /// 每个请求完成时输出一行日志
+    pub access_log: bool,
+}
+
+impl Default for FeaturesConfig {
+    fn default() -> Self {
+        Self {
+            swagger_ui: true,
+            access_log: true,
+        }
+    }
+}
+
+/// `/readyz` 的依赖检查
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HealthConfig {
+    /// 上游名称到 URL，返回 2xx 才算就绪
+    pub upstreams: BTreeMap<String, String>,
+    /// 单个检查的超时
+    pub timeout_ms: u64,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            upstreams: BTreeMap::new(),
+            timeout_ms: 2000,
+        }
+    }
+}
+
+/// `/api` 的版本选择和弃用
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiConfig {
+    /// URL、`Api-Version` 请求头和 `Accept` 的 version 参数都没有指定版本时使用
+    pub default_version: String,
+    /// 版本（`v1`）到弃用信息，这些版本的响应带 `Deprecation` 和 `Sunset`
+    pub deprecations: BTreeMap<String, DeprecationConfig>,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            // 没有版本号的旧客户端继续使用 v1
+            default_version: "v1".to_owned(),
+            deprecations: BTreeMap::new(),
+        }
+    }
+}
+
+/// 时间都是 HTTP 日期格式，例如 `Thu, 01 Jan 2026 00:00:00 GMT`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeprecationConfig {
+    /// 弃用时间
+    pub deprecated_at: String,
+    /// 之后返回 410
+    pub sunset: Option<String>,
+    /// 迁移说明的 URL，放在 `Link: <...>; rel="deprecation"` 中
+    pub link: Option<String>,
+}
+
+/// `/api/files` 上传的文件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilesConfig {
+    /// 文件和元数据所在的目录，不存在时创建
+    pub dir: PathBuf,
+    /// 单个文件的上限（字节）
+    pub max_file_bytes: u64,
+    /// 一次请求中所有文件的总大小上限（字节）。
+    /// 只限制单次上传，不是存储目录的总配额，已经保存的文件不计入
+    pub max_total_bytes: u64,
+    /// 一次请求中的文件个数上限
+    pub max_files: usize,
+    /// 按内容识别的类型，`image/*` 匹配所有图片，为空时不限制
+    pub allowed_types: Vec<String>,
+}
+
+impl Default for FilesConfig {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("./web1/files"),
+            max_file_bytes: 10 * 1024 * 1024,
+            max_total_bytes: 50 * 1024 * 1024,
+            max_files: 10,
+            allowed_types: Vec::new(),
+        }
+    }
+}
+
+impl AppConfig for Config {
+    fn server(&self) -> &ServerConfig {
+        &self.server
+    }
+
+    fn log(&self) -> &LogConfig {
+        &self.log
+    }
+
+    fn validate(&self, errors: &mut Vec<String>) {
+        if self.cors.enabled {
+            if self.cors.allowed_origins.is_empty() {
+                errors.push("cors.allowed_origins: required when cors is enabled".to_owned());
+            }
+            for origin in &self.cors.allowed_origins {
+                if origin != "*" && !valid_origin(origin) {
+                    errors.push(format!("cors.allowed_origins: {origin} is not an origin like https://example.com"));
+                }
+            }
+            if self.cors.allow_credentials && self.cors.allowed_origins.iter().any(|o| o == "*") {
+                errors.push("cors: allow_credentials can not be used with origin *".to_owned());
+            }
+            for method in &self.cors.allowed_methods {
+                if actix_web::http::Method::from_bytes(method.as_bytes()).is_err() {
+                    errors.push(format!("cors.allowed_methods: invalid method {method}"));
+                }
+            }
+        }
+
+        let headers = &self.security_headers;
+        for (name, value) in [
+            ("security_headers.content_security_policy", &headers.content_security_policy),
+            ("security_headers.frame_options", &headers.frame_options),
+            ("security_headers.referrer_policy", &headers.referrer_policy),
+        ] {
+            if let Some(value) = value.as_deref().filter(|v| v.is_empty() || HeaderValue::from_str(v).is_err()) {
+                errors.push(format!("{name}: invalid header value {value:?}"));
+            }
+        }
+        if let Some(value) = headers.frame_options.as_deref() {
+            if !["DENY", "SAMEORIGIN"].contains(&value.to_ascii_uppercase().as_str()) {
+                errors.push(format!("security_headers.frame_options: expected DENY or SAMEORIGIN, got {value}"));
+            }
+        }
+
+        if self.csrf.enabled {
+            if self.csrf.cookie_name.is_empty() || Cookie::parse(format!("{}=x", self.csrf.cookie_name)).is_err() {
+                errors.push(format!("csrf.cookie_name: invalid cookie name {:?}", self.csrf.cookie_name));
+            }
+            if HeaderName::from_bytes(self.csrf.header_name.as_bytes()).is_err() {
+                errors.push(format!("csrf.header_name: invalid header name {:?}", self.csrf.header_name));
+            }
+            if self.csrf.field_name.is_empty() {
+                errors.push("csrf.field_name: must not be empty".to_owned());
+            }
+        }
+
+        for (name, url) in &self.health.upstreams {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                errors.push(format!("health.upstreams.{name}: {url} is not an http(s) URL"));
+            }
+        }
+        if self.health.timeout_ms == 0 {
+            errors.push("health.timeout_ms: must be greater than 0".to_owned());
+        }
+
+        for (name, limit) in [
+            ("limits.json_bytes", self.limits.json_bytes),
+            ("limits.form_bytes", self.limits.form_bytes),
+            ("limits.payload_bytes", self.limits.payload_bytes),
+        ] {
+            if limit == 0 {
+                errors.push(format!("{name}: must be greater than 0"));
+            }
+        }
+
+        if let Err(e) = self.api.default_version.parse::<ApiVersion>() {
+            errors.push(format!("api.default_version: {e}"));
+        }
+        for (version, deprecation) in &self.api.deprecations {
+            if let Err(e) = version.parse::<ApiVersion>() {
+                errors.push(format!("api.deprecations.{version}: {e}"));
+            }
+            let dates = [("deprecated_at", Some(&deprecation.deprecated_at)), ("sunset", deprecation.sunset.as_ref())];
+            for (name, date) in dates {
+                if let Some(date) = date.filter(|d| d.parse::<HttpDate>().is_err()) {
+                    errors.push(format!("api.deprecations.{version}.{name}: {date:?} is not an HTTP date"));
+                }
+            }
+        }
+
+        for (name, limit) in [
+            ("files.max_file_bytes", self.files.max_file_bytes),
+            ("files.max_total_bytes", self.files.max_total_bytes),
+            ("files.max_files", self.files.max_files as u64),
+        ] {
+            if limit == 0 {
+                errors.push(format!("{name}: must be greater than 0"));
+            }
+        }
+        for content_type in &self.files.allowed_types {
+            if content_type.split('/').filter(|s| !s.is_empty()).count() != 2 {
+                errors.push(format!("files.allowed_types: {content_type:?} is not a type like image/png or image/*"));
+            }
+        }
+
+        if self.auth.jwt_secret.is_some() && self.auth.jwt_public_key.is_some() {
+            errors.push("auth: set only one of jwt_secret and jwt_public_key".to_owned());
+        }
+        for (name, path) in [
+            ("auth.keys_file", &self.auth.keys_file),
+            ("auth.jwt_public_key", &self.auth.jwt_public_key),
+            ("auth.policy_file", &self.auth.policy_file),
+        ] {
+            if let Some(path) = path.as_ref().filter(|p| !p.is_file()) {
+                errors.push(format!("{name}: {} does not exist", path.display()));
+            }
+        }
+    }
+}
+
+/// `scheme://host[:port]`，没有路径
+fn valid_origin(origin: &str) -> bool {
+    let Some((scheme, host)) = origin.split_once("://") else {
+        return false;
+    };
+    matches!(scheme, "http" | "https") && !host.is_empty() && !host.contains('/')
+}
@@ -0,0 +1,267 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::RwLock,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::{http::StatusCode, web::Bytes};
+use futures_util::{Stream, StreamExt as _};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use tokio::io::AsyncWriteExt as _;
+use utoipa::ToSchema;
+
+use crate::{api::ApiError, config::FilesConfig};
+
+/// 识别类型时读取的开头字节数
+const SNIFF_BYTES: usize = 512;
+
+/// 上传文件的元数据，保存在数据文件旁边的 `{id}.json` 中
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct FileMeta {
+    pub id: String,
+    /// 上传时的文件名，去掉了目录
+    pub name: String,
+    /// 按内容识别的类型，下载时使用
+    pub content_type: String,
+    /// 客户端声明的类型，只做记录
+    pub declared_type: Option<String>,
+    pub size: u64,
+    /// 十六进制的 SHA-256
+    pub sha256: String,
+    /// 上传者
+    pub owner: String,
+    /// 上传时间（Unix 秒）
+    pub created_at: u64,
+}
+
+/// 上传时客户端提供的信息
+pub struct Upload {
+    pub name: String,
+    pub declared_type: Option<String>,
+    pub owner: String,
+}
+
+/// 本地目录中的文件，所有 worker 共享。
+/// 数据文件是 `{id}`，写入时先写 `.{id}.part`，完成后再改名，启动时加载元数据并清理残留的 `.part`。
+pub struct FileStore {
+    dir: PathBuf,
+    pub max_file_bytes: u64,
+    /// 一次请求的上限，不是目录的总配额
+    pub max_total_bytes: u64,
+    pub max_files: usize,
+    allowed_types: Vec<String>,
+    index: RwLock<HashMap<String, FileMeta>>,
+}
+
+impl FileStore {
+    pub fn new(config: &FilesConfig) -> io::Result<Self> {
+        fs::create_dir_all(&config.dir)?;
+
+        let mut index = HashMap::new();
+        for entry in fs::read_dir(&config.dir)? {
+            let path = entry?.path();
+            let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            if name.starts_with('.') && name.ends_with(".part") {
+                log::warn!("removing incomplete upload {}", path.display());
+                fs::remove_file(&path)?;
+                continue;
+            }
+            if !name.ends_with(".json") {
+                continue;
+            }
+            match serde_json::from_slice::<FileMeta>(&fs::read(&path)?) {
+                Ok(meta) if config.dir.join(&meta.id).is_file() => {
+                    index.insert(meta.id.clone(), meta);
+                }
+                Ok(meta) => log::warn!("{}: data file {} is missing", path.display(), meta.id),
+                Err(e) => log::warn!("{}: {e}", path.display()),
+            }
+        }
+        log::info!("loaded {} files from {}", index.len(), config.dir.display());
+
+        Ok(Self {
+            dir: config.dir.clone(),
+            max_file_bytes: config.max_file_bytes,
+            max_total_bytes: config.max_total_bytes,
+            max_files: config.max_files,
+            allowed_types: config.allowed_types.clone(),
+            index: RwLock::new(index),
+        })
+    }
+
+    pub fn get(&self, id: &str) -> Option<FileMeta> {
+        self.index.read().unwrap().get(id).cloned()
+    }
+
+    pub fn list(&self) -> Vec<FileMeta> {
+        self.index.read().unwrap().values().cloned().collect()
+    }
+
+    /// 数据文件的路径，`id` 必须来自索引
+    pub fn path(&self, meta: &FileMeta) -> PathBuf {
+        self.dir.join(&meta.id)
+    }
+
+    /// 边接收边写入和计算摘要，超过 `limit` 字节时停止接收并删除已写入的部分。
+    /// `limit` 是单个文件的上限和本次请求剩余额度中较小的一个，超出时返回的错误由 `too_large` 生成。
+    pub async fn save<S, E>(
+        &self,
+        upload: Upload,
+        mut stream: S,
+        limit: u64,
+        too_large: impl Fn() -> ApiError,
+    ) -> Result<FileMeta, ApiError>
+    where
+        S: Stream<Item = Result<Bytes, E>> + Unpin,
+        E: Into<ApiError>,
+    {
+        let id = uuid::Uuid::new_v4().to_string();
+        let part = self.dir.join(format!(".{id}.part"));
+        let result = async {
+            let mut file = tokio::fs::File::create(&part).await.map_err(internal)?;
+            let mut hasher = Sha256::new();
+            let mut head = Vec::with_capacity(SNIFF_BYTES);
+            let mut size = 0u64;
+            while let Some(chunk) = stream.next().await {
+                let chunk = chunk.map_err(Into::into)?;
+                size += chunk.len() as u64;
+                if size > limit {
+                    return Err(too_large());
+                }
+                if head.len() < SNIFF_BYTES {
+                    let n = chunk.len().min(SNIFF_BYTES - head.len());
+                    head.extend_from_slice(&chunk[..n]);
+                }
+                hasher.update(&chunk);
+                file.write_all(&chunk).await.map_err(internal)?;
+            }
+            file.sync_all().await.map_err(internal)?;
+
+            let content_type = sniff(&head);
+            if !self.is_allowed(content_type) {
+                return Err(ApiError::new(
+                    StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                    "unsupported_file_type",
+                    format!("{}: {content_type} is not allowed", upload.name),
+                ));
+            }
+            Ok(FileMeta {
+                id: id.clone(),
+                name: upload.name,
+                content_type: content_type.to_owned(),
+                declared_type: upload.declared_type,
+                size,
+                sha256: format!("{:x}", hasher.finalize()),
+                owner: upload.owner,
+                created_at: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or_default(),
+            })
+        }
+        .await;
+
+        let meta = match result {
+            Ok(meta) => meta,
+            Err(e) => {
+                let _ = tokio::fs::remove_file(&part).await;
+                return Err(e);
+            }
+        };
+        // 先放好数据文件再写元数据，启动时只加载有数据文件的元数据
+        tokio::fs::rename(&part, self.dir.join(&id)).await.map_err(internal)?;
+        write_meta(&self.dir, &meta).await.map_err(internal)?;
+        self.index.write().unwrap().insert(id, meta.clone());
+        Ok(meta)
+    }
+
+    /// 不存在时返回 `None`。
+    /// 先从索引中取出，防止同时删除；删除数据文件失败时放回索引，文件仍然可以下载和重新删除
+    pub async fn delete(&self, id: &str) -> io::Result<Option<FileMeta>> {
+        let Some(meta) = self.index.write().unwrap().remove(id) else {
+            return Ok(None);
+        };
+        if let Err(e) = tokio::fs::remove_file(self.dir.join(id)).await {
+            self.index.write().unwrap().insert(meta.id.clone(), meta);
+            return Err(e);
+        }
+        // 数据文件已经删除，元数据删除失败时启动时也不会加载
+        tokio::fs::remove_file(self.dir.join(format!("{id}.json"))).await?;
+        Ok(Some(meta))
+    }
+
+    fn is_allowed(&self, content_type: &str) -> bool {
+        let essence = content_type.split(';').next().unwrap_or_default();
+        self.allowed_types.is_empty()
+            || self
+                .allowed_types
+                .iter()
+                .any(|allowed| match allowed.strip_suffix("/*") {
+                    Some(prefix) => essence.split('/').next() == Some(prefix),
+                    None => allowed == essence,
+                })
+    }
+}
+
+async fn write_meta(dir: &Path, meta: &FileMeta) -> io::Result<()> {
+    let tmp = dir.join(format!(".{}.json.part", meta.id));
+    tokio::fs::write(&tmp, serde_json::to_vec_pretty(meta)?).await?;
+    tokio::fs::rename(&tmp, dir.join(format!("{}.json", meta.id))).await
+}
+
+fn internal(e: io::Error) -> ApiError {
+    log::error!("file storage: {e}");
+    ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "internal", "failed to store file")
+}
+
+/// 去掉目录和控制字符，浏览器上传的文件名可能带有完整路径
+pub fn sanitize_name(name: &str) -> String {
+    let name = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let name: String = name.chars().filter(|c| !c.is_control()).take(255).collect();
+    match name.trim() {
+        "" | "." | ".." => "upload".to_owned(),
+        name => name.to_owned(),
+    }
+}
+
+/// 常见格式的文件头
+const SIGNATURES: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"\x00asm", "application/wasm"),
+];
+
+/// 按内容识别类型，不相信客户端声明的类型：
+/// 已知的文件头，不是文件头但能按 UTF-8 解码的是纯文本，其他都是 `application/octet-stream`
+pub fn sniff(head: &[u8]) -> &'static str {
+    if let Some((_, content_type)) = SIGNATURES.iter().find(|(magic, _)| head.starts_with(magic)) {
+        return content_type;
+    }
+    if head.len() >= 12 && &head[..4] == b"RIFF" && &head[8..12] == b"WEBP" {
+        return "image/webp";
+    }
+    if head.len() >= 12 && &head[4..8] == b"ftyp" {
+        return "video/mp4";
+    }
+
+    // 开头的字节可能截断在多字节字符中间
+    let text = match std::str::from_utf8(head) {
+        Ok(text) => Some(text),
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&head[..e.valid_up_to()]).ok(),
+        Err(_) => None,
+    };
+    match text {
+        Some(text) if !head.is_empty() && !text.contains('\0') => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
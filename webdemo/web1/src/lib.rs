@@ -0,0 +1,168 @@
+use std::{collections::BTreeMap, io, sync::Arc, time::Duration};
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceFactory, ServiceRequest, ServiceResponse},
+    get,
+    post,
+    web,
+    middleware,
+    App,
+    HttpRequest,
+    HttpResponse,
+    Responder,
+};
+use webdemo_common::health::Health;
+
+pub mod api;
+pub mod config;
+pub mod files;
+pub mod openapi;
+pub mod security;
+pub mod telemetry;
+use api::ApiError;
+use config::Config;
+use security::CsrfToken;
+
+#[utoipa::path(params(("name", description = "名字")), responses((status = 200, body = String)))]
+#[get("/hello/{name}")]
+async fn greet(name: web::Path<String>) -> impl Responder {
+    format!("Hello {name}!")
+}
+
+#[get("/")]
+async fn index(req: HttpRequest) -> String {
+    format!("index: {:?}", req)
+}
+
+#[utoipa::path(responses((status = 400, description = "总是返回错误", body = ApiError)))]
+#[get("/error")]
+async fn error(req: HttpRequest) -> Result<String, ApiError> {
+    Err(ApiError::bad_request("an").with_request_id(&req))
+}
+
+/// CSRF 演示：表单中带隐藏的 token 字段
+#[get("/form")]
+async fn form_page(token: CsrfToken) -> HttpResponse {
+    HttpResponse::Ok().content_type("text/html; charset=utf-8").body(format!(
+        r#"<form method="post" action="/form">
+<input type="hidden" name="csrf_token" value="{}">
+<input name="message">
+<button type="submit">submit</button>
+</form>
+"#,
+        token.0
+    ))
+}
+
+#[post("/form")]
+async fn form_submit(form: web::Form<BTreeMap<String, String>>) -> HttpResponse {
+    HttpResponse::Ok().json(form.into_inner())
+}
+
+/// 所有 worker 共享的状态，在 `HttpServer::new` 之外创建
+#[derive(Clone)]
+pub struct AppState {
+    pub config: Arc<Config>,
+    authenticator: Arc<api::auth::Authenticator>,
+    policy: Option<Arc<api::authz::Policy>>,
+    versions: Arc<api::version::Versions>,
+    files: Arc<files::FileStore>,
+}
+
+impl AppState {
+    /// 读取密钥和策略文件，加载上传文件的元数据
+    pub fn new(config: Config) -> io::Result<Self> {
+        let authenticator = Arc::new(api::auth::Authenticator::new(&config.auth)?);
+        let policy = config
+            .auth
+            .policy_file
+            .as_deref()
+            .map(api::authz::Policy::from_file)
+            .transpose()?
+            .map(Arc::new);
+        let versions = Arc::new(api::version::Versions::new(&config.api)?);
+        let files = Arc::new(files::FileStore::new(&config.files)?);
+        Ok(Self {
+            config: Arc::new(config),
+            authenticator,
+            policy,
+            versions,
+            files,
+        })
+    }
+}
+
+/// `[health]` 中配置的上游加入 `/readyz` 的检查
+pub fn health(config: &Config, health: Health) -> Health {
+    config
+        .health
+        .upstreams
+        .iter()
+        .fold(health, |health, (name, url)| health.check_url(name, url))
+        .timeout(Duration::from_millis(config.health.timeout_ms))
+}
+
+/// 每个 worker 调用一次，健康检查路由由调用方挂载
+pub fn app(
+    state: &AppState,
+) -> App<
+    impl ServiceFactory<
+        ServiceRequest,
+        Config = (),
+        Response = ServiceResponse<impl MessageBody>,
+        Error = actix_web::Error,
+        InitError = (),
+    >,
+> {
+    let config = &state.config;
+    App::new()
+        .wrap(api::version::Versioning(state.versions.clone()))
+        .wrap(api::error::error_handlers())
+        .wrap(middleware::Condition::new(
+            config.csrf.enabled,
+            security::Csrf::new(&config.csrf, config.limits.form_bytes),
+        ))
+        .wrap(middleware::Condition::new(
+            config.security_headers.enabled,
+            security::SecurityHeaders::new(&config.security_headers),
+        ))
+        .wrap(middleware::Condition::new(config.cors.enabled, security::cors(&config.cors)))
+        .wrap(telemetry::RequestTracing::new(config.features.access_log))
+        .configure(|cfg| api::error::configure(cfg, &config.limits))
+        .service(
+            web::resource("/api")
+                .app_data(web::Data::from(state.versions.clone()))
+                .route(web::get().to(api::version::list)),
+        )
+        .service(
+            api::version::ApiVersion::ALL.into_iter().fold(
+                web::scope("/api")
+                    .wrap(middleware::Condition::new(
+                        state.policy.is_some(),
+                        api::authz::Authorize(state.policy.clone().unwrap_or_default()),
+                    ))
+                    .wrap(api::auth::Authentication::new(state.authenticator.clone()))
+                    // .guard(guard::Header("content-type", "application/json"))
+                    // .guard(guard::Get())
+                    // .guard(guard::Post())
+                    .app_data(web::Data::from(state.authenticator.clone()))
+                    .app_data(web::Data::from(state.files.clone())),
+                |scope, version| scope.service(web::scope(version.as_str()).configure(|cfg| version.configure(cfg))),
+            ),
+        )
+        .configure(|cfg| {
+            if config.features.swagger_ui {
+                openapi::configure(cfg);
+            }
+            for (name, url) in &config.external_resources {
+                cfg.external_resource(name, url);
+            }
+        })
+        .service(error)
+        .service(index)
+        .service(greet)
+        .service(form_page)
+        .service(form_submit)
+        .default_service(web::to(api::error::not_found))
+}
@@ -1,57 +1,22 @@
-use actix_web::{
-    get,
-    web,
-    guard,
-    middleware,
-    App,
-    HttpServer,
-    HttpRequest,
-    Responder,
-};
+use webdemo_common::{health, Loader, Server, ServerArgs};
 
-mod api;
-use api::ApiError;
+use web1::{config::Config, AppState};
 
-#[get("/hello/{name}")]
-async fn greet(name: web::Path<String>) -> impl Responder {
-    format!("Hello {name}!")
+#[actix_web::main] // or #[tokio::main]
+async fn main() {
+    if let Err(e) = run().await {
+        e.exit("web1");
+    }
 }
 
-#[get("/")]
-async fn index(req: HttpRequest) -> String {
-    format!("index: {:?}", req)
-}
+async fn run() -> Result<(), webdemo_common::Error> {
+    let args = ServerArgs::parse("web1");
+    let config: Config = Loader::new("web1")
+        .defaults(Config::default())
+        .redact("auth.jwt_secret")
+        .load(&args)?;
+    let server = Server::new(&config, health::build_info!())?.health(|health| web1::health(&config, health));
 
-#[get("/error")]
-async fn error() -> Result<String, ApiError> {
-    Err(ApiError { message: "an".to_owned() })
+    let state = AppState::new(config)?;
+    server.run(move || web1::app(&state)).await
 }
-
-#[actix_web::main] // or #[tokio::main]
-async fn main() -> std::io::Result<()> {
-    env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
-    
-    let port = 48080;
-    log::info!("starting HTTP server at http://localhost:{:?}", port);
-
-    HttpServer::new(|| {
-        App::new()
-            .wrap(middleware::Logger::default())
-            .service(
-                web::scope("/api")
-                    // .guard(guard::Header("content-type", "application/json"))
-                    // .guard(guard::Get())
-                    // .guard(guard::Post())
-                    .service(api::apiparam::index)
-                    .service(api::apiparam::index1)
-            )
-            .external_resource("/baidu", "https://baidu.com")
-            .service(error)
-            .service(index)
-            .service(greet)
-    })
-    .bind(("0.0.0.0", port))?
-    .workers(4)
-    .run()
-    .await
-}
\ No newline at end of file
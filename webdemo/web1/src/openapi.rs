@@ -0,0 +1,76 @@
+use actix_web::web;
+use utoipa::{
+    openapi::{
+        security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+        Content, RefOr,
+    },
+    Modify, OpenApi,
+};
+use utoipa_swagger_ui::SwaggerUi;
+
+use crate::api::{self, negotiate::Format};
+
+/// web1 的 OpenAPI 文档，`/api/v1` 和 `/api/v2` 下的接口来自 `apiparam`。
+/// 不带版本的 `/api/...` 按请求头选择版本，文档中只列出带版本的路径。
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "web1"),
+    paths(crate::greet, crate::error, api::version::list),
+    nest(
+        (path = "/api/v1", api = api::apiparam::ApiV1Doc, tags = ["v1"]),
+        (path = "/api/v2", api = api::apiparam::ApiV2Doc, tags = ["v2"]),
+        (path = "/api/v1", api = api::files::FilesDoc, tags = ["v1"]),
+        (path = "/api/v2", api = api::files::FilesDoc, tags = ["v2"]),
+    ),
+    modifiers(&BearerAuth, &NegotiatedFormats),
+)]
+struct ApiDoc;
+
+/// `Authorization: Bearer <token>`，token 文件中的 token 或 JWT
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).bearer_format("JWT").build()),
+        );
+    }
+}
+
+/// `/api` 下的接口通过 `Negotiated` 支持多种格式，把 JSON 的请求体和成功响应复制到其他媒体类型
+struct NegotiatedFormats;
+
+impl Modify for NegotiatedFormats {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        for (path, item) in openapi.paths.paths.iter_mut() {
+            if !path.starts_with("/api/") {
+                continue;
+            }
+            let operations = [&mut item.get, &mut item.post, &mut item.put, &mut item.patch, &mut item.delete];
+            for operation in operations.into_iter().flatten() {
+                if let Some(body) = &mut operation.request_body {
+                    let json = body.content.get(Format::Json.media_type()).cloned();
+                    body.content.extend(other_formats(json));
+                }
+                for (status, response) in operation.responses.responses.iter_mut() {
+                    if let (true, RefOr::T(response)) = (status.starts_with('2'), response) {
+                        let json = response.content.get(Format::Json.media_type()).cloned();
+                        response.content.extend(other_formats(json));
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn other_formats(json: Option<Content>) -> impl Iterator<Item = (String, Content)> {
+    json.into_iter()
+        .flat_map(|json| Format::ALL[1..].iter().map(move |format| (format.media_type().to_owned(), json.clone())))
+}
+
+/// `/openapi.json` 和 `/swagger-ui/`，Swagger UI 的静态文件编译进二进制，不依赖外网
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(SwaggerUi::new("/swagger-ui/{_:.*}").url("/openapi.json", ApiDoc::openapi()));
+}
@@ -0,0 +1,318 @@
+use std::rc::Rc;
+
+use actix_cors::Cors;
+use actix_web::{
+    body::EitherBody,
+    cookie::{Cookie, SameSite},
+    dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    http::{
+        header::{self, HeaderName, HeaderValue},
+        Method, StatusCode,
+    },
+    web::{Bytes, BytesMut},
+    Error, FromRequest, HttpMessage, HttpRequest,
+};
+use futures_util::{
+    future::{self, LocalBoxFuture, Ready},
+    StreamExt as _,
+};
+use rand::RngCore as _;
+
+use crate::{
+    api::ApiError,
+    config::{CorsConfig, CsrfConfig, SecurityHeadersConfig},
+};
+
+pub fn cors(config: &CorsConfig) -> Cors {
+    let mut cors = Cors::default()
+        .allowed_methods(config.allowed_methods.iter().map(String::as_str))
+        .allowed_headers(config.allowed_headers.iter().map(String::as_str))
+        .expose_headers(config.expose_headers.iter().map(String::as_str))
+        .max_age(config.max_age_secs);
+    for origin in &config.allowed_origins {
+        cors = match origin.as_str() {
+            "*" => cors.allow_any_origin(),
+            origin => cors.allowed_origin(origin),
+        };
+    }
+    if config.allow_credentials {
+        cors = cors.supports_credentials();
+    }
+    cors
+}
+
+/// 安全相关的响应头，处理函数已经设置的不覆盖
+pub struct SecurityHeaders {
+    headers: Rc<Vec<(HeaderName, HeaderValue)>>,
+    /// HSTS 只在 HTTPS 响应中有效
+    hsts: Option<(HeaderName, HeaderValue)>,
+}
+
+impl SecurityHeaders {
+    pub fn new(config: &SecurityHeadersConfig) -> Self {
+        let mut headers = vec![(header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"))];
+        let configured = [
+            (header::CONTENT_SECURITY_POLICY, &config.content_security_policy),
+            (header::X_FRAME_OPTIONS, &config.frame_options),
+            (header::REFERRER_POLICY, &config.referrer_policy),
+        ];
+        for (name, value) in configured {
+            if let Some(value) = value.as_deref().and_then(|v| HeaderValue::from_str(v).ok()) {
+                headers.push((name, value));
+            }
+        }
+
+        let hsts = config.hsts_max_age_secs.map(|max_age| {
+            let value = match config.hsts_include_subdomains {
+                true => format!("max-age={max_age}; includeSubDomains"),
+                false => format!("max-age={max_age}"),
+            };
+            (header::STRICT_TRANSPORT_SECURITY, HeaderValue::from_str(&value).unwrap())
+        });
+
+        Self {
+            headers: Rc::new(headers),
+            hsts,
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for SecurityHeaders
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = SecurityHeadersMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        future::ok(SecurityHeadersMiddleware {
+            service,
+            headers: self.headers.clone(),
+            hsts: self.hsts.clone(),
+        })
+    }
+}
+
+pub struct SecurityHeadersMiddleware<S> {
+    service: S,
+    headers: Rc<Vec<(HeaderName, HeaderValue)>>,
+    hsts: Option<(HeaderName, HeaderValue)>,
+}
+
+impl<S, B> Service<ServiceRequest> for SecurityHeadersMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let https = req.connection_info().scheme() == "https";
+        let headers = self.headers.clone();
+        let hsts = self.hsts.clone().filter(|_| https);
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let mut res = fut.await?;
+            let map = res.headers_mut();
+            for (name, value) in headers.iter().chain(&hsts) {
+                if !map.contains_key(name) {
+                    map.insert(name.clone(), value.clone());
+                }
+            }
+            Ok(res)
+        })
+    }
+}
+
+/// 当前请求的 CSRF token，表单页面用它渲染隐藏字段
+#[derive(Debug, Clone)]
+pub struct CsrfToken(pub String);
+
+impl FromRequest for CsrfToken {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let token = req.extensions().get::<CsrfToken>().cloned();
+        let error = || ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "csrf_disabled", "csrf protection is disabled");
+        future::ready(token.ok_or_else(|| error().into()))
+    }
+}
+
+struct CsrfSettings {
+    cookie_name: String,
+    header_name: HeaderName,
+    field_name: String,
+    /// 从表单读取 token 时最多读取的字节数
+    form_limit: usize,
+}
+
+/// double-submit cookie CSRF 保护：cookie 中的随机 token 必须和请求头或表单字段中的一致。
+/// 只检查浏览器跨站也能直接提交的请求（非安全方法的表单、`text/plain`），
+/// 带 `Authorization` 的请求不依赖 cookie，不需要检查。
+pub struct Csrf {
+    settings: Rc<CsrfSettings>,
+}
+
+impl Csrf {
+    pub fn new(config: &CsrfConfig, form_limit: usize) -> Self {
+        Self {
+            settings: Rc::new(CsrfSettings {
+                cookie_name: config.cookie_name.clone(),
+                header_name: HeaderName::from_bytes(config.header_name.as_bytes()).unwrap(),
+                field_name: config.field_name.clone(),
+                form_limit,
+            }),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Csrf
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = CsrfMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        future::ok(CsrfMiddleware {
+            service: Rc::new(service),
+            settings: self.settings.clone(),
+        })
+    }
+}
+
+pub struct CsrfMiddleware<S> {
+    service: Rc<S>,
+    settings: Rc<CsrfSettings>,
+}
+
+impl<S, B> Service<ServiceRequest> for CsrfMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let settings = self.settings.clone();
+
+        let cookie = req
+            .cookie(&settings.cookie_name)
+            .map(|c| c.value().to_owned())
+            .filter(|t| t.len() == 64 && t.bytes().all(|b| b.is_ascii_hexdigit()));
+        let issue = cookie.is_none();
+        let token = cookie.clone().unwrap_or_else(new_token);
+        req.extensions_mut().insert(CsrfToken(token.clone()));
+
+        Box::pin(async move {
+            if needs_check(&req) {
+                let submitted = match req.headers().get(&settings.header_name) {
+                    Some(value) => value.to_str().ok().map(str::to_owned),
+                    None => form_field(&mut req, &settings).await,
+                };
+                let valid = matches!((&cookie, &submitted), (Some(c), Some(s)) if constant_time_eq(c, s));
+                if !valid {
+                    log::warn!("csrf check failed for {} {}", req.method(), req.path());
+                    let e = ApiError::new(StatusCode::FORBIDDEN, "csrf_failed", "csrf token missing or invalid").with_request_id(req.request());
+                    let mut res = req.error_response(e);
+                    if issue {
+                        set_cookie(&mut res, &settings, &token);
+                    }
+                    return Ok(res.map_into_right_body());
+                }
+            }
+
+            let mut res = service.call(req).await?;
+            if issue {
+                set_cookie(&mut res, &settings, &token);
+            }
+            Ok(res.map_into_left_body())
+        })
+    }
+}
+
+fn new_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// 非安全方法的表单或 `text/plain` 请求，没有 `Authorization`
+fn needs_check(req: &ServiceRequest) -> bool {
+    if matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE)
+        || req.headers().contains_key(header::AUTHORIZATION)
+    {
+        return false;
+    }
+    let content_type = req.content_type();
+    content_type.is_empty()
+        || ["application/x-www-form-urlencoded", "multipart/form-data", "text/plain"]
+            .iter()
+            .any(|form| content_type.eq_ignore_ascii_case(form))
+}
+
+/// 读取 urlencoded 表单中的 token，读完后把请求体放回去给处理函数
+async fn form_field(req: &mut ServiceRequest, settings: &CsrfSettings) -> Option<String> {
+    if !req.content_type().eq_ignore_ascii_case("application/x-www-form-urlencoded") {
+        return None;
+    }
+
+    let mut payload = req.take_payload();
+    let mut body = BytesMut::new();
+    let mut complete = true;
+    while let Some(chunk) = payload.next().await {
+        match chunk {
+            Ok(chunk) if body.len() + chunk.len() <= settings.form_limit => body.extend_from_slice(&chunk),
+            _ => {
+                complete = false;
+                break;
+            }
+        }
+    }
+    let body: Bytes = body.freeze();
+    let token = complete
+        .then(|| {
+            serde_urlencoded::from_bytes::<Vec<(String, String)>>(&body)
+                .ok()?
+                .into_iter()
+                .find_map(|(name, value)| (name == settings.field_name).then_some(value))
+        })
+        .flatten();
+
+    req.set_payload(Payload::from(futures_util::stream::once(async move { Ok(body) }).boxed_local()));
+    token
+}
+
+fn set_cookie<B>(res: &mut ServiceResponse<B>, settings: &CsrfSettings, token: &str) {
+    let secure = res.request().connection_info().scheme() == "https";
+    // 前端脚本需要读取 cookie 放到请求头中，不能是 HttpOnly
+    let cookie = Cookie::build(settings.cookie_name.clone(), token.to_owned())
+        .path("/")
+        .same_site(SameSite::Strict)
+        .secure(secure)
+        .finish();
+    let _ = res.response_mut().add_cookie(&cookie);
+}
+
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
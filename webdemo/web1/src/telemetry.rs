@@ -0,0 +1,157 @@
+use std::{rc::Rc, time::Instant};
+
+use actix_web::{
+    body::{BoxBody, EitherBody},
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    error::InternalError,
+    http::header::{HeaderName, HeaderValue},
+    Error, HttpResponse,
+};
+use futures_util::future::{self, LocalBoxFuture, Ready};
+use tracing::Instrument as _;
+
+use crate::api::{error::REQUEST_ID, ApiError};
+
+/// 分配或沿用 `X-Request-Id`，并在覆盖整个请求的 span 中处理：
+/// 请求头中带上 id 供后面的中间件和处理函数使用，响应头和 `ApiError` 的 `request_id` 中返回，
+/// 请求完成后记录状态码、耗时和路由模式。
+pub struct RequestTracing {
+    access_log: bool,
+}
+
+impl RequestTracing {
+    /// `access_log` 为 false 时只保留 span，不输出每个请求的完成日志
+    pub fn new(access_log: bool) -> Self {
+        Self { access_log }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequestTracing
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B, BoxBody>>;
+    type Error = Error;
+    type Transform = RequestTracingMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        future::ok(RequestTracingMiddleware {
+            service: Rc::new(service),
+            access_log: self.access_log,
+        })
+    }
+}
+
+pub struct RequestTracingMiddleware<S> {
+    service: Rc<S>,
+    access_log: bool,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestTracingMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B, BoxBody>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let start = Instant::now();
+        let id = req
+            .headers()
+            .get(REQUEST_ID)
+            .and_then(|v| v.to_str().ok())
+            .filter(|id| valid_request_id(id))
+            .map(str::to_owned)
+            .unwrap_or_else(new_request_id);
+        let header = HeaderValue::from_str(&id).unwrap();
+        req.headers_mut().insert(HeaderName::from_static(REQUEST_ID), header.clone());
+
+        let span = tracing::info_span!(
+            "http_request",
+            request_id = %id,
+            method = %req.method(),
+            path = %req.path(),
+            peer = req.connection_info().realip_remote_addr().unwrap_or("-"),
+            route = tracing::field::Empty,
+            status = tracing::field::Empty,
+            latency_ms = tracing::field::Empty,
+        );
+        let fut = span.in_scope(|| self.service.call(req));
+        let access_log = self.access_log;
+
+        Box::pin(
+            async move {
+                let res = fut.await;
+                let span = tracing::Span::current();
+                let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+                span.record("latency_ms", latency_ms);
+
+                let mut res = match res {
+                    Ok(res) => res,
+                    Err(e) => {
+                        let status = e.as_response_error().status_code();
+                        span.record("status", status.as_u16());
+                        tracing::error!(status = status.as_u16(), latency_ms, "request failed: {e}");
+                        // 请求已经交给内部服务，错误仍然向外传递，响应和正常返回的一样带上 id
+                        let mut res = missing_request_id(&e, &id)
+                            .map(HttpResponse::from_error)
+                            .unwrap_or_else(|| e.error_response());
+                        res.headers_mut().insert(HeaderName::from_static(REQUEST_ID), header);
+                        return Err(InternalError::from_response(e, res).into());
+                    }
+                };
+
+                let status = res.status().as_u16();
+                let route = res.request().match_pattern().unwrap_or_else(|| "-".to_owned());
+                span.record("route", route.as_str());
+                span.record("status", status);
+                if access_log {
+                    tracing::info!(target: "web1::access", status, latency_ms, route, "request completed");
+                }
+
+                res.headers_mut().insert(HeaderName::from_static(REQUEST_ID), header);
+                Ok(with_request_id(res, &id))
+            }
+            .instrument(span),
+        )
+    }
+}
+
+/// 没有调用 `ApiError::with_request_id` 的错误，在这里补上 `request_id`
+fn with_request_id<B>(res: ServiceResponse<B>, id: &str) -> ServiceResponse<EitherBody<B, BoxBody>> {
+    let Some(error) = res.response().error().and_then(|e| missing_request_id(e, id)) else {
+        return res.map_into_left_body();
+    };
+
+    let (req, old) = res.into_parts();
+    let mut new = HttpResponse::from_error(error);
+    for (name, value) in old.headers() {
+        if !new.headers().contains_key(name) {
+            new.headers_mut().append(name.clone(), value.clone());
+        }
+    }
+    ServiceResponse::new(req, new).map_into_right_body()
+}
+
+/// 还没有 `request_id` 的 `ApiError`，返回补上 id 的副本
+fn missing_request_id(error: &Error, id: &str) -> Option<ApiError> {
+    let mut error = error.as_error::<ApiError>().filter(|e| e.request_id.is_none()).cloned()?;
+    error.request_id = Some(id.to_owned());
+    Some(error)
+}
+
+/// 客户端传入的 id 只接受不太长的可见字符，避免注入日志
+fn valid_request_id(id: &str) -> bool {
+    (1..=128).contains(&id.len()) && id.bytes().all(|b| b.is_ascii_alphanumeric() || b"-_.:".contains(&b))
+}
+
+fn new_request_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
@@ -0,0 +1,779 @@
+//! web1 集成测试：页面、CSRF、`/api` 的认证授权和内容协商、文档、健康检查和错误格式。
+
+use std::{
+    fs,
+    path::PathBuf,
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use actix_web::{
+    body,
+    dev::ServiceResponse,
+    http::{header, StatusCode},
+    test::TestRequest,
+    web, App, HttpResponse,
+};
+use futures_util::future;
+use serde_json::{json, Value};
+use web1::{
+    api::ApiError,
+    config::{Config, DeprecationConfig},
+    telemetry::RequestTracing,
+    AppState,
+};
+use webdemo_common::health::{self, Health};
+use webdemo_testing::{assert, TestApp, TestServer};
+
+/// 测试用的 token：`admin` 可以读写，`reader` 只能读，`uploader` 只能读写文件，`nobody` 没有任何权限
+const KEYS: &str = r#"[
+    { "token": "admin-token", "subject": "admin", "roles": ["admin"], "scopes": ["api:read", "api:write"] },
+    { "token": "reader-token", "subject": "reader", "roles": ["reader"], "scopes": ["api:read"] },
+    { "token": "uploader-token", "subject": "uploader", "roles": ["uploader"], "scopes": ["api:read", "api:write"] },
+    { "token": "nobody-token", "subject": "nobody" },
+    { "token": "expired-token", "subject": "old", "expires_at": 1600000000 }
+]"#;
+
+fn keys_file() -> PathBuf {
+    let path = std::env::temp_dir().join(format!("web1-test-keys-{}.json", process::id()));
+    fs::write(&path, KEYS).unwrap();
+    path
+}
+
+/// 每个用例使用自己的上传目录
+fn files_dir() -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let n = NEXT.fetch_add(1, Ordering::Relaxed);
+    let dir = std::env::temp_dir().join(format!("web1-test-files-{}-{n}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+fn config() -> Config {
+    let mut config = Config::default();
+    config.auth.keys_file = Some(keys_file());
+    config.files.dir = files_dir();
+    config.auth.policy_file = Some(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("policy.example.json"));
+    config
+}
+
+async fn app(config: Config) -> TestApp {
+    let health = web1::health(&config, Health::new(health::build_info!()));
+    let state = AppState::new(config).unwrap();
+    TestApp::new(web1::app(&state).configure(health.configure())).await
+}
+
+fn bearer(token: &str) -> (header::HeaderName, String) {
+    (header::AUTHORIZATION, format!("Bearer {token}"))
+}
+
+/// `(字段名, 文件名, Content-Type, 内容)`，没有文件名的是普通字段
+fn multipart(parts: &[(&str, Option<&str>, &str, &[u8])]) -> (String, Vec<u8>) {
+    const BOUNDARY: &str = "web1-test-boundary";
+    let mut body = Vec::new();
+    for (name, filename, content_type, content) in parts {
+        let filename = filename.map(|f| format!("; filename=\"{f}\"")).unwrap_or_default();
+        body.extend_from_slice(
+            format!(
+                "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"{name}\"{filename}\r\nContent-Type: {content_type}\r\n\r\n"
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(content);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{BOUNDARY}--\r\n").as_bytes());
+    (format!("multipart/form-data; boundary={BOUNDARY}"), body)
+}
+
+fn upload(token: &str, parts: &[(&str, Option<&str>, &str, &[u8])]) -> TestRequest {
+    let (content_type, body) = multipart(parts);
+    TestRequest::post()
+        .uri("/api/files")
+        .insert_header(bearer(token))
+        .insert_header((header::CONTENT_TYPE, content_type))
+        .set_payload(body)
+}
+
+fn action_body() -> Value {
+    json!({
+        "name": "deploy",
+        "mode": "sync",
+        "tags": ["a", "b"],
+        "options": { "retries": 1, "timeout_ms": 500 },
+    })
+}
+
+#[actix_web::test]
+async fn serves_pages() {
+    let app = app(config()).await;
+
+    let body = assert::body(app.get("/").await, StatusCode::OK).await;
+    assert!(body.starts_with(b"index: "));
+
+    let body = assert::body(app.get("/hello/world").await, StatusCode::OK).await;
+    assert_eq!(body, "Hello world!");
+}
+
+#[actix_web::test]
+async fn returns_api_errors_with_request_id() {
+    let app = app(config()).await;
+
+    let res = app.call(TestRequest::get().uri("/error").insert_header(("x-request-id", "test-1"))).await;
+    assert_eq!(assert::header(res.headers(), "x-request-id"), "test-1");
+    let error = assert::json(res, StatusCode::BAD_REQUEST).await;
+    assert::json_includes(&error, &json!({ "code": "bad_request", "message": "an", "request_id": "test-1" }));
+
+    let res = app.get("/missing").await;
+    let id = assert::header(res.headers(), "x-request-id").to_owned();
+    let error = assert::json(res, StatusCode::NOT_FOUND).await;
+    assert::json_includes(&error, &json!({ "code": "not_found", "request_id": id }));
+}
+
+/// 中间件返回 `Err` 时同样带上请求 id
+#[actix_web::test]
+async fn adds_request_id_to_middleware_errors() {
+    let app = TestApp::new(
+        App::new()
+            .wrap_fn(|_, _| future::err::<ServiceResponse, _>(ApiError::bad_request("rejected").into()))
+            .wrap(RequestTracing::new(false))
+            .route("/", web::get().to(HttpResponse::Ok)),
+    )
+    .await;
+
+    // 错误继续向外传递，由服务器调用 `error_response` 生成响应
+    let res = app
+        .try_call(TestRequest::get().uri("/").insert_header(("x-request-id", "test-2")))
+        .await
+        .expect_err("middleware error")
+        .error_response();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(assert::header(res.headers(), "x-request-id"), "test-2");
+    let error: Value = serde_json::from_slice(&body::to_bytes(res.into_body()).await.unwrap()).unwrap();
+    assert::json_includes(&error, &json!({ "code": "bad_request", "message": "rejected", "request_id": "test-2" }));
+}
+
+#[actix_web::test]
+async fn sends_security_headers() {
+    let app = app(config()).await;
+
+    let res = app.get("/hello/world").await;
+    assert_eq!(assert::header(res.headers(), "x-content-type-options"), "nosniff");
+    assert_eq!(assert::header(res.headers(), "x-frame-options"), "DENY");
+    assert_eq!(assert::header(res.headers(), "referrer-policy"), "no-referrer");
+    assert!(assert::header(res.headers(), "content-security-policy").contains("default-src 'self'"));
+    // 只在 HTTPS 上发送
+    assert!(res.headers().get("strict-transport-security").is_none());
+}
+
+#[actix_web::test]
+async fn checks_csrf_token_on_forms() {
+    let app = app(config()).await;
+
+    let res = app.get("/form").await;
+    let cookie = res
+        .response()
+        .cookies()
+        .find(|c| c.name() == "csrf_token")
+        .expect("csrf cookie")
+        .into_owned();
+    let body = assert::body(res, StatusCode::OK).await;
+    assert!(String::from_utf8_lossy(&body).contains(cookie.value()));
+
+    let form = [("csrf_token", cookie.value()), ("message", "hi")];
+    let res = app.call(TestRequest::post().uri("/form").cookie(cookie.clone()).set_form(form)).await;
+    assert_eq!(assert::json(res, StatusCode::OK).await, json!({ "csrf_token": cookie.value(), "message": "hi" }));
+
+    let res = app
+        .call(
+            TestRequest::post()
+                .uri("/form")
+                .cookie(cookie.clone())
+                .insert_header(("x-csrf-token", cookie.value()))
+                .set_form([("message", "hi")]),
+        )
+        .await;
+    assert::body(res, StatusCode::OK).await;
+
+    // 媒体类型不区分大小写
+    let res = app
+        .call(
+            TestRequest::post()
+                .uri("/form")
+                .cookie(cookie.clone())
+                .insert_header((header::CONTENT_TYPE, "Application/X-WWW-Form-Urlencoded"))
+                .set_payload(format!("csrf_token={}&message=hi", cookie.value())),
+        )
+        .await;
+    assert::json(res, StatusCode::OK).await;
+
+    let res = app.call(TestRequest::post().uri("/form").cookie(cookie).set_form([("message", "hi")])).await;
+    assert::json_includes(&assert::json(res, StatusCode::FORBIDDEN).await, &json!({ "code": "csrf_failed" }));
+}
+
+#[actix_web::test]
+async fn authenticates_api_requests() {
+    let app = app(config()).await;
+
+    let res = app.get("/api/index.json").await;
+    assert::json_includes(&assert::json(res, StatusCode::UNAUTHORIZED).await, &json!({ "code": "unauthorized" }));
+
+    let res = app.call(TestRequest::get().uri("/api/index.json").insert_header(bearer("wrong"))).await;
+    assert::json_includes(&assert::json(res, StatusCode::UNAUTHORIZED).await, &json!({ "code": "invalid_token" }));
+
+    let res = app.call(TestRequest::get().uri("/api/index.json").insert_header(bearer("expired-token"))).await;
+    assert::json(res, StatusCode::UNAUTHORIZED).await;
+
+    // 认证方案不区分大小写
+    let res = app
+        .call(TestRequest::get().uri("/api/index.json").insert_header((header::AUTHORIZATION, "BEARER reader-token")))
+        .await;
+    assert::json(res, StatusCode::OK).await;
+    let res = app
+        .call(TestRequest::get().uri("/api/index.json").insert_header((header::AUTHORIZATION, "Basic reader-token")))
+        .await;
+    assert::json_includes(&assert::json(res, StatusCode::UNAUTHORIZED).await, &json!({ "code": "unauthorized" }));
+
+    let res = app.call(TestRequest::get().uri("/api/index.json").insert_header(bearer("reader-token"))).await;
+    assert_eq!(assert::json(res, StatusCode::OK).await, json!({ "code": 0, "message": "Ok" }));
+
+    let res = app.call(TestRequest::get().uri("/api/me.json").insert_header(bearer("reader-token"))).await;
+    assert::json_includes(
+        &assert::json(res, StatusCode::OK).await,
+        &json!({ "subject": "reader", "roles": ["reader"], "scopes": ["api:read"] }),
+    );
+}
+
+#[actix_web::test]
+async fn authorizes_by_scope_and_policy() {
+    let app = app(config()).await;
+
+    // 策略不允许没有角色的调用方访问
+    let res = app.call(TestRequest::get().uri("/api/users/5.json").insert_header(bearer("nobody-token"))).await;
+    assert::json(res, StatusCode::FORBIDDEN).await;
+
+    let res = app.call(TestRequest::get().uri("/api/users/5.json").insert_header(bearer("reader-token"))).await;
+    assert_eq!(assert::json(res, StatusCode::OK).await, json!({ "controller": "users", "action": 5 }));
+
+    // reader 角色只能 GET
+    let res = app
+        .call(
+            TestRequest::post()
+                .uri("/api/users/5.json")
+                .insert_header(bearer("reader-token"))
+                .set_json(action_body()),
+        )
+        .await;
+    assert::json(res, StatusCode::FORBIDDEN).await;
+
+    let res = app
+        .call(
+            TestRequest::post()
+                .uri("/api/users/5.json")
+                .insert_header(bearer("admin-token"))
+                .set_json(action_body()),
+        )
+        .await;
+    assert::json_includes(
+        &assert::json(res, StatusCode::OK).await,
+        &json!({ "controller": "users", "action": 5, "request": action_body() }),
+    );
+}
+
+#[actix_web::test]
+async fn validates_api_parameters() {
+    let app = app(config()).await;
+
+    let res = app.call(TestRequest::get().uri("/api/Users/5.json").insert_header(bearer("admin-token"))).await;
+    let error = assert::json(res, StatusCode::UNPROCESSABLE_ENTITY).await;
+    assert_eq!(error["details"][0]["field"], "controller");
+
+    let res = app.call(TestRequest::get().uri("/api/users/20000.json").insert_header(bearer("admin-token"))).await;
+    assert::json(res, StatusCode::UNPROCESSABLE_ENTITY).await;
+
+    let mut body = action_body();
+    body["mode"] = json!("later");
+    let res = app
+        .call(TestRequest::post().uri("/api/users/5.json").insert_header(bearer("admin-token")).set_json(body))
+        .await;
+    let error = assert::json(res, StatusCode::UNPROCESSABLE_ENTITY).await;
+    assert_eq!(error["details"][0]["field"], "mode");
+
+    let res = app
+        .call(
+            TestRequest::post()
+                .uri("/api/users/5.json")
+                .insert_header(bearer("admin-token"))
+                .insert_header(header::ContentType::json())
+                .set_payload("{"),
+        )
+        .await;
+    assert::json(res, StatusCode::BAD_REQUEST).await;
+}
+
+#[actix_web::test]
+async fn paginates_filters_and_sorts_lists() {
+    let app = app(config()).await;
+    let list = |uri: &str| TestRequest::get().uri(uri).insert_header(bearer("admin-token"));
+
+    let res = app.call(list("/api/principals.json?limit=2")).await;
+    assert_eq!(
+        assert::json(res, StatusCode::OK).await,
+        json!({
+            "items": [
+                { "subject": "admin", "roles": ["admin"], "scopes": ["api:read", "api:write"], "expires_at": null },
+                { "subject": "nobody", "roles": [], "scopes": [], "expires_at": null },
+            ],
+            "total": 5,
+            "limit": 2,
+            "page": 1,
+            "pages": 3,
+            "links": {
+                "self": "/api/v1/principals.json?limit=2&page=1",
+                "first": "/api/v1/principals.json?limit=2&page=1",
+                "next": "/api/v1/principals.json?limit=2&page=2",
+                "last": "/api/v1/principals.json?limit=2&page=3",
+            },
+        })
+    );
+
+    let res = app.call(list("/api/v1/principals.json?limit=2&page=2&sort=-subject")).await;
+    let page = assert::json(res, StatusCode::OK).await;
+    assert_eq!(page["items"][0]["subject"], "old");
+    assert_eq!(page["items"][1]["subject"], "nobody");
+    assert_eq!(page["links"]["prev"], "/api/v1/principals.json?limit=2&sort=-subject&page=1");
+    assert_eq!(page["links"]["next"], "/api/v1/principals.json?limit=2&sort=-subject&page=3");
+
+    // 同一字段的过滤满足任一，不同字段需要全部满足
+    let res = app
+        .call(list("/api/principals.json?filter%5Brole%5D=admin&filter%5Brole%5D=reader&filter%5Bscope%5D=api:write"))
+        .await;
+    assert::json_includes(
+        &assert::json(res, StatusCode::OK).await,
+        &json!({ "total": 1, "items": [{ "subject": "admin" }] }),
+    );
+
+    // 游标分页，没有过期时间的排在最后
+    let res = app.call(list("/api/v1/principals.json?limit=1&cursor=first&sort=expires_at")).await;
+    let page = assert::json(res, StatusCode::OK).await;
+    assert_eq!(page["items"][0]["subject"], "old");
+    assert!(page.get("page").is_none());
+    assert!(page["links"].get("prev").is_none());
+    let next = page["links"]["next"].as_str().unwrap().to_owned();
+    let res = app.call(list(&next)).await;
+    let page = assert::json(res, StatusCode::OK).await;
+    assert_eq!(page["items"][0]["subject"], "admin");
+    let prev = page["links"]["prev"].as_str().unwrap().to_owned();
+    let res = app.call(list(&prev)).await;
+    assert::json_includes(&assert::json(res, StatusCode::OK).await, &json!({ "items": [{ "subject": "old" }] }));
+
+    // 游标只能用于签发时的排序和过滤条件
+    let cursor = next.split("cursor=").nth(1).unwrap();
+    for query in ["sort=-expires_at", "sort=expires_at&filter%5Brole%5D=admin", ""] {
+        let res = app.call(list(&format!("/api/principals.json?cursor={cursor}&{query}"))).await;
+        let error = assert::json(res, StatusCode::BAD_REQUEST).await;
+        assert_eq!(error["details"][0]["field"], "cursor", "{query}");
+    }
+
+    for (query, field) in [
+        ("sort=roles", "sort"),
+        ("filter%5Btoken%5D=x", "filter[token]"),
+        ("limit=1000", "limit"),
+        ("page=0", "page"),
+        ("page=1&cursor=first", "cursor"),
+        ("cursor=zz", "cursor"),
+        ("cursor=o0", "cursor"),
+        ("offset=3", "offset"),
+    ] {
+        let res = app.call(list(&format!("/api/principals.json?{query}"))).await;
+        let error = assert::json(res, StatusCode::BAD_REQUEST).await;
+        assert_eq!(error["details"][0]["field"], field, "{query}");
+    }
+
+    // 列出调用方需要 admin 角色
+    let res = app.call(TestRequest::get().uri("/api/principals.json").insert_header(bearer("reader-token"))).await;
+    assert::json(res, StatusCode::FORBIDDEN).await;
+}
+
+#[actix_web::test]
+async fn selects_api_versions() {
+    let mut config = config();
+    config.api.deprecations.insert(
+        "v1".to_owned(),
+        DeprecationConfig {
+            deprecated_at: "Thu, 01 Jan 2026 00:00:00 GMT".to_owned(),
+            sunset: Some("Fri, 01 Jan 2100 00:00:00 GMT".to_owned()),
+            link: Some("https://example.com/migrate".to_owned()),
+        },
+    );
+    let app = app(config.clone()).await;
+    let index = |uri: &str| TestRequest::get().uri(uri).insert_header(bearer("reader-token"));
+
+    // 不需要认证
+    let list = assert::json(app.get("/api").await, StatusCode::OK).await;
+    assert_eq!(
+        list,
+        json!({
+            "default": "v1",
+            "versions": [
+                {
+                    "version": "v1",
+                    "url": "/api/v1",
+                    "status": "deprecated",
+                    "deprecated_at": "Thu, 01 Jan 2026 00:00:00 GMT",
+                    "sunset": "Fri, 01 Jan 2100 00:00:00 GMT",
+                    "link": "https://example.com/migrate",
+                },
+                { "version": "v2", "url": "/api/v2", "status": "current" },
+            ],
+        })
+    );
+
+    // 没有指定版本时使用默认的 v1，弃用的版本带上弃用信息
+    let res = app.call(index("/api/index.json")).await;
+    let headers = res.headers().clone();
+    assert_eq!(assert::json(res, StatusCode::OK).await, json!({ "code": 0, "message": "Ok" }));
+    assert_eq!(assert::header(&headers, "api-version"), "v1");
+    assert_eq!(assert::header(&headers, "deprecation"), "@1767225600");
+    assert_eq!(assert::header(&headers, "sunset"), "Fri, 01 Jan 2100 00:00:00 GMT");
+    let links: Vec<_> = headers.get_all(header::LINK).map(|v| v.to_str().unwrap()).collect();
+    assert_eq!(
+        links,
+        ["</api/v2>; rel=\"successor-version\"", "<https://example.com/migrate>; rel=\"deprecation\""]
+    );
+    let vary: Vec<_> = headers.get_all(header::VARY).map(|v| v.to_str().unwrap()).collect();
+    assert_eq!(vary, ["accept", "api-version"]);
+
+    // URL、请求头和 Accept 参数都可以选择 v2
+    for req in [
+        index("/api/v2/index.json"),
+        index("/api/index.json").insert_header(("api-version", "2")),
+        index("/api/index.json").insert_header((header::ACCEPT, "application/json; version=v2")),
+        index("/api/v2/index.json").insert_header(("api-version", "v2")),
+    ] {
+        let res = app.call(req).await;
+        assert_eq!(assert::header(res.headers(), "api-version"), "v2");
+        assert!(res.headers().get("deprecation").is_none());
+        assert_eq!(assert::json(res, StatusCode::OK).await, json!({ "status": "ok" }));
+    }
+
+    let res = app.call(index("/api/v2/index.json").insert_header(("api-version", "1"))).await;
+    assert_eq!(assert::json(res, StatusCode::BAD_REQUEST).await["code"], "bad_request");
+    let res = app.call(index("/api/index.json").insert_header(("api-version", "3"))).await;
+    assert_eq!(assert::json(res, StatusCode::BAD_REQUEST).await["code"], "unsupported_version");
+    let res = app.call(index("/api/v3/index.json")).await;
+    assert_eq!(assert::json(res, StatusCode::NOT_FOUND).await["code"], "unsupported_version");
+
+    // 过了 Sunset 时间返回 410
+    config.api.deprecations.get_mut("v1").unwrap().sunset = Some("Sat, 01 Jan 2000 00:00:00 GMT".to_owned());
+    config.api.default_version = "v2".to_owned();
+    let app = self::app(config).await;
+    let res = app.call(index("/api/v1/index.json")).await;
+    assert_eq!(assert::header(res.headers(), "sunset"), "Sat, 01 Jan 2000 00:00:00 GMT");
+    assert_eq!(assert::json(res, StatusCode::GONE).await["code"], "version_sunset");
+    let res = app.call(index("/api/index.json")).await;
+    assert_eq!(assert::json(res, StatusCode::OK).await, json!({ "status": "ok" }));
+}
+
+#[actix_web::test]
+async fn uploads_and_downloads_files() {
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n0000IHDR";
+    const PNG_SHA256: &str = "ed8bac0d547f074905c590874b0f4e557f4b82e52c4aea6d39d425326d3a6a12";
+    let mut config = config();
+    config.files.max_file_bytes = 64;
+    config.files.max_total_bytes = 100;
+    let dir = config.files.dir.clone();
+    let app = app(config.clone()).await;
+    let get = |uri: &str| TestRequest::get().uri(uri).insert_header(bearer("reader-token"));
+    let delete = |uri: &str, token| TestRequest::delete().uri(uri).insert_header(bearer(token));
+
+    // 按内容识别类型，不相信客户端声明的类型；普通字段忽略
+    let req = upload(
+        "uploader-token",
+        &[
+            ("comment", None, "text/plain", b"ignored"),
+            ("file", Some("photos/cat.png"), "text/plain", PNG),
+            ("file", Some("notes.txt"), "application/octet-stream", "你好".as_bytes()),
+        ],
+    );
+    let res = app.call(req).await;
+    assert!(res.headers().get(header::LOCATION).is_none());
+    let uploaded = assert::json(res, StatusCode::CREATED).await;
+    assert::json_includes(
+        &uploaded,
+        &json!({ "files": [
+            {
+                "name": "cat.png",
+                "content_type": "image/png",
+                "declared_type": "text/plain",
+                "size": 16,
+                "sha256": PNG_SHA256,
+                "owner": "uploader",
+            },
+            { "name": "notes.txt", "content_type": "text/plain; charset=utf-8", "size": 6 },
+        ] }),
+    );
+    let png = uploaded["files"][0].clone();
+    let id = png["id"].as_str().unwrap();
+    assert!(dir.join(id).is_file() && dir.join(format!("{id}.json")).is_file());
+
+    // 单个文件时返回 Location
+    let res = app.call(upload("admin-token", &[("file", Some("a.bin"), "application/octet-stream", b"\0\x01")])).await;
+    assert!(assert::header(res.headers(), "location").starts_with("/api/v1/files/"));
+    assert::json(res, StatusCode::CREATED).await;
+
+    // 下载和 Range
+    let res = app.call(get(&format!("/api/files/{id}"))).await;
+    assert_eq!(assert::header(res.headers(), "content-type"), "image/png");
+    assert_eq!(assert::header(res.headers(), "content-disposition"), "attachment; filename=\"cat.png\"");
+    assert_eq!(assert::header(res.headers(), "x-checksum-sha256"), png["sha256"]);
+    assert_eq!(assert::body(res, StatusCode::OK).await, PNG);
+
+    let res = app.call(get(&format!("/api/v2/files/{id}")).insert_header((header::RANGE, "bytes=1-3"))).await;
+    assert_eq!(assert::header(res.headers(), "content-range"), "bytes 1-3/16");
+    assert_eq!(assert::body(res, StatusCode::PARTIAL_CONTENT).await, "PNG");
+
+    let res = app.call(get(&format!("/api/files/{id}/meta"))).await;
+    assert_eq!(assert::json(res, StatusCode::OK).await, png);
+
+    let res = app.call(get("/api/files?filter%5Bcontent_type%5D=image/png")).await;
+    assert::json_includes(
+        &assert::json(res, StatusCode::OK).await,
+        &json!({ "total": 1, "items": [{ "id": id }] }),
+    );
+
+    // 超过单个文件或一次请求的上限时什么都不保存
+    let big = [b'a'; 65];
+    let res = app.call(upload("uploader-token", &[("file", Some("big.txt"), "text/plain", &big)])).await;
+    assert_eq!(assert::json(res, StatusCode::PAYLOAD_TOO_LARGE).await["code"], "file_too_large");
+    let half = [b'a'; 50];
+    let parts = ["a.txt", "b.txt", "c.txt"].map(|name| ("file", Some(name), "text/plain", &half[..]));
+    let res = app.call(upload("uploader-token", &parts)).await;
+    assert_eq!(assert::json(res, StatusCode::PAYLOAD_TOO_LARGE).await["code"], "upload_too_large");
+    let res = app.call(get("/api/files")).await;
+    assert_eq!(assert::json(res, StatusCode::OK).await["total"], 3);
+
+    let res = app.call(upload("uploader-token", &[("comment", None, "text/plain", b"no file")])).await;
+    assert::json(res, StatusCode::BAD_REQUEST).await;
+    let res = app
+        .call(TestRequest::post().uri("/api/files").insert_header(bearer("uploader-token")).set_json(json!({})))
+        .await;
+    assert::json(res, StatusCode::UNSUPPORTED_MEDIA_TYPE).await;
+    let res = app.call(upload("reader-token", &[("file", Some("a.txt"), "text/plain", b"a")])).await;
+    assert::json(res, StatusCode::FORBIDDEN).await;
+
+    // 重启后从目录中加载元数据
+    let app = self::app(config.clone()).await;
+    let res = app.call(get("/api/files?sort=name")).await;
+    let names: Vec<_> = assert::json(res, StatusCode::OK).await["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|f| f["name"].as_str().unwrap().to_owned())
+        .collect();
+    assert_eq!(names, ["a.bin", "cat.png", "notes.txt"]);
+
+    // 只有上传者或 admin 可以删除
+    let res = app.call(get("/api/files?filter%5Bname%5D=a.bin")).await;
+    let a_bin = assert::json(res, StatusCode::OK).await["items"][0]["id"].as_str().unwrap().to_owned();
+    let res = app.call(delete(&format!("/api/files/{a_bin}"), "uploader-token")).await;
+    assert::json(res, StatusCode::FORBIDDEN).await;
+    let res = app.call(delete(&format!("/api/files/{id}"), "uploader-token")).await;
+    assert::body(res, StatusCode::NO_CONTENT).await;
+    assert!(!dir.join(id).exists() && !dir.join(format!("{id}.json")).exists());
+    let res = app.call(get(&format!("/api/files/{id}"))).await;
+    assert::json(res, StatusCode::NOT_FOUND).await;
+
+    // 删除数据文件失败时保留索引和元数据，可以重新删除
+    fs::remove_file(dir.join(&a_bin)).unwrap();
+    fs::create_dir(dir.join(&a_bin)).unwrap();
+    let res = app.call(delete(&format!("/api/files/{a_bin}"), "admin-token")).await;
+    assert::json(res, StatusCode::INTERNAL_SERVER_ERROR).await;
+    assert!(dir.join(format!("{a_bin}.json")).exists());
+    let res = app.call(get(&format!("/api/files/{a_bin}/meta"))).await;
+    assert::json(res, StatusCode::OK).await;
+    fs::remove_dir(dir.join(&a_bin)).unwrap();
+    fs::write(dir.join(&a_bin), b"a").unwrap();
+    let res = app.call(delete(&format!("/api/files/{a_bin}"), "admin-token")).await;
+    assert::body(res, StatusCode::NO_CONTENT).await;
+
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[actix_web::test]
+async fn pages_files_by_cursor_while_inserting() {
+    let config = config();
+    let dir = config.files.dir.clone();
+    let app = app(config).await;
+    let list = |uri: &str| TestRequest::get().uri(uri).insert_header(bearer("reader-token"));
+    let names = |page: &Value| -> Vec<String> {
+        page["items"].as_array().unwrap().iter().map(|f| f["name"].as_str().unwrap().to_owned()).collect()
+    };
+    for name in ["a.txt", "b.txt", "c.txt"] {
+        let res = app.call(upload("uploader-token", &[("file", Some(name), "text/plain", b"x")])).await;
+        assert::json(res, StatusCode::CREATED).await;
+    }
+
+    let res = app.call(list("/api/files?sort=name&limit=2&cursor=first")).await;
+    let page = assert::json(res, StatusCode::OK).await;
+    assert_eq!(names(&page), ["a.txt", "b.txt"]);
+
+    // 两页之间插入排在前面的条目，下一页不会重复 b.txt
+    let res = app.call(upload("uploader-token", &[("file", Some("aa.txt"), "text/plain", b"x")])).await;
+    assert::json(res, StatusCode::CREATED).await;
+    let res = app.call(list(page["links"]["next"].as_str().unwrap())).await;
+    let page = assert::json(res, StatusCode::OK).await;
+    assert_eq!(names(&page), ["c.txt"]);
+    assert_eq!(page["total"], 4);
+    assert!(page["links"].get("next").is_none());
+
+    let res = app.call(list(page["links"]["prev"].as_str().unwrap())).await;
+    assert_eq!(names(&assert::json(res, StatusCode::OK).await), ["aa.txt", "b.txt"]);
+
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[actix_web::test]
+async fn negotiates_api_formats() {
+    let app = app(config()).await;
+
+    let res = app
+        .call(
+            TestRequest::get()
+                .uri("/api/index.json")
+                .insert_header(bearer("admin-token"))
+                .insert_header((header::ACCEPT, "application/yaml")),
+        )
+        .await;
+    assert_eq!(assert::header(res.headers(), "content-type"), "application/yaml");
+    assert_eq!(assert::header(res.headers(), "vary"), "accept");
+    let body = assert::body(res, StatusCode::OK).await;
+    assert!(String::from_utf8_lossy(&body).contains("message: Ok"));
+
+    let res = app
+        .call(
+            TestRequest::post()
+                .uri("/api/users/5.json")
+                .insert_header(bearer("admin-token"))
+                .insert_header((header::CONTENT_TYPE, "application/yaml"))
+                .set_payload("name: deploy\nmode: async\ntags: []\noptions: { retries: 0, timeout_ms: 100 }\n"),
+        )
+        .await;
+    assert::json_includes(&assert::json(res, StatusCode::OK).await, &json!({ "request": { "mode": "async" } }));
+
+    let res = app
+        .call(
+            TestRequest::get()
+                .uri("/api/index.json")
+                .insert_header(bearer("admin-token"))
+                .insert_header((header::ACCEPT, "text/html")),
+        )
+        .await;
+    assert::json_includes(&assert::json(res, StatusCode::NOT_ACCEPTABLE).await, &json!({ "code": "not_acceptable" }));
+
+    // 拆成多个 `Accept` 头时一起参与协商
+    let res = app
+        .call(
+            TestRequest::get()
+                .uri("/api/index.json")
+                .insert_header(bearer("admin-token"))
+                .append_header((header::ACCEPT, "text/html"))
+                .append_header((header::ACCEPT, "application/cbor;q=0.5")),
+        )
+        .await;
+    assert_eq!(assert::header(res.headers(), "content-type"), "application/cbor");
+    assert::body(res, StatusCode::OK).await;
+
+    let res = app
+        .call(
+            TestRequest::post()
+                .uri("/api/users/5.json")
+                .insert_header(bearer("admin-token"))
+                .insert_header((header::CONTENT_TYPE, "application/xml"))
+                .set_payload("<x/>"),
+        )
+        .await;
+    assert::json(res, StatusCode::UNSUPPORTED_MEDIA_TYPE).await;
+}
+
+#[actix_web::test]
+async fn serves_openapi_docs() {
+    let app = app(config()).await;
+
+    let doc = assert::json(app.get("/openapi.json").await, StatusCode::OK).await;
+    for path in [
+        "/hello/{name}",
+        "/error",
+        "/api",
+        "/api/v1/index.json",
+        "/api/v2/index.json",
+        "/api/v2/me.json",
+        "/api/v2/{controller}/{action}.json",
+    ] {
+        assert!(doc["paths"].get(path).is_some(), "missing {path}");
+    }
+
+    let body = assert::body(app.get("/swagger-ui/").await, StatusCode::OK).await;
+    assert!(String::from_utf8_lossy(&body).contains("swagger"));
+
+    let mut config = config();
+    config.features.swagger_ui = false;
+    let without_docs = self::app(config).await;
+    assert::json(without_docs.get("/openapi.json").await, StatusCode::NOT_FOUND).await;
+}
+
+#[actix_web::test]
+async fn allows_configured_cors_origins() {
+    let mut config = config();
+    config.cors.enabled = true;
+    config.cors.allowed_origins = vec!["http://localhost:3000".to_owned()];
+    let app = app(config).await;
+
+    let res = app
+        .call(
+            TestRequest::default()
+                .method(actix_web::http::Method::OPTIONS)
+                .uri("/api/index.json")
+                .insert_header((header::ORIGIN, "http://localhost:3000"))
+                .insert_header((header::ACCESS_CONTROL_REQUEST_METHOD, "GET"))
+                .insert_header((header::ACCESS_CONTROL_REQUEST_HEADERS, "authorization")),
+        )
+        .await;
+    assert_eq!(assert::header(res.headers(), "access-control-allow-origin"), "http://localhost:3000");
+    assert::body(res, StatusCode::OK).await;
+
+    let res = app
+        .try_call(
+            TestRequest::get()
+                .uri("/hello/world")
+                .insert_header((header::ORIGIN, "http://evil.example")),
+        )
+        .await;
+    assert!(res.map_or(true, |res| res.headers().get("access-control-allow-origin").is_none()));
+}
+
+#[actix_web::test]
+async fn serves_health_endpoints() {
+    let app = app(config()).await;
+    assert::json_includes(&assert::json(app.get("/healthz").await, StatusCode::OK).await, &json!({ "status": "ok" }));
+    assert::json_includes(&assert::json(app.get("/readyz").await, StatusCode::OK).await, &json!({ "status": "ok" }));
+    assert::json_includes(&assert::json(app.get("/version").await, StatusCode::OK).await, &json!({ "name": "web1" }));
+}
+
+#[actix_web::test]
+async fn readyz_checks_upstreams() {
+    let upstream = TestServer::start(|| actix_web::App::new().configure(Health::new(health::build_info!()).configure()));
+
+    let mut config = config();
+    config.health.timeout_ms = 500;
+    config.health.upstreams.insert("up".to_owned(), upstream.url("/healthz"));
+    config.health.upstreams.insert("down".to_owned(), "http://127.0.0.1:1/healthz".to_owned());
+    let app = app(config).await;
+
+    let report = assert::json(app.get("/readyz").await, StatusCode::SERVICE_UNAVAILABLE).await;
+    assert::json_includes(
+        &report,
+        &json!({ "status": "unavailable", "checks": { "up": { "status": "ok" }, "down": { "status": "error" } } }),
+    );
+    upstream.stop().await;
+}
@@ -0,0 +1,74 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use actix::*;
+use actix_files::{Files, NamedFile};
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceFactory, ServiceRequest, ServiceResponse},
+    middleware::Logger, web, App, Error, HttpRequest, HttpResponse, Responder,
+};
+use actix_web_actors::ws;
+
+pub mod server;
+pub mod session;
+
+// 前端页面
+async fn index() -> impl Responder {
+    NamedFile::open_async("./wschatsrv1/static/index.html").await.unwrap()
+}
+
+/// 聊天 websocket 入口点路由
+async fn chat_route(
+    req: HttpRequest,
+    stream: web::Payload,
+    srv: web::Data<Addr<server::ChatServer>>,
+) -> Result<HttpResponse, Error> {
+    ws::start(
+        session::WsChatSession {
+            id: 0,
+            hb: Instant::now(),
+            room: "main".to_owned(),
+            name: None,
+            addr: srv.get_ref().clone(),
+        },
+        &req,
+        stream,
+    )
+}
+
+
+/// 显示状态
+async fn get_count(count: web::Data<AtomicUsize>) -> impl Responder {
+    let current_count = count.load(Ordering::SeqCst);
+    format!("Visitors: {current_count}")
+}
+
+/// 每个 worker 调用一次，`visitors` 和 `chat_server` 在 `HttpServer::new` 之外创建。
+/// 健康检查路由由调用方挂载。
+pub fn app(
+    visitors: Arc<AtomicUsize>,
+    chat_server: Addr<server::ChatServer>,
+) -> App<
+    impl ServiceFactory<
+        ServiceRequest,
+        Config = (),
+        Response = ServiceResponse<impl MessageBody>,
+        Error = Error,
+        InitError = (),
+    >,
+> {
+    App::new()
+        .app_data(web::Data::from(visitors))
+        .app_data(web::Data::new(chat_server))
+        .service(web::resource("/").to(index))
+        .route("/count", web::get().to(get_count))
+        .route("/ws", web::get().to(chat_route))
+        .service(Files::new("/static", "./wschatsrv1/static"))
+        .wrap(Logger::default())
+}
@@ -8,6 +8,7 @@ use std::{
 
 use actix::prelude::*;
 use rand::{self, rngs::ThreadRng, Rng};
+use webdemo_common::health;
 
 /// 服务器指令消息
 #[derive(Message)]
@@ -124,7 +125,7 @@ impl Handler<Connect> for ChatServer {
         // 自动加入主房间
         self.rooms
             .entry("main".to_owned())
-            .or_insert_with(HashSet::new)
+            .or_default()
             .insert(id);
 
         let count = self.visitor_count.fetch_add(1, Ordering::SeqCst);
@@ -190,6 +191,13 @@ impl Handler<ListRooms> for ChatServer {
     }
 }
 
+/// 就绪检查，能处理说明邮箱没有堵塞
+impl Handler<health::Ping> for ChatServer {
+    type Result = ();
+
+    fn handle(&mut self, _: health::Ping, _: &mut Context<Self>) {}
+}
+
 
 
 /// 处理加入消息。断开老房间，加入新房间。
@@ -214,7 +222,7 @@ impl Handler<Join> for ChatServer {
         // 加入新房间。
         self.rooms
             .entry(name.clone())
-            .or_insert_with(HashSet::new)
+            .or_default()
             .insert(id);
 
         // 发送消息给房间里的其他用户
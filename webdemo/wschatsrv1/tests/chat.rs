@@ -0,0 +1,156 @@
+//! wschatsrv1 集成测试：页面、访问计数、健康检查和每个聊天命令。
+//! 每个用例启动自己的 `ChatServer`，互不影响。
+
+use std::{
+    sync::{atomic::AtomicUsize, Arc},
+    time::Duration,
+};
+
+use actix::{Actor as _, Addr};
+use actix_web::http::StatusCode;
+use serde_json::json;
+use webdemo_common::health::{self, Health};
+use webdemo_testing::{assert, enter_workspace, TestApp, TestServer, WsClient};
+use wschatsrv1::server::ChatServer;
+
+/// 确认没有消息时等待的时间
+const QUIET: Duration = Duration::from_millis(300);
+
+fn chat_server() -> (Arc<AtomicUsize>, Addr<ChatServer>) {
+    let visitors = Arc::new(AtomicUsize::new(0));
+    let server = ChatServer::new(visitors.clone()).start();
+    (visitors, server)
+}
+
+fn start() -> TestServer {
+    let (visitors, chat) = chat_server();
+    TestServer::start(move || wschatsrv1::app(visitors.clone(), chat.clone()))
+}
+
+/// 连接并读掉进入主房间时的访问人数通知
+async fn join(server: &TestServer, visitors_before: usize) -> WsClient {
+    let mut ws = server.ws("/ws").await;
+    assert_eq!(ws.recv_text().await, format!("Total visitors {visitors_before}"));
+    ws
+}
+
+#[actix_web::test]
+async fn serves_pages_and_static_files() {
+    enter_workspace();
+    let (visitors, chat) = chat_server();
+    let app = TestApp::new(wschatsrv1::app(visitors, chat)).await;
+
+    let res = app.get("/").await;
+    assert!(assert::header(res.headers(), "content-type").starts_with("text/html"));
+    assert::body(res, StatusCode::OK).await;
+
+    let res = app.get("/static/index.html").await;
+    assert::body(res, StatusCode::OK).await;
+
+    let res = app.get("/static/missing.js").await;
+    assert::body(res, StatusCode::NOT_FOUND).await;
+}
+
+#[actix_web::test]
+async fn readyz_checks_chat_server() {
+    let (visitors, chat) = chat_server();
+    let health = Health::new(health::build_info!()).check_actor("chat_server", chat.clone());
+    let app = TestApp::new(wschatsrv1::app(visitors, chat).configure(health.configure())).await;
+
+    let res = app.get("/readyz").await;
+    assert::json_includes(
+        &assert::json(res, StatusCode::OK).await,
+        &json!({ "status": "ok", "checks": { "chat_server": { "status": "ok" } } }),
+    );
+
+    let res = app.get("/version").await;
+    assert::json_includes(&assert::json(res, StatusCode::OK).await, &json!({ "name": "wschatsrv1" }));
+}
+
+#[actix_web::test]
+async fn counts_visitors() {
+    let server = start();
+    let mut count = server.get("/count").send().await.unwrap();
+    assert_eq!(assert::client_body(&mut count, StatusCode::OK).await, "Visitors: 0");
+
+    let mut alice = join(&server, 0).await;
+    let bob = join(&server, 1).await;
+    alice.expect_texts(&["Someone joined", "Total visitors 1"]).await;
+
+    let mut count = server.get("/count").send().await.unwrap();
+    assert_eq!(assert::client_body(&mut count, StatusCode::OK).await, "Visitors: 2");
+
+    bob.close().await;
+    assert_eq!(alice.recv_text().await, "Someone disconnected");
+    alice.close().await;
+    server.stop().await;
+}
+
+#[actix_web::test]
+async fn broadcasts_messages_with_name() {
+    let server = start();
+    let mut alice = join(&server, 0).await;
+    let mut bob = join(&server, 1).await;
+    alice.expect_texts(&["Someone joined", "Total visitors 1"]).await;
+
+    bob.send_text("hello without a name").await;
+    assert_eq!(alice.recv_text().await, "hello without a name");
+
+    alice.send_text("/name alice").await;
+    alice.send_text("  hi bob  ").await;
+    assert_eq!(bob.recv_text().await, "alice: hi bob");
+
+    // 发送者自己收不到
+    alice.expect_silence(QUIET).await;
+    server.stop().await;
+}
+
+#[actix_web::test]
+async fn joins_rooms_and_lists_them() {
+    let server = start();
+    let mut alice = join(&server, 0).await;
+    let mut bob = join(&server, 1).await;
+    alice.expect_texts(&["Someone joined", "Total visitors 1"]).await;
+
+    alice.send_text("/list").await;
+    assert_eq!(alice.recv_text().await, "main");
+
+    alice.send_text("/join rust").await;
+    assert_eq!(alice.recv_text().await, "joined");
+    assert_eq!(bob.recv_text().await, "Someone disconnected");
+
+    alice.send_text("/list").await;
+    let mut rooms = vec![alice.recv_text().await, alice.recv_text().await];
+    rooms.sort();
+    assert_eq!(rooms, ["main", "rust"]);
+
+    // 不同房间之间收不到消息
+    bob.send_text("anyone in main?").await;
+    alice.expect_silence(QUIET).await;
+
+    bob.send_text("/join rust").await;
+    assert_eq!(bob.recv_text().await, "joined");
+    assert_eq!(alice.recv_text().await, "Someone connected");
+
+    bob.send_text("welcome").await;
+    assert_eq!(alice.recv_text().await, "welcome");
+    server.stop().await;
+}
+
+#[actix_web::test]
+async fn reports_command_errors() {
+    let server = start();
+    let mut ws = join(&server, 0).await;
+
+    ws.send_text("/join").await;
+    assert_eq!(ws.recv_text().await, "!!! room name is required");
+
+    ws.send_text("/name").await;
+    assert_eq!(ws.recv_text().await, "!!! name is required");
+
+    ws.send_text("/shout hello").await;
+    assert_eq!(ws.recv_text().await, "!!! unknown command: \"/shout hello\"");
+
+    ws.close().await;
+    server.stop().await;
+}
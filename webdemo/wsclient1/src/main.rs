@@ -32,7 +32,7 @@ async fn main() {
     });
 
     let (res, mut ws) = awc::Client::new()
-        .ws("ws://127.0.0.1:48080/ws")
+        .ws("ws://127.0.0.1:48081/ws")
         .connect()
         .await
         .unwrap();
@@ -0,0 +1,39 @@
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceFactory, ServiceRequest, ServiceResponse},
+    middleware, web, App, Error, HttpRequest, HttpResponse, Responder,
+};
+use actix_web_actors::ws;
+use actix_files::NamedFile;
+
+mod server;
+use self::server::MyWebSocket;
+
+// 前端页面
+async fn index() -> impl Responder {
+    NamedFile::open_async("./wsserver1/static/index.html").await.unwrap()
+}
+
+/// WebSocket `MyWebSocket` actor.
+async fn echo_ws(req: HttpRequest, stream: web::Payload) -> Result<HttpResponse, Error> {
+    ws::start(MyWebSocket::new(), &req, stream)
+}
+
+/// 每个 worker 调用一次，健康检查路由由调用方挂载
+pub fn app() -> App<
+    impl ServiceFactory<
+        ServiceRequest,
+        Config = (),
+        Response = ServiceResponse<impl MessageBody>,
+        Error = Error,
+        InitError = (),
+    >,
+> {
+    App::new()
+        // WebSocket UI HTML file
+        .service(web::resource("/").to(index))
+        // websocket route
+        .service(web::resource("/ws").route(web::get().to(echo_ws)))
+        // enable logger
+        .wrap(middleware::Logger::default())
+}
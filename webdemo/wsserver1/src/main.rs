@@ -1,38 +1,17 @@
-use actix_web::{middleware, web, App, Error, HttpRequest, HttpResponse, HttpServer, Responder};
-use actix_web_actors::ws;
-use actix_files::NamedFile;
-
-mod server;
-use self::server::MyWebSocket;
-
-// 前端页面
-async fn index() -> impl Responder {
-    NamedFile::open_async("./wsserver1/static/index.html").await.unwrap()
-}
-
-/// WebSocket `MyWebSocket` actor.
-async fn echo_ws(req: HttpRequest, stream: web::Payload) -> Result<HttpResponse, Error> {
-    ws::start(MyWebSocket::new(), &req, stream)
-}
+use webdemo_common::{health, BaseConfig, Loader, Server, ServerArgs};
 
 #[actix_web::main]
-async fn main()  -> std::io::Result<()> {
-    env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
+async fn main() {
+    if let Err(e) = run().await {
+        e.exit("wsserver1");
+    }
+}
 
-    let port = 48080;
-    log::info!("starting HTTP server at http://localhost:{:?}", port);
+async fn run() -> Result<(), webdemo_common::Error> {
+    let args = ServerArgs::parse("wsserver1");
+    let config: BaseConfig = Loader::new("wsserver1")
+        .defaults(BaseConfig::new("0.0.0.0:48081"))
+        .load(&args)?;
 
-    HttpServer::new(|| {
-        App::new()
-            // WebSocket UI HTML file
-            .service(web::resource("/").to(index))
-            // websocket route
-            .service(web::resource("/ws").route(web::get().to(echo_ws)))
-            // enable logger
-            .wrap(middleware::Logger::default())
-    })
-    .workers(2)
-    .bind(("0.0.0.0", port))?
-    .run()
-    .await
-}
\ No newline at end of file
+    Server::new(&config, health::build_info!())?.run(wsserver1::app).await
+}
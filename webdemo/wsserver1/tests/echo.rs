@@ -0,0 +1,81 @@
+//! wsserver1 集成测试：页面、健康检查和 WebSocket 回显。
+
+use std::time::Duration;
+
+use actix_web::{http::StatusCode, test::TestRequest};
+use awc::ws::Frame;
+use serde_json::json;
+use webdemo_common::health::{self, Health};
+use webdemo_testing::{assert, enter_workspace, TestApp, TestServer};
+
+#[actix_web::test]
+async fn serves_index_page() {
+    enter_workspace();
+    let app = TestApp::new(wsserver1::app()).await;
+
+    let res = app.get("/").await;
+    let content_type = assert::header(res.headers(), "content-type").to_owned();
+    let body = assert::body(res, StatusCode::OK).await;
+    assert!(content_type.starts_with("text/html"), "{content_type}");
+    assert!(String::from_utf8_lossy(&body).contains("WebSocket"));
+}
+
+#[actix_web::test]
+async fn serves_health_endpoints() {
+    let health = Health::new(health::build_info!());
+    let app = TestApp::new(wsserver1::app().configure(health.configure())).await;
+
+    let res = app.get("/healthz").await;
+    assert::json_includes(&assert::json(res, StatusCode::OK).await, &json!({ "status": "ok" }));
+
+    let res = app.get("/readyz").await;
+    assert::json_includes(&assert::json(res, StatusCode::OK).await, &json!({ "status": "ok", "checks": {} }));
+
+    let res = app.get("/version").await;
+    assert::json_includes(&assert::json(res, StatusCode::OK).await, &json!({ "name": "wsserver1" }));
+}
+
+#[actix_web::test]
+async fn rejects_plain_http_on_ws_route() {
+    let app = TestApp::new(wsserver1::app()).await;
+
+    let res = app.get("/ws").await;
+    assert::body(res, StatusCode::BAD_REQUEST).await;
+
+    let res = app.call(TestRequest::post().uri("/ws")).await;
+    assert::body(res, StatusCode::METHOD_NOT_ALLOWED).await;
+}
+
+#[actix_web::test]
+async fn echoes_text_and_binary() {
+    let server = TestServer::start(wsserver1::app);
+    let mut ws = server.ws("/ws").await;
+
+    ws.send_text("hello").await;
+    assert_eq!(ws.recv_text().await, "hello");
+
+    ws.send_binary(&b"\x00\x01binary"[..]).await;
+    match ws.recv().await {
+        Frame::Binary(bytes) => assert_eq!(&bytes[..], b"\x00\x01binary"),
+        frame => panic!("expected binary frame, got {frame:?}"),
+    }
+
+    ws.close().await;
+    server.stop().await;
+}
+
+#[actix_web::test]
+async fn answers_ping_with_pong() {
+    let server = TestServer::start(wsserver1::app);
+    let mut ws = server.ws("/ws").await;
+
+    ws.ping(&b"heartbeat"[..]).await;
+    match ws.recv().await {
+        Frame::Pong(bytes) => assert_eq!(&bytes[..], b"heartbeat"),
+        frame => panic!("expected pong, got {frame:?}"),
+    }
+    ws.expect_silence(Duration::from_millis(200)).await;
+
+    ws.close().await;
+    server.stop().await;
+}
@@ -7,6 +7,7 @@ async fn main() -> std::io::Result<()> {
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
 
     let hello_config = app::hello::make_config();
+    let health = health::Health::new(health::build_info!());
     let port = 44444;
     log::info!("starting HTTP server at http://localhost:{:?}", port);
 
@@ -17,6 +18,7 @@ async fn main() -> std::io::Result<()> {
         log::info!("on new.");
         App::new()
             .wrap(middleware::Logger::default())
+            .configure(health.configure())
             // configure 不支持 arc ，类型又解不出来，只能直接多套一层闭包（只是为了让类型对上。。）了。再调一次。
             .service(web::scope("/hello").configure(|cfg| {hello_config_arc(cfg)}))
     })